@@ -19,8 +19,9 @@ use zingo_testutils::{
     paths::get_cargo_manifest_dir, scenarios,
 };
 
-use zingoconfig::{ChainType, RegtestNetwork, ZingoConfig, MAX_REORG};
+use zingoconfig::{ChainType, RegtestNetwork, SyncMode, ZingoConfig, MAX_REORG};
 use zingolib::{
+    blaze::sync_events::SyncEvent,
     check_client_balances, get_base_address,
     lightclient::{LightClient, PoolBalances},
     testvectors::{
@@ -34,7 +35,7 @@ use zingolib::{
             extended_transparent::ExtendedPrivKey,
             unified::{Capability, WalletCapability},
         },
-        LightWallet, Pool,
+        LightWallet, Pool, WalletBase,
     },
 };
 
@@ -157,6 +158,136 @@ mod fast {
         assert!(postshield_utxos[0].unconfirmed_spent.is_some());
     }
     #[tokio::test]
+    async fn sync_emits_events() {
+        let (regtest_manager, _cph, faucet, recipient) =
+            scenarios::faucet_recipient_default().await;
+        let mut events = recipient.subscribe_sync_events().await;
+        let txid = zingo_testutils::send_value_between_clients_and_sync(
+            &regtest_manager,
+            &faucet,
+            &recipient,
+            100_000,
+            "sapling",
+        )
+        .await
+        .unwrap();
+
+        let mut received = vec![];
+        while let Ok(event) = events.try_recv() {
+            received.push(event);
+        }
+        assert!(matches!(
+            received.first(),
+            Some(SyncEvent::BatchStarted { .. })
+        ));
+        assert!(matches!(
+            received.last(),
+            Some(SyncEvent::BatchFinished { .. })
+        ));
+        assert!(received.iter().any(|event| matches!(
+            event,
+            SyncEvent::BlocksScanned { blocks_done, blocks_total, .. } if blocks_done == blocks_total
+        )));
+        assert!(received.iter().any(|event| matches!(
+            event,
+            SyncEvent::NoteFound { txid: found, pool: Pool::Sapling, .. } if found.to_string() == txid
+        )));
+        assert!(received.iter().any(|event| matches!(
+            event,
+            SyncEvent::TransactionDiscovered { txid: discovered, .. } if discovered.to_string() == txid
+        )));
+        assert!(received
+            .iter()
+            .any(|event| matches!(event, SyncEvent::WitnessesUpdated { .. })));
+    }
+    #[tokio::test]
+    async fn trial_decryption_threads_find_the_same_notes() {
+        let (regtest_manager, _cph, mut client_builder, regtest_network) =
+            scenarios::custom_clients_default().await;
+        let faucet = client_builder.build_faucet(false, regtest_network).await;
+        let recipient = client_builder
+            .build_client(HOSPITAL_MUSEUM_SEED.to_string(), 0, false, regtest_network)
+            .await;
+        increase_height_and_wait_for_client(&regtest_manager, &faucet, 1)
+            .await
+            .unwrap();
+        // Notes of both pools, over several blocks, some sharing a block
+        faucet
+            .do_send(vec![
+                (&get_base_address!(recipient, "sapling"), 10_000, None),
+                (&get_base_address!(recipient, "unified"), 20_000, None),
+            ])
+            .await
+            .unwrap();
+        increase_height_and_wait_for_client(&regtest_manager, &faucet, 1)
+            .await
+            .unwrap();
+        faucet
+            .do_send(vec![(
+                &get_base_address!(recipient, "unified"),
+                30_000,
+                None,
+            )])
+            .await
+            .unwrap();
+        increase_height_and_wait_for_client(&regtest_manager, &faucet, 1)
+            .await
+            .unwrap();
+        faucet
+            .do_send(vec![(
+                &get_base_address!(recipient, "sapling"),
+                40_000,
+                None,
+            )])
+            .await
+            .unwrap();
+        increase_height_and_wait_for_client(&regtest_manager, &faucet, 2)
+            .await
+            .unwrap();
+
+        let mut synced = vec![];
+        for threads in [1, 4] {
+            let mut config = client_builder.make_unique_data_dir_and_load_config(regtest_network);
+            config.trial_decryption_threads = threads;
+            let client = LightClient::create_from_wallet_base_async(
+                WalletBase::MnemonicPhrase(HOSPITAL_MUSEUM_SEED.to_string()),
+                &config,
+                0,
+                false,
+            )
+            .await
+            .unwrap();
+            client.do_sync(false).await.unwrap();
+
+            let txmds = client
+                .wallet
+                .transaction_context
+                .transaction_metadata_set
+                .read()
+                .await;
+            let trees = txmds.witness_trees.as_ref().unwrap();
+            let positions_and_roots = (
+                trees.witness_tree_sapling.marked_positions().unwrap(),
+                trees.witness_tree_orchard.marked_positions().unwrap(),
+                trees
+                    .witness_tree_sapling
+                    .root_at_checkpoint_depth(0)
+                    .unwrap(),
+                trees
+                    .witness_tree_orchard
+                    .root_at_checkpoint_depth(0)
+                    .unwrap(),
+            );
+            drop(txmds);
+            synced.push((client.do_list_notes(true).await, positions_and_roots));
+        }
+        assert_eq!(
+            synced[0].0["unspent_sapling_notes"].len() + synced[0].0["unspent_orchard_notes"].len(),
+            4
+        );
+        assert_eq!(synced[0], synced[1]);
+    }
+    #[tokio::test]
     async fn send_without_reorg_buffer_blocks_gives_correct_error() {
         let (_regtest_manager, _cph, faucet, mut recipient) =
             scenarios::faucet_recipient_default().await;
@@ -777,10 +908,150 @@ mod fast {
             .unwrap();
         faucet.do_shield(&[Pool::Transparent], None).await.unwrap();
     }
+    #[tokio::test]
+    async fn imported_key_is_scanned_on_import_then_syncs_with_the_wallet() {
+        let (regtest_manager, _cph, faucet, recipient) =
+            scenarios::faucet_recipient_default().await;
+        faucet
+            .do_send(vec![(
+                &get_base_address!(recipient, "unified"),
+                100_000,
+                None,
+            )])
+            .await
+            .unwrap();
+        increase_height_and_wait_for_client(&regtest_manager, &faucet, 1)
+            .await
+            .unwrap();
+        let imported_orchard_balance =
+            |imported_keys: JsonValue| imported_keys[0]["orchard_balance"].as_u64().unwrap();
+
+        // The blocks the faucet has synced are scanned for the key as it is imported
+        faucet
+            .do_import_ufvk(recipient.wallet.encoded_ufvk().unwrap(), 1)
+            .await
+            .unwrap();
+        assert_eq!(
+            imported_orchard_balance(faucet.do_list_imported_keys().await),
+            100_000
+        );
+
+        // Later blocks are scanned for it in the faucet's own sync
+        faucet
+            .do_send(vec![(
+                &get_base_address!(recipient, "unified"),
+                50_000,
+                None,
+            )])
+            .await
+            .unwrap();
+        increase_height_and_wait_for_client(&regtest_manager, &faucet, 1)
+            .await
+            .unwrap();
+        assert_eq!(
+            imported_orchard_balance(faucet.do_list_imported_keys().await),
+            150_000
+        );
+    }
 }
 mod slow {
     use super::*;
 
+    #[tokio::test]
+    async fn spend_before_sync_spends_at_the_tip_then_finds_history() {
+        let (regtest_manager, _cph, mut client_builder, regtest_network) =
+            scenarios::custom_clients_default().await;
+        let faucet = client_builder.build_faucet(false, regtest_network).await;
+        let recipient = client_builder
+            .build_client(HOSPITAL_MUSEUM_SEED.to_string(), 0, false, regtest_network)
+            .await;
+        increase_height_and_wait_for_client(&regtest_manager, &faucet, 1)
+            .await
+            .unwrap();
+        // History: a note received, then spent with change
+        faucet
+            .do_send(vec![(
+                &get_base_address!(recipient, "unified"),
+                100_000,
+                None,
+            )])
+            .await
+            .unwrap();
+        increase_height_and_wait_for_client(&regtest_manager, &recipient, 2)
+            .await
+            .unwrap();
+        recipient
+            .do_send(vec![(&get_base_address!(faucet, "unified"), 30_000, None)])
+            .await
+            .unwrap();
+        increase_height_and_wait_for_client(&regtest_manager, &recipient, 5)
+            .await
+            .unwrap();
+        // Regtest never completes a shard, so the restored wallet is told one ended here
+        let last_shard_end = u64::from(regtest_manager.get_current_height().unwrap());
+        increase_height_and_wait_for_client(&regtest_manager, &faucet, 1)
+            .await
+            .unwrap();
+        faucet
+            .do_send(vec![(
+                &get_base_address!(recipient, "unified"),
+                50_000,
+                None,
+            )])
+            .await
+            .unwrap();
+        increase_height_and_wait_for_client(&regtest_manager, &recipient, 2)
+            .await
+            .unwrap();
+
+        let mut config = client_builder.make_unique_data_dir_and_load_config(regtest_network);
+        config.sync_mode = SyncMode::SpendBeforeSync;
+        let restored = LightClient::create_from_wallet_base_async(
+            WalletBase::MnemonicPhrase(HOSPITAL_MUSEUM_SEED.to_string()),
+            &config,
+            1,
+            false,
+        )
+        .await
+        .unwrap();
+        *restored.wallet.subtree_root_ends.write().await =
+            [vec![last_shard_end], vec![last_shard_end]];
+
+        // The first batch is the tip range, which holds only the latest note
+        restored.interrupt_sync_after_batch(true).await;
+        restored.do_sync(false).await.unwrap();
+        assert!(!restored.wallet.unscanned_ranges.read().await.is_empty());
+        assert_eq!(
+            restored.do_balance().await.spendable_orchard_balance,
+            Some(50_000)
+        );
+        let tip_spend = restored
+            .do_send(vec![(&get_base_address!(faucet, "unified"), 20_000, None)])
+            .await
+            .unwrap();
+        increase_height_and_wait_for_client(&regtest_manager, &faucet, 1)
+            .await
+            .unwrap();
+
+        restored.interrupt_sync_after_batch(false).await;
+        restored.do_sync(false).await.unwrap();
+        recipient.do_sync(false).await.unwrap();
+        assert!(restored.wallet.unscanned_ranges.read().await.is_empty());
+        assert_eq!(restored.do_balance().await, recipient.do_balance().await);
+        let notes = restored.do_list_notes(true).await;
+        let spent = notes["spent_orchard_notes"]
+            .members()
+            .map(|note| (note["value"].as_u64().unwrap(), note["spent"].clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(spent.len(), 2);
+        assert!(spent
+            .iter()
+            .any(|(value, spent)| *value == 50_000 && *spent == tip_spend));
+        assert!(spent
+            .iter()
+            .any(|(value, spent)| *value == 100_000 && !spent.is_null()));
+    }
+
     #[tokio::test]
     async fn zero_value_receipts() {
         let (regtest_manager, _cph, faucet, recipient, _txid) =
@@ -9,6 +9,9 @@ pub enum ConfirmationStatus {
     /// The transaction has been included in at-least one block mined to the zcash blockchain.
     /// The height of a confirmed block that contains the transaction.
     Confirmed(BlockHeight),
+    /// The chain passed the transaction's expiry height before it was mined, so it can never be mined.
+    /// The expiry height of the transaction.
+    Expired(BlockHeight),
 }
 
 impl ConfirmationStatus {
@@ -55,6 +58,22 @@ impl ConfirmationStatus {
     pub fn is_confirmed(&self) -> bool {
         matches!(self, Self::Confirmed(_))
     }
+    /// A wrapper matching the Expired case.
+    /// # Examples
+    ///
+    /// ```
+    /// use zingo_status::confirmation_status::ConfirmationStatus;
+    /// use zcash_primitives::consensus::BlockHeight;
+    ///
+    /// let status = ConfirmationStatus::Broadcast(10.into());
+    /// assert_eq!(status.is_expired(), false);
+    ///
+    /// let status = ConfirmationStatus::Expired(50.into());
+    /// assert_eq!(status.is_expired(), true);
+    /// ```
+    pub fn is_expired(&self) -> bool {
+        matches!(self, Self::Expired(_))
+    }
     /// To return true, the status must be confirmed and no earlier than specified height.
     /// # Examples
     ///
@@ -124,7 +143,7 @@ impl ConfirmationStatus {
     pub fn is_broadcast_before(&self, comparison_height: &BlockHeight) -> bool {
         match self {
             Self::Broadcast(self_height) => self_height < comparison_height,
-            Self::Confirmed(_) => false,
+            Self::Confirmed(_) | Self::Expired(_) => false,
         }
     }
     /// Returns none if transaction is not confirmed, otherwise returns the height it was confirmed at.
@@ -179,6 +198,7 @@ impl ConfirmationStatus {
         match self {
             Self::Broadcast(self_height) => *self_height,
             Self::Confirmed(self_height) => *self_height,
+            Self::Expired(self_height) => *self_height,
         }
     }
 }
@@ -196,6 +216,9 @@ impl std::fmt::Display for ConfirmationStatus {
                     self_height
                 )
             }
+            Self::Expired(self_height) => {
+                write!(f, "Transaction expired unmined at height {}.", self_height)
+            }
         }
     }
 }
@@ -27,8 +27,12 @@ pub const DEFAULT_LIGHTWALLETD_SERVER: &str = "https://mainnet.lightwalletd.com:
 pub const MAX_REORG: usize = 100;
 pub const DEFAULT_WALLET_NAME: &str = "zingo-wallet.dat";
 pub const DEFAULT_LOGFILE_NAME: &str = "zingo-wallet.debug.log";
+pub const DEFAULT_COMPACT_BLOCK_CACHE_DIR: &str = "compact_blocks";
 pub const REORG_BUFFER_OFFSET: u32 = 0;
+/// The size of the first batch of a sync, before any blocks have been seen
 pub const BATCH_SIZE: u64 = 100;
+pub const MIN_BATCH_SIZE: u64 = 10;
+pub const MAX_BATCH_SIZE: u64 = 10_000;
 
 #[cfg(any(target_os = "ios", target_os = "android"))]
 pub const GAP_RULE_UNUSED_ADDRESSES: usize = 0;
@@ -64,6 +68,13 @@ pub fn load_clientconfig(
         wallet_dir: data_dir,
         wallet_name: DEFAULT_WALLET_NAME.into(),
         logfile_name: DEFAULT_LOGFILE_NAME.into(),
+        prover: ProverKind::default(),
+        transparent_gap_limit: GAP_RULE_UNUSED_ADDRESSES,
+        sync_mode: SyncMode::default(),
+        trial_decryption_threads: 1,
+        min_batch_size: MIN_BATCH_SIZE,
+        max_batch_size: MAX_BATCH_SIZE,
+        compact_block_cache_limit: None,
     };
 
     Ok(config)
@@ -100,6 +111,14 @@ pub struct ZingoConfigBuilder {
     pub wallet_name: Option<PathBuf>,
     /// The filename of the logfile. This will be created in the `wallet_dir`.
     pub logfile_name: Option<PathBuf>,
+    /// What proves the Sapling parts of the transactions the wallet sends. Unset is
+    /// [`ProverKind::Local`]. [`ProverKind::Mock`] needs the mock-prover feature of zingolib.
+    pub prover: Option<ProverKind>,
+    pub transparent_gap_limit: Option<usize>,
+    pub sync_mode: Option<SyncMode>,
+    pub trial_decryption_threads: Option<usize>,
+    pub batch_size_bounds: Option<(u64, u64)>,
+    pub compact_block_cache_limit: Option<u64>,
 }
 /// Configuration data that is necessary? and sufficient? for the creation of a LightClient.
 #[derive(Clone, Debug)]
@@ -114,6 +133,51 @@ pub struct ZingoConfig {
     pub wallet_name: PathBuf,
     /// The filename of the logfile. This will be created in the `wallet_dir`.
     pub logfile_name: PathBuf,
+    /// What proves the Sapling parts of the transactions this wallet sends.
+    pub prover: ProverKind,
+    /// How many consecutive unused transparent addresses past the wallet's own are looked up
+    /// when a wallet is scanned from its birthday, before giving up on finding more that
+    /// were used. 0 turns this discovery off.
+    pub transparent_gap_limit: usize,
+    /// Whether sync scans forward from the wallet's last block, or spend-before-sync.
+    pub sync_mode: SyncMode,
+    /// How many threads trial decrypt the compact blocks of a sync batch. With 1, the
+    /// default, they are decrypted on the sync task, which suits memory constrained devices.
+    pub trial_decryption_threads: usize,
+    /// The fewest blocks a sync batch holds, however busy the blocks are.
+    pub min_batch_size: u64,
+    /// The most blocks a sync batch holds, however sparse the blocks are. Setting both
+    /// bounds to [`BATCH_SIZE`] syncs in the fixed batches of earlier versions.
+    pub max_batch_size: u64,
+    /// The most bytes of compact blocks kept in the wallet directory, so that a rescan reads
+    /// them from disk rather than the server. None, the default, keeps none.
+    pub compact_block_cache_limit: Option<u64>,
+}
+
+/// The order in which sync scans the blocks the wallet has not seen.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SyncMode {
+    /// Scans in batches from the wallet's last synced block up to the chain tip. Nothing
+    /// found is spendable until the scan reaches the tip.
+    #[default]
+    Linear,
+    /// Seeds the witness trees with the server's subtree roots, then scans the blocks near
+    /// the chain tip first, then the rest of the shards holding notes the wallet has found,
+    /// then the remaining history. A note is spendable once its shard has been scanned.
+    /// Needs a lightwalletd that serves subtree roots.
+    SpendBeforeSync,
+}
+
+/// What proves the Sapling parts of a transaction.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ProverKind {
+    /// Proves with the Sapling parameters.
+    #[default]
+    Local,
+    /// Writes placeholder proofs, so that no Sapling parameters are needed. The
+    /// transactions are otherwise complete, but no node accepts them, so this is only for
+    /// offline tests of note selection, fees and change.
+    Mock,
 }
 impl ZingoConfigBuilder {
     pub fn set_wallet_dir(mut self, dir: PathBuf) -> Self {
@@ -124,7 +188,37 @@ impl ZingoConfigBuilder {
         self.lightwalletd_uri = Some(lightwalletd_uri);
         self
     }
+    pub fn set_prover(mut self, prover: ProverKind) -> Self {
+        self.prover = Some(prover);
+        self
+    }
+    pub fn set_transparent_gap_limit(mut self, gap_limit: usize) -> Self {
+        self.transparent_gap_limit = Some(gap_limit);
+        self
+    }
+    pub fn set_sync_mode(mut self, sync_mode: SyncMode) -> Self {
+        self.sync_mode = Some(sync_mode);
+        self
+    }
+    pub fn set_trial_decryption_threads(mut self, threads: usize) -> Self {
+        self.trial_decryption_threads = Some(threads);
+        self
+    }
+    /// Bounds the number of blocks in each sync batch, which is otherwise chosen from how
+    /// many shielded outputs the blocks hold and how long they take to scan.
+    pub fn set_batch_size_bounds(mut self, min: u64, max: u64) -> Self {
+        self.batch_size_bounds = Some((min, max));
+        self
+    }
+    pub fn set_compact_block_cache_limit(mut self, max_bytes: u64) -> Self {
+        self.compact_block_cache_limit = Some(max_bytes);
+        self
+    }
     pub fn create(&self) -> ZingoConfig {
+        let (min_batch_size, max_batch_size) = match self.batch_size_bounds {
+            Some((min, max)) => (min.max(1), max.max(min.max(1))),
+            None => (MIN_BATCH_SIZE, MAX_BATCH_SIZE),
+        };
         let lightwalletd_uri = if let Some(uri) = self.lightwalletd_uri.clone() {
             uri
         } else {
@@ -138,6 +232,15 @@ impl ZingoConfigBuilder {
             wallet_dir: self.wallet_dir.clone(),
             wallet_name: DEFAULT_WALLET_NAME.into(),
             logfile_name: DEFAULT_LOGFILE_NAME.into(),
+            prover: self.prover.unwrap_or_default(),
+            transparent_gap_limit: self
+                .transparent_gap_limit
+                .unwrap_or(GAP_RULE_UNUSED_ADDRESSES),
+            sync_mode: self.sync_mode.unwrap_or_default(),
+            trial_decryption_threads: self.trial_decryption_threads.unwrap_or(1),
+            min_batch_size,
+            max_batch_size,
+            compact_block_cache_limit: self.compact_block_cache_limit,
         }
     }
 }
@@ -150,6 +253,12 @@ impl Default for ZingoConfigBuilder {
             wallet_dir: None,
             wallet_name: None,
             logfile_name: None,
+            prover: None,
+            transparent_gap_limit: None,
+            sync_mode: None,
+            trial_decryption_threads: None,
+            batch_size_bounds: None,
+            compact_block_cache_limit: None,
             chain: ChainType::Mainnet,
         }
     }
@@ -320,6 +429,11 @@ impl ZingoConfig {
         wallet_location.push(&self.wallet_name);
         wallet_location
     }
+    pub fn get_compact_block_cache_path(&self) -> PathBuf {
+        let mut cache_location = self.get_zingo_wallet_dir().into_path_buf();
+        cache_location.push(DEFAULT_COMPACT_BLOCK_CACHE_DIR);
+        cache_location
+    }
     pub fn get_wallet_path(&self) -> Box<Path> {
         self.get_wallet_pathbuf().into_boxed_path()
     }
@@ -419,6 +533,15 @@ impl ChainType {
             ChainType::FakeMainnet => "uview",
         }
     }
+    /// The human-readable part of ZIP 320 TEX addresses
+    pub fn hrp_tex_address(&self) -> &str {
+        match self {
+            ChainType::Testnet => "textest",
+            ChainType::Regtest(_) => "texregtest",
+            ChainType::Mainnet => "tex",
+            ChainType::FakeMainnet => "tex",
+        }
+    }
     pub fn to_zcash_address_network(&self) -> zcash_address::Network {
         match self {
             Mainnet | FakeMainnet => zcash_address::Network::Main,
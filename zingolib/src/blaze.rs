@@ -1,7 +1,11 @@
+pub(super) mod batch_sizing;
 pub(super) mod block_management_reorg_detection;
+pub(super) mod compact_block_cache;
 pub(super) mod fetch_compact_blocks;
 pub(super) mod fetch_full_transaction;
 pub(super) mod fetch_taddr_transactions;
+pub(super) mod scan_priority;
+pub mod sync_events;
 pub(super) mod sync_status;
 pub(super) mod syncdata;
 pub(super) mod trial_decryptions;
@@ -0,0 +1,101 @@
+//! How many blocks each sync batch scans. Sparse blocks go in large batches, to spend less
+//! of the sync on the per batch tree fetches and saves, and busy blocks in small ones, to
+//! bound the memory a batch holds and the time between saves.
+use std::time::Duration;
+
+use zingoconfig::{ZingoConfig, BATCH_SIZE};
+
+/// The shielded outputs a batch aims to hold. The compact outputs, and the notes and
+/// witnesses they make, are most of the memory a batch uses.
+const TARGET_BATCH_OUTPUTS: u64 = 50_000;
+/// The time a batch aims to take, so that progress is saved and an interrupt is seen often.
+const TARGET_BATCH_DURATION: Duration = Duration::from_secs(10);
+
+/// Sizes each batch from the density and the scan time of the one before it, within the
+/// bounds of the [`ZingoConfig`].
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct BatchSizer {
+    min: u64,
+    max: u64,
+    size: u64,
+}
+
+impl BatchSizer {
+    pub(crate) fn new(config: &ZingoConfig) -> Self {
+        Self {
+            min: config.min_batch_size,
+            max: config.max_batch_size,
+            size: BATCH_SIZE.clamp(config.min_batch_size, config.max_batch_size),
+        }
+    }
+
+    /// The number of blocks in the next batch
+    pub(crate) fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// The number of batches, at the current size, that `blocks` blocks take
+    pub(crate) fn batches_in(&self, blocks: u64) -> usize {
+        blocks.div_ceil(self.size) as usize
+    }
+
+    /// Resizes after a batch of `blocks` blocks, holding `outputs` shielded outputs, took
+    /// `elapsed`. A batch shrinks as far as it must at once, but at most doubles, so that
+    /// one sparse batch ahead of busy blocks costs little.
+    pub(crate) fn record(&mut self, blocks: u64, outputs: u64, elapsed: Duration) {
+        if blocks == 0 {
+            return;
+        }
+        let by_outputs = match outputs {
+            0 => u64::MAX,
+            _ => TARGET_BATCH_OUTPUTS.saturating_mul(blocks) / outputs,
+        };
+        let by_duration = match elapsed.as_millis() as u64 {
+            0 => u64::MAX,
+            millis => (TARGET_BATCH_DURATION.as_millis() as u64).saturating_mul(blocks) / millis,
+        };
+        self.size = by_outputs
+            .min(by_duration)
+            .min(self.size.saturating_mul(2))
+            .clamp(self.min, self.max);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use zingoconfig::{ChainType, ZingoConfig};
+
+    use super::BatchSizer;
+
+    #[test]
+    fn sizes_follow_density_and_latency() {
+        let config = ZingoConfig::build(ChainType::Mainnet)
+            .set_batch_size_bounds(10, 1_000)
+            .create();
+        let mut sizer = BatchSizer::new(&config);
+        assert_eq!(sizer.size(), 100);
+        assert_eq!(sizer.batches_in(250), 3);
+
+        // Sparse, quick blocks grow the batches by doubling, up to the maximum
+        sizer.record(100, 100, Duration::from_secs(1));
+        assert_eq!(sizer.size(), 200);
+        for _ in 0..5 {
+            sizer.record(sizer.size(), 0, Duration::from_millis(10));
+        }
+        assert_eq!(sizer.size(), 1_000);
+
+        // Busy blocks shrink them at once
+        sizer.record(1_000, 1_000_000, Duration::from_secs(1));
+        assert_eq!(sizer.size(), 50);
+
+        // As do slow ones
+        sizer.record(50, 50, Duration::from_secs(20));
+        assert_eq!(sizer.size(), 25);
+
+        // Never below the minimum
+        sizer.record(25, 10_000_000, Duration::from_secs(1));
+        assert_eq!(sizer.size(), 10);
+    }
+}
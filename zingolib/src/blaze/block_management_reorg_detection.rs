@@ -1,4 +1,5 @@
 use crate::wallet::traits::FromCommitment;
+use crate::wallet::utils::txid_from_slice;
 use crate::{
     grpc_connector::GrpcConnector,
     wallet::{
@@ -30,8 +31,10 @@ use tokio::{
 use zcash_primitives::{
     consensus::BlockHeight,
     merkle_tree::{read_commitment_tree, write_commitment_tree, HashSer},
+    transaction::TxId,
 };
 
+use super::sync_events::{SyncEvent, SyncEventSender};
 use super::sync_status::BatchSyncStatus;
 
 type Node<D> = <<D as DomainWalletExt>::WalletNote as ShieldedNoteInterface>::Node;
@@ -62,6 +65,9 @@ pub struct BlockManagementData {
 
     // Link to the syncstatus where we can update progress
     pub sync_status: Arc<RwLock<BatchSyncStatus>>,
+
+    // Where the progress is streamed to subscribers
+    pub(crate) sync_events: SyncEventSender,
 }
 
 impl BlockManagementData {
@@ -73,6 +79,7 @@ impl BlockManagementData {
             batch_size: zingoconfig::BATCH_SIZE,
             highest_verified_trees: None,
             sync_status,
+            sync_events: SyncEventSender::new(),
         }
     }
 
@@ -126,6 +133,27 @@ impl BlockManagementData {
         }
     }
 
+    /// The nullifiers revealed in the current batch, each with the transaction that revealed
+    /// it and the height and time of its block.
+    pub(crate) async fn revealed_nullifiers(&self) -> Vec<(Vec<u8>, (TxId, u64, u32))> {
+        let blocks = self.blocks_in_current_batch.read().await;
+        let mut revealed = vec![];
+        for cb in blocks.iter().map(BlockData::cb) {
+            for compact_transaction in &cb.vtx {
+                let txid = txid_from_slice(&compact_transaction.hash);
+                let nullifiers = compact_transaction
+                    .spends
+                    .iter()
+                    .map(|cs| &cs.nf)
+                    .chain(compact_transaction.actions.iter().map(|ca| &ca.nullifier));
+                for nullifier in nullifiers {
+                    revealed.push((nullifier.clone(), (txid, cb.height, cb.time)));
+                }
+            }
+        }
+        revealed
+    }
+
     pub async fn get_compact_transaction_for_nullifier_at_height(
         &self,
         nullifier: &PoolNullifier,
@@ -335,12 +363,14 @@ impl BlockManagementData {
             .remove_txns_at_height(reorg_height);
     }
 
-    /// Ingest the incoming blocks, handle any reorgs, then populate the block data
+    /// Ingest the incoming blocks, handle any reorgs, then populate the block data. A
+    /// reorged block's transactions are removed from each of the wallets'
+    /// `transaction_metadata_sets`, which the batch is scanned for.
     pub(crate) async fn handle_reorgs_and_populate_block_mangement_data(
         &self,
         start_block: u64,
         end_block: u64,
-        transaction_metadata_set: Arc<RwLock<TransactionMetadataSet>>,
+        transaction_metadata_sets: Vec<Arc<RwLock<TransactionMetadataSet>>>,
         reorg_transmitter: UnboundedSender<Option<u64>>,
     ) -> (
         JoinHandle<Result<Option<u64>, String>>,
@@ -354,7 +384,7 @@ impl BlockManagementData {
         // pass them on for further processing.
         let h0: JoinHandle<Result<Option<u64>, String>> = tokio::spawn(
             reorg_managment_thread_data
-                .handle_reorgs_populate_data_inner(transaction_metadata_set, reorg_transmitter),
+                .handle_reorgs_populate_data_inner(transaction_metadata_sets, reorg_transmitter),
         );
 
         // Handle: Final
@@ -391,6 +421,7 @@ impl BlockManagementData {
                 blocks_in_current_batch,
                 existing_blocks,
                 sync_status,
+                sync_events: self.sync_events.clone(),
                 receiver,
                 // Needed as we can't borrow self in the thread...as the thread could outlive self
                 batch_size: self.batch_size,
@@ -578,6 +609,7 @@ struct BlockManagementThreadData {
     existing_blocks: Arc<RwLock<Vec<BlockData>>>,
     /// Link to the syncstatus where we can update progress
     sync_status: Arc<RwLock<BatchSyncStatus>>,
+    sync_events: SyncEventSender,
     receiver: UnboundedReceiver<CompactBlock>,
     batch_size: u64,
     end_block: u64,
@@ -586,11 +618,12 @@ struct BlockManagementThreadData {
 impl BlockManagementThreadData {
     async fn handle_reorgs_populate_data_inner(
         mut self,
-        transaction_metadata_set: Arc<RwLock<TransactionMetadataSet>>,
+        transaction_metadata_sets: Vec<Arc<RwLock<TransactionMetadataSet>>>,
         reorg_transmitter: UnboundedSender<Option<u64>>,
     ) -> Result<Option<u64>, String> {
         // Temporary holding place for blocks while we process them.
         let mut unprocessed_blocks = vec![];
+        let mut unprocessed_outputs = 0;
         let mut earliest_block_height = None;
 
         // Reorg stuff
@@ -599,7 +632,10 @@ impl BlockManagementThreadData {
         while let Some(compact_block) = self.receiver.recv().await {
             if compact_block.height % self.batch_size == 0 && !unprocessed_blocks.is_empty() {
                 // Add these blocks to the list
-                self.sync_status.write().await.blocks_done += unprocessed_blocks.len() as u64;
+                let mut sync_status = self.sync_status.write().await;
+                sync_status.blocks_done += unprocessed_blocks.len() as u64;
+                sync_status.outputs_done += std::mem::take(&mut unprocessed_outputs);
+                drop(sync_status);
                 self.blocks_in_current_batch
                     .write()
                     .await
@@ -626,23 +662,40 @@ impl BlockManagementThreadData {
 
                 // If there was a reorg, then we need to invalidate the block and its associated transactions
                 if let Some(reorg_height) = reorg_block {
+                    self.sync_events.send(SyncEvent::ReorgDetected {
+                        height: reorg_height,
+                    });
                     BlockManagementData::invalidate_block(
                         reorg_height,
                         self.existing_blocks.clone(),
-                        transaction_metadata_set.clone(),
+                        transaction_metadata_sets[0].clone(),
                     )
                     .await;
+                    for transaction_metadata_set in &transaction_metadata_sets[1..] {
+                        transaction_metadata_set
+                            .write()
+                            .await
+                            .remove_txns_at_height(reorg_height);
+                    }
                     last_block_expecting = reorg_height;
                 }
                 reorg_transmitter.send(reorg_block).unwrap();
             }
 
             earliest_block_height = Some(compact_block.height);
+            unprocessed_outputs += compact_block
+                .vtx
+                .iter()
+                .map(|transaction| (transaction.outputs.len() + transaction.actions.len()) as u64)
+                .sum::<u64>();
             unprocessed_blocks.push(BlockData::new(compact_block));
         }
 
         if !unprocessed_blocks.is_empty() {
-            self.sync_status.write().await.blocks_done += unprocessed_blocks.len() as u64;
+            let mut sync_status = self.sync_status.write().await;
+            sync_status.blocks_done += unprocessed_blocks.len() as u64;
+            sync_status.outputs_done += unprocessed_outputs;
+            drop(sync_status);
             self.blocks_in_current_batch
                 .write()
                 .await
@@ -832,7 +885,9 @@ mod test {
             .handle_reorgs_and_populate_block_mangement_data(
                 start_block,
                 end_block,
-                Arc::new(RwLock::new(TransactionMetadataSet::new_with_witness_trees())),
+                vec![Arc::new(RwLock::new(
+                    TransactionMetadataSet::new_with_witness_trees(),
+                ))],
                 reorg_transmitter,
             )
             .await;
@@ -881,7 +936,9 @@ mod test {
             .handle_reorgs_and_populate_block_mangement_data(
                 start_block,
                 end_block,
-                Arc::new(RwLock::new(TransactionMetadataSet::new_with_witness_trees())),
+                vec![Arc::new(RwLock::new(
+                    TransactionMetadataSet::new_with_witness_trees(),
+                ))],
                 reorg_transmitter,
             )
             .await;
@@ -977,7 +1034,9 @@ mod test {
             .handle_reorgs_and_populate_block_mangement_data(
                 start_block,
                 end_block,
-                Arc::new(RwLock::new(TransactionMetadataSet::new_with_witness_trees())),
+                vec![Arc::new(RwLock::new(
+                    TransactionMetadataSet::new_with_witness_trees(),
+                ))],
                 reorg_transmitter,
             )
             .await;
@@ -0,0 +1,280 @@
+//! Compact blocks kept in the wallet directory, so that a rescan reads them from disk rather
+//! than downloading them again. The blocks are kept in segment files, each holding the
+//! blocks of a range of heights, ascending, as length-prefixed protobuf, after a header and
+//! followed by a SHA-256 of the rest of the file.
+use std::{
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use prost::Message;
+use sha2::{Digest, Sha256};
+use zcash_client_backend::proto::compact_formats::CompactBlock;
+use zingoconfig::ZingoConfig;
+
+const SEGMENT_MAGIC: &[u8; 4] = b"ZCBC";
+const SEGMENT_VERSION: u8 = 1;
+const SEGMENT_EXTENSION: &str = "blocks";
+const CHECKSUM_LEN: usize = 32;
+
+#[derive(Clone)]
+pub(crate) struct CompactBlockCache {
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+impl CompactBlockCache {
+    /// The cache of the `config`, if it keeps one
+    pub(crate) fn new(config: &ZingoConfig) -> Option<Self> {
+        config
+            .compact_block_cache_limit
+            .map(|max_bytes| Self::in_dir(config.get_compact_block_cache_path(), max_bytes))
+    }
+
+    fn in_dir(dir: PathBuf, max_bytes: u64) -> Self {
+        Self { dir, max_bytes }
+    }
+
+    /// The cached blocks from `low` to `high`, ascending. Heights that are not cached are
+    /// left out. A segment that fails its checks is deleted, so its blocks are fetched again.
+    pub(crate) fn read(&self, low: u64, high: u64) -> Vec<CompactBlock> {
+        let mut blocks: Vec<CompactBlock> = vec![];
+        for (segment_low, segment_high, path) in self.segments() {
+            if segment_high < low || segment_low > high {
+                continue;
+            }
+            match read_segment(&path, segment_low, segment_high) {
+                Ok(segment) => {
+                    for block in segment {
+                        // Segments may overlap
+                        if (low..=high).contains(&block.height)
+                            && !matches!(blocks.last(), Some(last) if last.height >= block.height)
+                        {
+                            blocks.push(block);
+                        }
+                    }
+                }
+                Err(e) => {
+                    log::warn!(
+                        "Deleting compact block cache segment {}: {}",
+                        path.display(),
+                        e
+                    );
+                    let _ = fs::remove_file(&path);
+                }
+            }
+        }
+        blocks
+    }
+
+    /// Adds the contiguous, ascending `blocks` as a segment, deletes the segments it covers,
+    /// and then the least recently written ones while the cache is over its limit.
+    pub(crate) fn write(&self, blocks: &[CompactBlock]) -> io::Result<()> {
+        let (Some(first), Some(last)) = (blocks.first(), blocks.last()) else {
+            return Ok(());
+        };
+        let (low, high) = (first.height, last.height);
+
+        let mut contents = SEGMENT_MAGIC.to_vec();
+        contents.write_u8(SEGMENT_VERSION)?;
+        contents.write_u64::<LittleEndian>(low)?;
+        contents.write_u64::<LittleEndian>(high)?;
+        contents.write_u32::<LittleEndian>(blocks.len() as u32)?;
+        for block in blocks {
+            let bytes = block.encode_to_vec();
+            contents.write_u32::<LittleEndian>(bytes.len() as u32)?;
+            contents.extend_from_slice(&bytes);
+        }
+        let checksum = Sha256::digest(&contents);
+        contents.extend_from_slice(checksum.as_slice());
+
+        // Written aside and renamed, so that an interrupted write leaves no partial segment
+        fs::create_dir_all(&self.dir)?;
+        let path = self
+            .dir
+            .join(format!("{low:010}-{high:010}.{SEGMENT_EXTENSION}"));
+        let partial = path.with_extension("partial");
+        fs::write(&partial, contents)?;
+        fs::rename(&partial, &path)?;
+
+        for (segment_low, segment_high, segment_path) in self.segments() {
+            if low <= segment_low && segment_high <= high && segment_path != path {
+                fs::remove_file(segment_path)?;
+            }
+        }
+        self.prune()
+    }
+
+    /// Deletes the segments holding any block from `low` to `high`
+    pub(crate) fn discard(&self, low: u64, high: u64) -> io::Result<()> {
+        for (segment_low, segment_high, path) in self.segments() {
+            if segment_low <= high && low <= segment_high {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn prune(&self) -> io::Result<()> {
+        let mut segments = self
+            .segments()
+            .into_iter()
+            .filter_map(|(_, _, path)| {
+                let metadata = fs::metadata(&path).ok()?;
+                let written = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                Some((written, metadata.len(), path))
+            })
+            .collect::<Vec<_>>();
+        segments.sort_unstable();
+        let mut total = segments.iter().map(|(_, len, _)| len).sum::<u64>();
+        for (_, len, path) in segments {
+            if total <= self.max_bytes {
+                break;
+            }
+            fs::remove_file(path)?;
+            total -= len;
+        }
+        Ok(())
+    }
+
+    /// The segment files, as their lowest and highest heights and path, by lowest height
+    fn segments(&self) -> Vec<(u64, u64, PathBuf)> {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return vec![];
+        };
+        let mut segments = entries
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != SEGMENT_EXTENSION {
+                    return None;
+                }
+                let (low, high) = path.file_stem()?.to_str()?.split_once('-')?;
+                Some((low.parse().ok()?, high.parse().ok()?, path))
+            })
+            .collect::<Vec<_>>();
+        segments.sort_unstable();
+        segments
+    }
+}
+
+/// Reads the segment at `path`, checking that it holds the blocks from `low` to `high`, each
+/// following on from the one before.
+fn read_segment(path: &Path, low: u64, high: u64) -> io::Result<Vec<CompactBlock>> {
+    let contents = fs::read(path)?;
+    if contents.len() < CHECKSUM_LEN {
+        return Err(invalid("truncated"));
+    }
+    let (mut reader, checksum) = contents.split_at(contents.len() - CHECKSUM_LEN);
+    if Sha256::digest(reader).as_slice() != checksum {
+        return Err(invalid("checksum mismatch"));
+    }
+
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != SEGMENT_MAGIC {
+        return Err(invalid("not a compact block segment"));
+    }
+    let version = reader.read_u8()?;
+    if version != SEGMENT_VERSION {
+        return Err(invalid(format!("unknown segment version {version}")));
+    }
+    if (
+        reader.read_u64::<LittleEndian>()?,
+        reader.read_u64::<LittleEndian>()?,
+    ) != (low, high)
+        || u64::from(reader.read_u32::<LittleEndian>()?) != high - low + 1
+    {
+        return Err(invalid("heights do not match the file name"));
+    }
+
+    let mut blocks: Vec<CompactBlock> = vec![];
+    for height in low..=high {
+        let len = reader.read_u32::<LittleEndian>()? as usize;
+        if reader.len() < len {
+            return Err(invalid("truncated block"));
+        }
+        let (bytes, rest) = reader.split_at(len);
+        reader = rest;
+        let block = CompactBlock::decode(bytes).map_err(invalid)?;
+        if block.height != height {
+            return Err(invalid(format!(
+                "expected block {height}, found {}",
+                block.height
+            )));
+        }
+        if matches!(blocks.last(), Some(previous) if previous.hash != block.prev_hash) {
+            return Err(invalid(format!(
+                "block {height} does not follow the one before"
+            )));
+        }
+        blocks.push(block);
+    }
+    if !reader.is_empty() {
+        return Err(invalid("data after the last block"));
+    }
+    Ok(blocks)
+}
+
+fn invalid(e: impl ToString) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use zcash_client_backend::proto::compact_formats::CompactBlock;
+
+    use super::CompactBlockCache;
+
+    fn chain(low: u64, high: u64) -> Vec<CompactBlock> {
+        (low..=high)
+            .map(|height| CompactBlock {
+                height,
+                hash: height.to_le_bytes().to_vec(),
+                prev_hash: (height - 1).to_le_bytes().to_vec(),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn heights(blocks: &[CompactBlock]) -> Vec<u64> {
+        blocks.iter().map(|block| block.height).collect()
+    }
+
+    #[test]
+    fn reads_what_was_written_and_drops_bad_segments() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = CompactBlockCache::in_dir(dir.path().to_path_buf(), u64::MAX);
+        cache.write(&chain(10, 19)).unwrap();
+        cache.write(&chain(30, 39)).unwrap();
+        assert_eq!(
+            heights(&cache.read(15, 35)),
+            [15, 16, 17, 18, 19, 30, 31, 32, 33, 34, 35]
+        );
+
+        // A segment covering others replaces them
+        cache.write(&chain(5, 39)).unwrap();
+        assert_eq!(cache.segments().len(), 1);
+        assert_eq!(heights(&cache.read(0, 100)), (5..=39).collect::<Vec<_>>());
+
+        // A corrupt segment is deleted
+        let (_, _, path) = cache.segments().remove(0);
+        let mut contents = fs::read(&path).unwrap();
+        contents[20] ^= 1;
+        fs::write(&path, contents).unwrap();
+        assert!(cache.read(0, 100).is_empty());
+        assert!(cache.segments().is_empty());
+    }
+
+    #[test]
+    fn prunes_to_its_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = CompactBlockCache::in_dir(dir.path().to_path_buf(), 0);
+        cache.write(&chain(10, 19)).unwrap();
+        assert!(cache.segments().is_empty());
+    }
+}
@@ -1,18 +1,21 @@
 use std::{cmp::max, sync::Arc};
 
+use super::compact_block_cache::CompactBlockCache;
 use crate::grpc_connector::GrpcConnector;
-use log::debug;
-use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use log::{debug, warn};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 use zcash_client_backend::proto::compact_formats::CompactBlock;
-use zingoconfig::ZingoConfig;
+use zingoconfig::{ZingoConfig, MAX_REORG};
 pub struct FetchCompactBlocks {
     config: ZingoConfig,
+    cache: Option<CompactBlockCache>,
 }
 
 impl FetchCompactBlocks {
     pub fn new(config: &ZingoConfig) -> Self {
         Self {
             config: config.clone(),
+            cache: CompactBlockCache::new(config),
         }
     }
 
@@ -23,24 +26,123 @@ impl FetchCompactBlocks {
         end_block: u64,
     ) -> Result<(), String> {
         let grpc_client = Arc::new(GrpcConnector::new(self.config.get_lightwalletd_uri()));
-        const STEP: u64 = 10_000;
+        // No batch is larger than this, so each is fetched in one request
+        let step = self.config.max_batch_size;
+        // Blocks above this may yet be reorged away, so are not cached
+        let mut cacheable_height = None;
+        let mut lowest_sent = None;
 
         // We need the `rev()` here because rust ranges can only go up
-        for b in (end_block..(start_block + 1)).rev().step_by(STEP as usize) {
+        for b in (end_block..(start_block + 1)).rev().step_by(step as usize) {
             let start = b;
-            let end = max((b as i64) - (STEP as i64) + 1, end_block as i64) as u64;
+            let end = max((b as i64) - (step as i64) + 1, end_block as i64) as u64;
             if start < end {
                 return Err("Wrong block order".to_string());
             }
 
             debug!("Fetching blocks {}-{}", start, end);
 
-            grpc_client.get_block_range(start, end, senders).await?;
+            match &self.cache {
+                Some(cache) => {
+                    self.fetch_through_cache(
+                        cache,
+                        &grpc_client,
+                        senders,
+                        start,
+                        end,
+                        &mut cacheable_height,
+                        &mut lowest_sent,
+                    )
+                    .await?
+                }
+                None => grpc_client.get_block_range(start, end, senders).await?,
+            }
         }
 
         Ok(())
     }
 
+    /// Sends the blocks from `start` down to `end`, reading those that are cached, and
+    /// fetching and caching the rest. `lowest_sent` is the last block sent before these, if
+    /// any, which the first of them must join.
+    #[allow(clippy::too_many_arguments)]
+    async fn fetch_through_cache(
+        &self,
+        cache: &CompactBlockCache,
+        grpc_client: &GrpcConnector,
+        senders: &[UnboundedSender<CompactBlock>; 2],
+        start: u64,
+        end: u64,
+        cacheable_height: &mut Option<u64>,
+        lowest_sent: &mut Option<CompactBlock>,
+    ) -> Result<(), String> {
+        let reader = cache.clone();
+        let cached = tokio::task::spawn_blocking(move || reader.read(end, start))
+            .await
+            .map_err(|e| format!("{}", e))?;
+
+        let mut fetched = vec![];
+        for (high, low) in uncached_ranges(&cached, start, end) {
+            debug!("Fetching uncached blocks {}-{}", high, low);
+            fetched.push(fetch_descending(grpc_client, high, low).await?);
+        }
+        let blocks = match join_blocks(cached, fetched.clone(), lowest_sent.as_ref()) {
+            Ok(blocks) => blocks,
+            Err(height) => {
+                warn!(
+                    "Cached compact block {} does not join the chain, fetching {}-{} again",
+                    height, start, end
+                );
+                let discarder = cache.clone();
+                match tokio::task::spawn_blocking(move || discarder.discard(end, start)).await {
+                    Ok(Ok(())) => (),
+                    Ok(Err(e)) => warn!("Could not discard compact block cache: {}", e),
+                    Err(e) => warn!("Could not discard compact block cache: {}", e),
+                }
+                let blocks = fetch_descending(grpc_client, start, end).await?;
+                fetched = vec![blocks.clone()];
+                blocks
+            }
+        };
+
+        for block in &blocks {
+            for sender in senders {
+                sender.send(block.clone()).map_err(|e| format!("{}", e))?;
+            }
+        }
+        *lowest_sent = blocks.last().cloned();
+
+        if fetched.is_empty() {
+            return Ok(());
+        }
+        if cacheable_height.is_none() {
+            match GrpcConnector::get_latest_block(self.config.get_lightwalletd_uri()).await {
+                Ok(latest) => {
+                    *cacheable_height = Some(latest.height.saturating_sub(MAX_REORG as u64))
+                }
+                Err(e) => warn!("Not caching compact blocks: {}", e),
+            }
+        }
+        if let Some(cacheable_height) = *cacheable_height {
+            let writer = cache.clone();
+            let written = tokio::task::spawn_blocking(move || {
+                for mut run in fetched {
+                    run.reverse();
+                    run.retain(|block| block.height <= cacheable_height);
+                    writer.write(&run)?;
+                }
+                Ok::<_, std::io::Error>(())
+            })
+            .await;
+            match written {
+                Ok(Ok(())) => (),
+                Ok(Err(e)) => warn!("Could not cache compact blocks: {}", e),
+                Err(e) => warn!("Could not cache compact blocks: {}", e),
+            }
+        }
+        Ok(())
+    }
+
     // Load all the blocks from LightwalletD
     pub async fn start(
         &self,
@@ -68,3 +170,147 @@ impl FetchCompactBlocks {
         Ok(())
     }
 }
+
+/// Fetches the blocks from `high` down to `low`, in that order
+async fn fetch_descending(
+    grpc_client: &GrpcConnector,
+    high: u64,
+    low: u64,
+) -> Result<Vec<CompactBlock>, String> {
+    let (transmitter, mut receiver) = unbounded_channel();
+    grpc_client
+        .get_block_range(high, low, &[transmitter])
+        .await?;
+    let mut blocks = vec![];
+    while let Some(block) = receiver.recv().await {
+        blocks.push(block);
+    }
+    Ok(blocks)
+}
+
+/// The ranges from `start` down to `end` missing from the ascending `cached` blocks, each
+/// as its highest and lowest height, highest range first
+fn uncached_ranges(cached: &[CompactBlock], start: u64, end: u64) -> Vec<(u64, u64)> {
+    let mut ranges = vec![];
+    let mut next = Some(start);
+    let mut cached = cached.iter().rev().peekable();
+    while let Some(height) = next.filter(|height| *height >= end) {
+        match cached.peek() {
+            Some(block) if block.height == height => {
+                cached.next();
+                next = height.checked_sub(1);
+            }
+            Some(block) if block.height > height => {
+                cached.next();
+            }
+            below => {
+                let low = below.map_or(end, |block| block.height + 1);
+                ranges.push((height, low));
+                next = low.checked_sub(1);
+            }
+        }
+    }
+    ranges
+}
+
+/// Puts the ascending `cached` blocks and the descending `fetched` runs together, highest
+/// first, checking that each block follows on from the one below it, and the highest from
+/// the one below `above`. Fails with the height of a cached block where that does not hold,
+/// as the server's chain is taken over the cache's.
+fn join_blocks(
+    cached: Vec<CompactBlock>,
+    fetched: Vec<Vec<CompactBlock>>,
+    above: Option<&CompactBlock>,
+) -> Result<Vec<CompactBlock>, u64> {
+    let mut blocks = cached
+        .into_iter()
+        .map(|block| (block, true))
+        .chain(fetched.into_iter().flatten().map(|block| (block, false)))
+        .collect::<Vec<_>>();
+    blocks.sort_by(|(a, _), (b, _)| b.height.cmp(&a.height));
+
+    let mut upper = above.map(|block| (block, false));
+    for (block, is_cached) in &blocks {
+        if let Some((upper, upper_cached)) = upper {
+            if (*is_cached || upper_cached) && upper.prev_hash != block.hash {
+                return Err(if *is_cached {
+                    block.height
+                } else {
+                    upper.height
+                });
+            }
+        }
+        upper = Some((block, *is_cached));
+    }
+    Ok(blocks.into_iter().map(|(block, _)| block).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(height: u64, fork: u8) -> CompactBlock {
+        let hash = |height: u64| {
+            let mut hash = height.to_le_bytes().to_vec();
+            hash.push(fork);
+            hash
+        };
+        CompactBlock {
+            height,
+            hash: hash(height),
+            prev_hash: hash(height - 1),
+            ..Default::default()
+        }
+    }
+
+    fn heights(blocks: &[CompactBlock]) -> Vec<u64> {
+        blocks.iter().map(|block| block.height).collect()
+    }
+
+    #[test]
+    fn cached_blocks_are_joined_to_fetched_ones() {
+        let cached = [12, 13, 16, 17].map(|height| block(height, 0)).to_vec();
+        assert_eq!(
+            uncached_ranges(&cached, 18, 10),
+            [(18, 18), (15, 14), (11, 10)]
+        );
+        assert!(uncached_ranges(&cached, 17, 16).is_empty());
+        assert_eq!(uncached_ranges(&[], 17, 16), [(17, 16)]);
+
+        let fetched = |heights: &[u64], fork| -> Vec<CompactBlock> {
+            heights.iter().map(|height| block(*height, fork)).collect()
+        };
+        let joined = join_blocks(
+            cached.clone(),
+            vec![
+                fetched(&[18], 0),
+                fetched(&[15, 14], 0),
+                fetched(&[11, 10], 0),
+            ],
+            Some(&block(19, 0)),
+        )
+        .unwrap();
+        assert_eq!(heights(&joined), (10..=18).rev().collect::<Vec<_>>());
+
+        // The server has moved to another chain below the cached 16
+        assert_eq!(
+            join_blocks(
+                cached.clone(),
+                vec![
+                    fetched(&[18], 0),
+                    fetched(&[15, 14], 1),
+                    fetched(&[11, 10], 1),
+                ],
+                None,
+            ),
+            Err(16)
+        );
+        // and above the cached 17
+        assert_eq!(
+            join_blocks(cached.clone(), vec![fetched(&[18], 1)], None),
+            Err(17)
+        );
+        // and above the cached blocks sent before these
+        assert_eq!(join_blocks(cached, vec![], Some(&block(18, 1))), Err(17));
+    }
+}
@@ -1,4 +1,4 @@
-use super::syncdata::BlazeSyncData;
+use super::{sync_events::SyncEvent, syncdata::BlazeSyncData};
 use crate::{
     error::{ZingoLibError, ZingoLibResult},
     wallet::{
@@ -635,6 +635,11 @@ pub async fn start(
                 per_txid_iter_context
                     .scan_full_tx(&transaction, status, block_time, None)
                     .await;
+                let event = SyncEvent::TransactionDiscovered {
+                    txid: transaction_id,
+                    height: u64::from(height),
+                };
+                bsync_data.read().await.block_data.sync_events.send(event);
 
                 Ok::<_, String>(())
             }));
@@ -675,6 +680,11 @@ pub async fn start(
             transaction_context
                 .scan_full_tx(&transaction, status, block_time, None)
                 .await;
+            let event = SyncEvent::TransactionDiscovered {
+                txid: transaction.txid(),
+                height: u64::from(height),
+            };
+            bsync_data.read().await.block_data.sync_events.send(event);
         }
 
         //info!("Finished full_tx scanning all txns");
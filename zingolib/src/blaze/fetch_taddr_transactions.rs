@@ -46,10 +46,9 @@ impl FetchTaddrTransactions {
         let config = self.config.clone();
         tokio::spawn(async move {
             let taddrs = wc
-                .addresses()
-                .iter()
-                .filter_map(|ua| ua.transparent())
-                .map(|taddr| address_from_pubkeyhash(&config, *taddr))
+                .transparent_receivers()
+                .into_iter()
+                .map(|taddr| address_from_pubkeyhash(&config, taddr))
                 .collect::<Vec<_>>();
 
             // Fetch all transactions for all t-addresses in parallel, and process them in height order
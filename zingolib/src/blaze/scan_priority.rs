@@ -0,0 +1,272 @@
+//! The order in which a spend-before-sync scans the blocks it has not seen. Ranges of
+//! blocks are inclusive at both ends, as `(start, end)`.
+use std::collections::HashMap;
+
+/// The most nullifiers a spend-before-sync keeps for the notes it has yet to find, some
+/// tens of megabytes of them
+pub const MAX_REVEALED_NULLIFIERS: usize = 1 << 18;
+
+/// Why a range of blocks is scanned when it is, most urgent first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ScanPriority {
+    /// The blocks after the last complete shard, up to the chain tip
+    ChainTip,
+    /// The rest of a shard that holds a note the wallet has found
+    FoundNote,
+    /// Everything else, oldest first
+    Historic,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScanRange {
+    pub start: u64,
+    pub end: u64,
+    pub priority: ScanPriority,
+}
+
+/// The blocks holding the shard that `height` falls in, given the heights that completed
+/// each shard, ascending. A shard's first block is the one that completed the shard before
+/// it, which may also hold its first notes. The open shard ends at `tip`.
+pub fn shard_containing(shard_ends: &[u64], height: u64, tip: u64) -> (u64, u64) {
+    let index = shard_ends.partition_point(|end| *end < height);
+    let start = match index {
+        0 => 0,
+        _ => shard_ends[index - 1],
+    };
+    (start, shard_ends.get(index).copied().unwrap_or(tip))
+}
+
+/// Orders the `unscanned` ranges: the blocks from `tip_start` on first, then the parts of
+/// the `found_note_shards`, the most recent first, then the rest from the oldest up.
+/// Historic ranges go up so that a note found in one is checked for spends in the next.
+pub fn plan(
+    unscanned: &[(u64, u64)],
+    tip_start: u64,
+    found_note_shards: &[(u64, u64)],
+) -> Vec<ScanRange> {
+    let mut ranges = vec![];
+    for (start, end) in merge(unscanned.to_vec()) {
+        let (history, chain_tip) = split(
+            (start, end),
+            &[(tip_start, u64::MAX)],
+            ScanPriority::Historic,
+            ScanPriority::ChainTip,
+        );
+        ranges.extend(chain_tip);
+        for historic in history {
+            let (history, found_note) = split(
+                (historic.start, historic.end),
+                &merge(found_note_shards.to_vec()),
+                ScanPriority::Historic,
+                ScanPriority::FoundNote,
+            );
+            ranges.extend(found_note);
+            ranges.extend(history);
+        }
+    }
+    ranges.sort_by(|a, b| {
+        a.priority.cmp(&b.priority).then(match a.priority {
+            ScanPriority::Historic => a.start.cmp(&b.start),
+            _ => b.start.cmp(&a.start),
+        })
+    });
+    ranges
+}
+
+/// The wallets, by their index in `unscanned`, that scan the blocks from `start`: those yet
+/// to scan it. The blocks end at `end`, or sooner where another wallet's ranges start or
+/// one of theirs ends, so that every one of them has yet to scan all of the blocks.
+pub fn wallets_to_scan(unscanned: &[Vec<(u64, u64)>], start: u64, end: u64) -> (Vec<usize>, u64) {
+    let mut wallets = vec![];
+    let mut end = end;
+    for (wallet, ranges) in unscanned.iter().enumerate() {
+        for (range_start, range_end) in ranges {
+            if (*range_start..=*range_end).contains(&start) {
+                wallets.push(wallet);
+                end = end.min(*range_end);
+            } else if *range_start > start {
+                end = end.min(range_start - 1);
+            }
+        }
+    }
+    (wallets, end)
+}
+
+/// Adds `range` to the `ranges`, merging any that overlap or touch.
+pub fn insert(ranges: &mut Vec<(u64, u64)>, range: (u64, u64)) {
+    ranges.push(range);
+    *ranges = merge(std::mem::take(ranges));
+}
+
+/// Takes `range` out of the `ranges`.
+pub fn remove(ranges: &mut Vec<(u64, u64)>, range: (u64, u64)) {
+    *ranges = std::mem::take(ranges)
+        .into_iter()
+        .flat_map(|existing| {
+            split(
+                existing,
+                &[range],
+                ScanPriority::Historic,
+                ScanPriority::Historic,
+            )
+            .0
+            .into_iter()
+            .map(|kept| (kept.start, kept.end))
+        })
+        .collect();
+}
+
+/// Forgets the revealed nullifiers that can spend no note left to find: those revealed
+/// below every `unscanned` block, as such a note would be below them too. Then, past `max`,
+/// the lowest are forgotten. Returns how many were forgotten for `max`.
+pub fn prune_revealed<T>(
+    revealed: &mut HashMap<Vec<u8>, (T, u64, u32)>,
+    unscanned: &[(u64, u64)],
+    max: usize,
+) -> usize {
+    match unscanned.iter().map(|(start, _)| *start).min() {
+        Some(lowest) => revealed.retain(|_, (_, height, _)| *height > lowest),
+        None => revealed.clear(),
+    }
+    let excess = revealed.len().saturating_sub(max);
+    if excess == 0 {
+        return 0;
+    }
+    let mut heights = revealed
+        .values()
+        .map(|(_, height, _)| *height)
+        .collect::<Vec<_>>();
+    heights.sort_unstable();
+    let highest_forgotten = heights[excess - 1];
+    let before = revealed.len();
+    revealed.retain(|_, (_, height, _)| *height > highest_forgotten);
+    before - revealed.len()
+}
+
+fn merge(mut ranges: Vec<(u64, u64)>) -> Vec<(u64, u64)> {
+    ranges.sort_unstable();
+    let mut merged: Vec<(u64, u64)> = vec![];
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// Splits `range` into the parts outside of the sorted, disjoint `cover`, and those inside.
+fn split(
+    (start, end): (u64, u64),
+    cover: &[(u64, u64)],
+    outside: ScanPriority,
+    inside: ScanPriority,
+) -> (Vec<ScanRange>, Vec<ScanRange>) {
+    let (mut outer, mut inner) = (vec![], vec![]);
+    let mut next = start;
+    for &(cover_start, cover_end) in cover {
+        if next > end || cover_start > end {
+            break;
+        }
+        if cover_end < next {
+            continue;
+        }
+        if cover_start > next {
+            outer.push(ScanRange {
+                start: next,
+                end: cover_start - 1,
+                priority: outside,
+            });
+        }
+        let inner_end = cover_end.min(end);
+        inner.push(ScanRange {
+            start: cover_start.max(next),
+            end: inner_end,
+            priority: inside,
+        });
+        match inner_end.checked_add(1) {
+            Some(after) => next = after,
+            None => return (outer, inner),
+        }
+    }
+    if next <= end {
+        outer.push(ScanRange {
+            start: next,
+            end,
+            priority: outside,
+        });
+    }
+    (outer, inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plans_tip_then_found_notes_then_history() {
+        let shard_ends = [1_100, 1_500, 1_900];
+        assert_eq!(shard_containing(&shard_ends, 1_200, 2_000), (1_100, 1_500));
+        assert_eq!(shard_containing(&shard_ends, 1_500, 2_000), (1_100, 1_500));
+        assert_eq!(shard_containing(&shard_ends, 1_950, 2_000), (1_900, 2_000));
+
+        let found_note_shards = [(1_100, 1_500), (1_500, 1_900)];
+        let plan = plan(&[(1_000, 2_000)], 1_901, &found_note_shards)
+            .into_iter()
+            .map(|range| (range.start, range.end, range.priority))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            plan,
+            vec![
+                (1_901, 2_000, ScanPriority::ChainTip),
+                (1_100, 1_900, ScanPriority::FoundNote),
+                (1_000, 1_099, ScanPriority::Historic),
+            ]
+        );
+    }
+
+    #[test]
+    fn ranges_insert_and_remove() {
+        let mut ranges = vec![(10, 20)];
+        insert(&mut ranges, (21, 30));
+        insert(&mut ranges, (50, 60));
+        assert_eq!(ranges, vec![(10, 30), (50, 60)]);
+        remove(&mut ranges, (15, 52));
+        assert_eq!(ranges, vec![(10, 14), (53, 60)]);
+        remove(&mut ranges, (0, 100));
+        assert!(ranges.is_empty());
+    }
+
+    #[test]
+    fn batches_hold_blocks_every_wallet_scanning_them_has_yet_to_scan() {
+        let unscanned = [vec![(10, 20)], vec![(15, 30)], vec![(40, 50)]];
+        assert_eq!(wallets_to_scan(&unscanned, 10, 100), (vec![0], 14));
+        assert_eq!(wallets_to_scan(&unscanned, 15, 100), (vec![0, 1], 20));
+        assert_eq!(wallets_to_scan(&unscanned, 21, 100), (vec![1], 30));
+        assert_eq!(wallets_to_scan(&unscanned, 21, 25), (vec![1], 25));
+        assert_eq!(wallets_to_scan(&unscanned, 40, 100), (vec![2], 50));
+    }
+
+    fn heights(revealed: &HashMap<Vec<u8>, ((), u64, u32)>) -> Vec<u64> {
+        let mut heights = revealed.values().map(|(_, h, _)| *h).collect::<Vec<_>>();
+        heights.sort_unstable();
+        heights
+    }
+
+    #[test]
+    fn revealed_nullifiers_are_kept_above_unscanned_blocks_and_bounded() {
+        let mut revealed = (0..6u8)
+            .map(|i| (vec![i], ((), 100 + i as u64 * 10, 0)))
+            .collect::<HashMap<_, _>>();
+        // Blocks 100 to 129 are scanned, so notes spent in them are all found
+        assert_eq!(prune_revealed(&mut revealed, &[(130, 135)], 10), 0);
+        assert_eq!(heights(&revealed), vec![140, 150]);
+
+        revealed.insert(vec![9], ((), 160, 0));
+        assert_eq!(prune_revealed(&mut revealed, &[(0, 99)], 2), 1);
+        assert_eq!(heights(&revealed), vec![150, 160]);
+
+        assert_eq!(prune_revealed(&mut revealed, &[], 2), 0);
+        assert!(revealed.is_empty());
+    }
+}
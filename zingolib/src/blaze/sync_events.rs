@@ -0,0 +1,185 @@
+//! The progress of a sync as typed events, for UIs to subscribe to rather than polling the
+//! [`BatchSyncStatus`](super::sync_status::BatchSyncStatus).
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tokio::sync::broadcast;
+use zcash_primitives::transaction::TxId;
+
+use crate::wallet::Pool;
+
+/// How many events a subscriber can fall behind by before it misses some
+const SYNC_EVENT_CAPACITY: usize = 1_024;
+/// How far back the scan rate that the ETA is worked out from is measured
+const THROUGHPUT_WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SyncEvent {
+    /// A batch started scanning the blocks from `end_block` up to `start_block`
+    BatchStarted {
+        batch_num: usize,
+        batch_total: usize,
+        start_block: u64,
+        end_block: u64,
+    },
+    /// More blocks were trial decrypted, the lowest of them at `height`. The counts are of
+    /// the whole sync of the wallet, and the ETA is for the rest of it.
+    BlocksScanned {
+        height: u64,
+        blocks_done: u64,
+        blocks_total: u64,
+        eta: Option<Duration>,
+    },
+    /// Trial decryption found a note of the wallet's
+    NoteFound { txid: TxId, height: u64, pool: Pool },
+    /// A transaction of the wallet's was fetched and scanned in full
+    TransactionDiscovered { txid: TxId, height: u64 },
+    /// The wallet's block at `height` is no longer on the server's chain
+    ReorgDetected { height: u64 },
+    /// The witness trees take in the blocks up to `height`
+    WitnessesUpdated { height: u64 },
+    /// A batch finished, and the ETA is for the rest of the sync
+    BatchFinished {
+        batch_num: usize,
+        blocks_synced: u64,
+        eta: Option<Duration>,
+    },
+    /// The sync stopped on an error
+    Error(String),
+}
+
+/// Sends the [`SyncEvent`]s of the syncs, and keeps the throughput their ETAs come from.
+#[derive(Clone, Debug)]
+pub(crate) struct SyncEventSender {
+    sender: broadcast::Sender<SyncEvent>,
+    throughput: Arc<Mutex<Throughput>>,
+}
+
+impl SyncEventSender {
+    pub(crate) fn new() -> Self {
+        Self {
+            sender: broadcast::channel(SYNC_EVENT_CAPACITY).0,
+            throughput: Arc::new(Mutex::new(Throughput::default())),
+        }
+    }
+
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<SyncEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Sends the `event` to the subscribers, if there are any
+    pub(crate) fn send(&self, event: SyncEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    /// Starts measuring the throughput of a sync, which carries over from each pass over the
+    /// chain to the next
+    pub(crate) fn begin(&self) {
+        *self.throughput.lock().unwrap() = Throughput::new(0, Instant::now());
+    }
+
+    /// Starts measuring the progress of a pass over `blocks_total` blocks, which scans them
+    /// for every wallet taking part at once
+    pub(crate) fn start(&self, blocks_total: u64) {
+        self.throughput.lock().unwrap().start_pass(blocks_total);
+    }
+
+    /// Records that `blocks` more blocks were scanned, the lowest at `height`
+    pub(crate) fn blocks_scanned(&self, height: u64, blocks: u64) {
+        let event = {
+            let mut throughput = self.throughput.lock().unwrap();
+            throughput.record(blocks, Instant::now());
+            SyncEvent::BlocksScanned {
+                height,
+                blocks_done: throughput.blocks_done,
+                blocks_total: throughput.blocks_total,
+                eta: throughput.eta(),
+            }
+        };
+        self.send(event);
+    }
+
+    pub(crate) fn eta(&self) -> Option<Duration> {
+        self.throughput.lock().unwrap().eta()
+    }
+}
+
+/// The blocks scanned over the last [`THROUGHPUT_WINDOW`], as the time and the running total
+/// after each update, oldest first. The running total is of every pass of the sync, while the
+/// counts the ETA is for are of the current pass.
+#[derive(Debug, Default)]
+struct Throughput {
+    blocks_total: u64,
+    blocks_done: u64,
+    blocks_scanned: u64,
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl Throughput {
+    fn new(blocks_total: u64, now: Instant) -> Self {
+        Self {
+            blocks_total,
+            blocks_done: 0,
+            blocks_scanned: 0,
+            samples: VecDeque::from([(now, 0)]),
+        }
+    }
+
+    fn start_pass(&mut self, blocks_total: u64) {
+        self.blocks_total = blocks_total;
+        self.blocks_done = 0;
+    }
+
+    fn record(&mut self, blocks: u64, now: Instant) {
+        self.blocks_done += blocks;
+        self.blocks_scanned += blocks;
+        self.samples.push_back((now, self.blocks_scanned));
+        // The last sample from before the window is where the window's rate is measured from
+        while self.samples.len() > 2 && now.duration_since(self.samples[1].0) >= THROUGHPUT_WINDOW {
+            self.samples.pop_front();
+        }
+    }
+
+    fn eta(&self) -> Option<Duration> {
+        let (first_time, first_done) = *self.samples.front()?;
+        let (last_time, last_done) = *self.samples.back()?;
+        let blocks = last_done - first_done;
+        if blocks == 0 {
+            return None;
+        }
+        let remaining = self.blocks_total.saturating_sub(self.blocks_done);
+        Some(
+            last_time
+                .duration_since(first_time)
+                .mul_f64(remaining as f64 / blocks as f64),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::Throughput;
+
+    #[test]
+    fn eta_follows_recent_throughput() {
+        let start = Instant::now();
+        let mut throughput = Throughput::new(1_000, start);
+        assert_eq!(throughput.eta(), None);
+
+        throughput.record(100, start + Duration::from_secs(10));
+        assert_eq!(throughput.eta(), Some(Duration::from_secs(90)));
+
+        // The first ten seconds fall out of the window, so the slower rate since counts
+        throughput.record(100, start + Duration::from_secs(80));
+        assert_eq!(throughput.eta(), Some(Duration::from_secs(560)));
+
+        // The next wallet is scanned at the same rate
+        throughput.start_pass(500);
+        assert_eq!(throughput.eta(), Some(Duration::from_secs(350)));
+    }
+}
@@ -15,6 +15,8 @@ pub struct BatchSyncStatus {
     pub blocks_done: u64,
     pub trial_dec_done: u64,
     pub txn_scan_done: u64,
+    /// The shielded outputs in the blocks done
+    pub outputs_done: u64,
 
     pub witnesses_updated: HashMap<PoolNullifier, u64>,
 
@@ -22,6 +24,9 @@ pub struct BatchSyncStatus {
 
     pub batch_num: usize,
     pub batch_total: usize,
+    /// The number of blocks in each batch of this sync so far, as the batches adapt to the
+    /// blocks
+    pub batch_sizes: Vec<u64>,
 }
 
 impl BatchSyncStatus {
@@ -34,9 +39,11 @@ impl BatchSyncStatus {
         self.trial_dec_done = 0;
         self.blocks_total = 0;
         self.txn_scan_done = 0;
+        self.outputs_done = 0;
         self.witnesses_updated = HashMap::new();
         self.batch_num = 0;
         self.batch_total = batch_total;
+        self.batch_sizes = vec![];
     }
 
     /// Setup a new sync status in prep for an upcoming sync
@@ -55,7 +62,9 @@ impl BatchSyncStatus {
         self.blocks_total = 0;
         self.witnesses_updated = HashMap::new();
         self.txn_scan_done = 0;
+        self.outputs_done = 0;
         self.batch_num = batch_num;
+        self.batch_sizes.push(start_block - end_block + 1);
     }
 
     /// Finish up a sync
@@ -69,9 +78,12 @@ impl fmt::Display for BatchSyncStatus {
         if self.blocks_total > 0 && self.in_progress {
             write!(
                 f,
-                "**Batch** Current: {:4} Total: {:4}\n   \
+                "**Batch** Current: {:4} Total: {:4} Size: {:4}\n   \
                 **Blocks** Current Height: {:4}",
-                self.batch_num, self.batch_total, self.start_block,
+                self.batch_num,
+                self.batch_total,
+                self.start_block - self.end_block + 1,
+                self.start_block,
             )
         } else {
             write!(
@@ -4,7 +4,10 @@ use http::Uri;
 use tokio::sync::RwLock;
 use zcash_client_backend::proto::service::TreeState;
 
-use super::{block_management_reorg_detection::BlockManagementData, sync_status::BatchSyncStatus};
+use super::{
+    block_management_reorg_detection::BlockManagementData, sync_events::SyncEvent,
+    sync_status::BatchSyncStatus,
+};
 use crate::wallet::data::BlockData;
 use crate::wallet::WalletOptions;
 use zingoconfig::ZingoConfig;
@@ -13,16 +16,35 @@ pub struct BlazeSyncData {
     pub(crate) block_data: BlockManagementData,
     uri: Arc<std::sync::RwLock<Uri>>,
     pub(crate) wallet_options: WalletOptions,
+    /// Trial decrypts compact blocks across cores, when more than one thread is configured
+    pub(crate) trial_decryption_pool: Option<Arc<rayon::ThreadPool>>,
 }
 
 impl BlazeSyncData {
     pub fn new(config: &ZingoConfig) -> Self {
         let sync_status = Arc::new(RwLock::new(BatchSyncStatus::default()));
 
+        let trial_decryption_pool = if config.trial_decryption_threads > 1 {
+            match rayon::ThreadPoolBuilder::new()
+                .num_threads(config.trial_decryption_threads)
+                .thread_name(|index| format!("trial-decryption-{index}"))
+                .build()
+            {
+                Ok(pool) => Some(Arc::new(pool)),
+                Err(e) => {
+                    log::warn!("Could not start trial decryption threads: {e}");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
         Self {
             uri: config.lightwalletd_uri.clone(),
             block_data: BlockManagementData::new(sync_status),
             wallet_options: WalletOptions::default(),
+            trial_decryption_pool,
         }
     }
 
@@ -47,11 +69,17 @@ impl BlazeSyncData {
         }
 
         // Clear the status for a new sync batch
-        self.block_data
-            .sync_status
-            .write()
-            .await
-            .new_sync_batch(start_block, end_block, batch_num);
+        let batch_total = {
+            let mut sync_status = self.block_data.sync_status.write().await;
+            sync_status.new_sync_batch(start_block, end_block, batch_num);
+            sync_status.batch_total
+        };
+        self.block_data.sync_events.send(SyncEvent::BatchStarted {
+            batch_num,
+            batch_total,
+            start_block,
+            end_block,
+        });
 
         self.wallet_options = wallet_options;
 
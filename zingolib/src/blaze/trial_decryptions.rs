@@ -11,7 +11,7 @@ use crate::wallet::{
     traits::{CompactOutput as _, DomainWalletExt, FromCommitment, Recipient},
     transactions::TransactionMetadataSet,
     utils::txid_from_slice,
-    MemoDownloadOption,
+    MemoDownloadOption, Pool,
 };
 use futures::{stream::FuturesUnordered, StreamExt};
 use incrementalmerkletree::{Position, Retention};
@@ -30,45 +30,38 @@ use tokio::{
 use zcash_client_backend::proto::compact_formats::{CompactBlock, CompactTx};
 use zcash_note_encryption::Domain;
 use zcash_primitives::{
-    consensus::{BlockHeight, Parameters},
+    consensus::{BlockHeight, NetworkUpgrade, Parameters},
     transaction::{Transaction, TxId},
 };
 use zingo_status::confirmation_status::ConfirmationStatus;
 use zingoconfig::ZingoConfig;
 
-use super::syncdata::BlazeSyncData;
+use super::{
+    fetch_full_transaction::TransactionContext, sync_events::SyncEvent, syncdata::BlazeSyncData,
+};
 
 pub struct TrialDecryptions {
-    wc: Arc<WalletCapability>,
-    transaction_metadata_set: Arc<RwLock<TransactionMetadataSet>>,
+    /// The wallets whose keys every output is tried with, each note going to the wallet
+    /// whose key decrypted it
+    wallets: Vec<TransactionContext>,
     config: Arc<ZingoConfig>,
 }
 
 impl TrialDecryptions {
-    pub fn new(
-        config: Arc<ZingoConfig>,
-        wc: Arc<WalletCapability>,
-        transaction_metadata_set: Arc<RwLock<TransactionMetadataSet>>,
-    ) -> Self {
-        Self {
-            config,
-            wc,
-            transaction_metadata_set,
-        }
+    pub fn new(config: Arc<ZingoConfig>, wallets: Vec<TransactionContext>) -> Self {
+        Self { config, wallets }
     }
 
     /// Pass keys and data store to dedicated trial_decrpytion *management* thread,
     /// the *management* thread in turns spawns per-1000-cb trial decryption threads.
+    /// The transactions found for each wallet are sent to its own one of the
+    /// `detected_transaction_id_senders`.
     pub async fn start(
         &self,
         bsync_data: Arc<RwLock<BlazeSyncData>>,
-        detected_transaction_id_sender: UnboundedSender<(
-            TxId,
-            PoolNullifier,
-            BlockHeight,
-            Option<u32>,
-            bool,
-        )>,
+        detected_transaction_id_senders: Vec<
+            UnboundedSender<(TxId, PoolNullifier, BlockHeight, Option<u32>, bool)>,
+        >,
         transaction_size_filter: Option<u32>,
         full_transaction_fetcher: UnboundedSender<(
             TxId,
@@ -80,16 +73,21 @@ impl TrialDecryptions {
         // Create a new channel where we'll receive the blocks
         let (transmitter, mut receiver) = unbounded_channel::<CompactBlock>();
 
-        let wc = self.wc.clone();
-        let transaction_metadata_set = self.transaction_metadata_set.clone();
+        let wallets = self.wallets.clone();
 
         let config = self.config.clone();
         let management_thread_handle = tokio::spawn(async move {
             let mut workers = FuturesUnordered::new();
             let mut cbs = vec![];
 
-            let sapling_ivk = SaplingIvk::try_from(&*wc).ok();
-            let orchard_ivk = orchard::keys::IncomingViewingKey::try_from(&*wc).ok();
+            let sapling_ivks = wallets
+                .iter()
+                .map(|wallet| SaplingIvk::try_from(&*wallet.key).ok())
+                .collect::<Vec<_>>();
+            let orchard_ivks = wallets
+                .iter()
+                .map(|wallet| OrchardIvk::try_from(&*wallet.key).ok())
+                .collect::<Vec<_>>();
 
             while let Some(cb) = receiver.recv().await {
                 cbs.push(cb);
@@ -98,13 +96,12 @@ impl TrialDecryptions {
             workers.push(tokio::spawn(Self::trial_decrypt_batch(
                 config,
                 cbs,
-                wc,
+                wallets,
                 bsync_data,
-                sapling_ivk,
-                orchard_ivk,
-                transaction_metadata_set,
+                sapling_ivks,
+                orchard_ivks,
                 transaction_size_filter,
-                detected_transaction_id_sender,
+                detected_transaction_id_senders,
                 full_transaction_fetcher,
             )));
 
@@ -125,19 +122,14 @@ impl TrialDecryptions {
     async fn trial_decrypt_batch(
         config: Arc<ZingoConfig>,
         compact_blocks: Vec<CompactBlock>,
-        wc: Arc<WalletCapability>,
+        wallets: Vec<TransactionContext>,
         bsync_data: Arc<RwLock<BlazeSyncData>>,
-        sapling_ivk: Option<SaplingIvk>,
-        orchard_ivk: Option<OrchardIvk>,
-        transaction_metadata_set: Arc<RwLock<TransactionMetadataSet>>,
+        sapling_ivks: Vec<Option<SaplingIvk>>,
+        orchard_ivks: Vec<Option<OrchardIvk>>,
         transaction_size_filter: Option<u32>,
-        detected_transaction_id_sender: UnboundedSender<(
-            TxId,
-            PoolNullifier,
-            BlockHeight,
-            Option<u32>,
-            bool,
-        )>,
+        detected_transaction_id_senders: Vec<
+            UnboundedSender<(TxId, PoolNullifier, BlockHeight, Option<u32>, bool)>,
+        >,
         full_transaction_fetcher: UnboundedSender<(
             TxId,
             oneshot::Sender<Result<Transaction, String>>,
@@ -146,71 +138,131 @@ impl TrialDecryptions {
         let mut workers = FuturesUnordered::new();
 
         let download_memos = bsync_data.read().await.wallet_options.download_memos;
-        let mut sapling_notes_to_mark_position = Vec::new();
-        let mut orchard_notes_to_mark_position = Vec::new();
+        // Every wallet's witness trees take every commitment, marking only its own notes
+        let mut sapling_notes_to_mark_position = vec![Vec::new(); wallets.len()];
+        let mut orchard_notes_to_mark_position = vec![Vec::new(); wallets.len()];
+
+        // The decryptions are the costly part, so they can be spread over a thread pool. The
+        // results stay in block order, as the witness trees are built in that order.
+        let trial_decryption_pool = bsync_data.read().await.trial_decryption_pool.clone();
+        let blocks_scanned = compact_blocks.len() as u64;
+        let lowest_height = compact_blocks.iter().map(|block| block.height).min();
+        let (compact_blocks, decrypted_blocks) = match trial_decryption_pool {
+            Some(pool) => {
+                let config = config.clone();
+                tokio::task::spawn_blocking(move || {
+                    use rayon::prelude::*;
+                    let decrypted_blocks = pool.install(|| {
+                        compact_blocks
+                            .par_iter()
+                            .map(|compact_block| {
+                                decrypt_block(
+                                    compact_block,
+                                    &sapling_ivks,
+                                    &orchard_ivks,
+                                    &config,
+                                    transaction_size_filter,
+                                )
+                            })
+                            .collect::<Vec<_>>()
+                    });
+                    (compact_blocks, decrypted_blocks)
+                })
+                .await
+                .map_err(|e| e.to_string())?
+            }
+            None => {
+                let decrypted_blocks = compact_blocks
+                    .iter()
+                    .map(|compact_block| {
+                        decrypt_block(
+                            compact_block,
+                            &sapling_ivks,
+                            &orchard_ivks,
+                            &config,
+                            transaction_size_filter,
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                (compact_blocks, decrypted_blocks)
+            }
+        };
 
-        for compact_block in compact_blocks {
+        for (compact_block, decrypted_transactions) in
+            compact_blocks.into_iter().zip(decrypted_blocks)
+        {
             let height = BlockHeight::from_u32(compact_block.height as u32);
-            let mut sapling_notes_to_mark_position_in_block = Vec::new();
-            let mut orchard_notes_to_mark_position_in_block = Vec::new();
-
-            for (transaction_num, compact_transaction) in compact_block.vtx.iter().enumerate() {
-                let mut sapling_notes_to_mark_position_in_tx =
-                    zip_outputs_with_retention_txids_indexes::<SaplingDomain>(compact_transaction);
-                let mut orchard_notes_to_mark_position_in_tx =
-                    zip_outputs_with_retention_txids_indexes::<OrchardDomain>(compact_transaction);
-
-                if let Some(filter) = transaction_size_filter {
-                    if compact_transaction.outputs.len() + compact_transaction.actions.len()
-                        > filter as usize
-                    {
-                        sapling_notes_to_mark_position_in_block
-                            .extend_from_slice(&sapling_notes_to_mark_position_in_tx);
-                        orchard_notes_to_mark_position_in_block
-                            .extend_from_slice(&orchard_notes_to_mark_position_in_tx);
-                        continue;
-                    }
-                }
-                let mut transaction_metadata = false;
-                if let Some(ref ivk) = sapling_ivk {
-                    Self::trial_decrypt_domain_specific_outputs::<SaplingDomain>(
-                        &mut transaction_metadata,
-                        compact_transaction,
-                        transaction_num,
-                        &compact_block,
-                        sapling_crypto::note_encryption::PreparedIncomingViewingKey::new(ivk),
-                        height,
-                        &config,
-                        &wc,
-                        &bsync_data,
-                        &transaction_metadata_set,
-                        &detected_transaction_id_sender,
-                        &workers,
-                        &mut sapling_notes_to_mark_position_in_tx,
-                    )
-                };
-
-                sapling_notes_to_mark_position_in_block
-                    .extend_from_slice(&sapling_notes_to_mark_position_in_tx);
-                if let Some(ref ivk) = orchard_ivk {
-                    Self::trial_decrypt_domain_specific_outputs::<OrchardDomain>(
-                        &mut transaction_metadata,
-                        compact_transaction,
-                        transaction_num,
-                        &compact_block,
-                        orchard::keys::PreparedIncomingViewingKey::new(ivk),
-                        height,
-                        &config,
-                        &wc,
-                        &bsync_data,
-                        &transaction_metadata_set,
-                        &detected_transaction_id_sender,
-                        &workers,
-                        &mut orchard_notes_to_mark_position_in_tx,
-                    )
+            let mut sapling_notes_to_mark_position_in_block = vec![Vec::new(); wallets.len()];
+            let mut orchard_notes_to_mark_position_in_block = vec![Vec::new(); wallets.len()];
+
+            for ((transaction_num, compact_transaction), decrypted) in compact_block
+                .vtx
+                .iter()
+                .enumerate()
+                .zip(decrypted_transactions)
+            {
+                let mut sapling_notes_to_mark_position_in_tx = vec![
+                        zip_outputs_with_retention_txids_indexes::<SaplingDomain>(
+                            compact_transaction
+                        );
+                        wallets.len()
+                    ];
+                let mut orchard_notes_to_mark_position_in_tx = vec![
+                        zip_outputs_with_retention_txids_indexes::<OrchardDomain>(
+                            compact_transaction
+                        );
+                        wallets.len()
+                    ];
+
+                // Filtered out transactions only add their commitments to the trees
+                let Some((sapling_decrypted, orchard_decrypted)) = decrypted else {
+                    extend_each(
+                        &mut sapling_notes_to_mark_position_in_block,
+                        sapling_notes_to_mark_position_in_tx,
+                    );
+                    extend_each(
+                        &mut orchard_notes_to_mark_position_in_block,
+                        orchard_notes_to_mark_position_in_tx,
+                    );
+                    continue;
                 };
-                orchard_notes_to_mark_position_in_block
-                    .extend_from_slice(&orchard_notes_to_mark_position_in_tx);
+                let mut transaction_metadata = false;
+                Self::trial_decrypt_domain_specific_outputs::<SaplingDomain>(
+                    &mut transaction_metadata,
+                    compact_transaction,
+                    transaction_num,
+                    &compact_block,
+                    sapling_decrypted,
+                    height,
+                    &config,
+                    &wallets,
+                    &bsync_data,
+                    &detected_transaction_id_senders,
+                    &workers,
+                    &mut sapling_notes_to_mark_position_in_tx,
+                );
+                extend_each(
+                    &mut sapling_notes_to_mark_position_in_block,
+                    sapling_notes_to_mark_position_in_tx,
+                );
+                Self::trial_decrypt_domain_specific_outputs::<OrchardDomain>(
+                    &mut transaction_metadata,
+                    compact_transaction,
+                    transaction_num,
+                    &compact_block,
+                    orchard_decrypted,
+                    height,
+                    &config,
+                    &wallets,
+                    &bsync_data,
+                    &detected_transaction_id_senders,
+                    &workers,
+                    &mut orchard_notes_to_mark_position_in_tx,
+                );
+                extend_each(
+                    &mut orchard_notes_to_mark_position_in_block,
+                    orchard_notes_to_mark_position_in_tx,
+                );
 
                 // Check option to see if we are fetching all transactions.
                 // grabs all memos regardless of decryption status.
@@ -229,24 +281,63 @@ impl TrialDecryptions {
                     }));
                 }
             }
-            sapling_notes_to_mark_position.push((sapling_notes_to_mark_position_in_block, height));
-            orchard_notes_to_mark_position.push((orchard_notes_to_mark_position_in_block, height));
+            let (sapling_tree_size, orchard_tree_size) = compact_block
+                .chain_metadata
+                .as_ref()
+                .map(|metadata| {
+                    (
+                        Some(metadata.sapling_commitment_tree_size),
+                        Some(metadata.orchard_commitment_tree_size),
+                    )
+                })
+                .unwrap_or_default();
+            for (positions, in_block) in sapling_notes_to_mark_position
+                .iter_mut()
+                .zip(sapling_notes_to_mark_position_in_block)
+            {
+                positions.push((in_block, height, sapling_tree_size));
+            }
+            for (positions, in_block) in orchard_notes_to_mark_position
+                .iter_mut()
+                .zip(orchard_notes_to_mark_position_in_block)
+            {
+                positions.push((in_block, height, orchard_tree_size));
+            }
         }
 
         while let Some(r) = workers.next().await {
             r.map_err(|e| e.to_string())??;
         }
-        let mut txmds_writelock = transaction_metadata_set.write().await;
-        update_witnesses::<SaplingDomain>(
-            sapling_notes_to_mark_position,
-            &mut txmds_writelock,
-            &wc,
-        )?;
-        update_witnesses::<OrchardDomain>(
-            orchard_notes_to_mark_position,
-            &mut txmds_writelock,
-            &wc,
-        )?;
+        let witnessed_height = sapling_notes_to_mark_position
+            .first()
+            .into_iter()
+            .flatten()
+            .map(|(_, height, _)| u64::from(*height))
+            .max();
+        for ((wallet, sapling_positions), orchard_positions) in wallets
+            .iter()
+            .zip(sapling_notes_to_mark_position)
+            .zip(orchard_notes_to_mark_position)
+        {
+            let mut txmds_writelock = wallet.transaction_metadata_set.write().await;
+            update_witnesses::<SaplingDomain>(
+                sapling_positions,
+                &mut txmds_writelock,
+                &wallet.key,
+            )?;
+            update_witnesses::<OrchardDomain>(
+                orchard_positions,
+                &mut txmds_writelock,
+                &wallet.key,
+            )?;
+        }
+        let sync_events = bsync_data.read().await.block_data.sync_events.clone();
+        if let Some(height) = witnessed_height {
+            sync_events.send(SyncEvent::WitnessesUpdated { height });
+        }
+        if let Some(height) = lowest_height {
+            sync_events.blocks_scanned(height, blocks_scanned);
+        }
 
         // Return a nothing-value
         Ok::<(), String>(())
@@ -257,26 +348,25 @@ impl TrialDecryptions {
         compact_transaction: &CompactTx,
         transaction_num: usize,
         compact_block: &CompactBlock,
-        ivk: D::IncomingViewingKey,
+        maybe_decrypted_outputs: DecryptedOutputs<D>,
         height: BlockHeight,
         config: &zingoconfig::ZingoConfig,
-        wc: &Arc<WalletCapability>,
+        wallets: &[TransactionContext],
         bsync_data: &Arc<RwLock<BlazeSyncData>>,
-        transaction_metadata_set: &Arc<RwLock<TransactionMetadataSet>>,
-        detected_transaction_id_sender: &UnboundedSender<(
+        detected_transaction_id_senders: &[UnboundedSender<(
             TxId,
             PoolNullifier,
             BlockHeight,
             Option<u32>,
             bool,
-        )>,
+        )>],
         workers: &FuturesUnordered<JoinHandle<Result<(), String>>>,
-        notes_to_mark_position: &mut [(
+        notes_to_mark_position: &mut [Vec<(
             u32,
             TxId,
             <D::WalletNote as ShieldedNoteInterface>::Node,
             Retention<BlockHeight>,
-        )],
+        )>],
     ) where
         D: DomainWalletExt,
         <D as Domain>::Recipient: crate::wallet::traits::Recipient + Send + 'static,
@@ -285,95 +375,196 @@ impl TrialDecryptions {
         <<D as DomainWalletExt>::WalletNote as ShieldedNoteInterface>::Node: PartialEq,
     {
         let transaction_id = txid_from_slice(&compact_transaction.hash);
-        let outputs = D::CompactOutput::from_compact_transaction(compact_transaction)
-            .iter()
-            .map(|output| {
-                (
-                    output.domain(config.chain, height),
-                    output.to_compact_output_impl(),
-                )
-            })
-            .collect::<Vec<_>>();
-        let maybe_decrypted_outputs =
-            zcash_note_encryption::batch::try_compact_note_decryption(&[ivk], &outputs);
         for maybe_decrypted_output in maybe_decrypted_outputs.into_iter().enumerate() {
-            let (output_num, witnessed) =
-                if let (i, Some(((note, to), _ivk_num))) = maybe_decrypted_output {
-                    *transaction_metadata = true; // i.e. we got metadata
-
-                    let wc = wc.clone();
-                    let bsync_data = bsync_data.clone();
-                    let transaction_metadata_set = transaction_metadata_set.clone();
-                    let detected_transaction_id_sender = detected_transaction_id_sender.clone();
-                    let timestamp = compact_block.time as u64;
-                    let config = config.clone();
-
-                    workers.push(tokio::spawn(async move {
-                        let Ok(fvk) = D::wc_to_fvk(&wc) else {
-                            // skip any scanning if the wallet doesn't have viewing capability
-                            return Ok::<_, String>(());
-                        };
-
-                        //TODO: Wrong. We don't have fvk import, all our keys are spending
-                        let have_spending_key = true;
-                        let uri = bsync_data.read().await.uri().clone();
-
-                        // Get the witness for the note
-                        let witness = bsync_data
-                            .read()
-                            .await
-                            .block_data
-                            .get_note_witness::<D>(
-                                uri,
-                                height,
-                                transaction_num,
-                                i,
-                                config.chain.activation_height(D::NU).unwrap().into(),
-                            )
-                            .await?;
-
-                        let spend_nullifier = D::get_nullifier_from_note_fvk_and_witness_position(
-                            &note,
-                            &fvk,
-                            u64::from(witness.witnessed_position()),
-                        );
-
-                        let status = ConfirmationStatus::Confirmed(height);
-                        transaction_metadata_set.write().await.add_new_note::<D>(
+            let (output_num, witnessed_by) = if let (i, Some(((note, to), wallet_num))) =
+                maybe_decrypted_output
+            {
+                *transaction_metadata = true; // i.e. we got metadata
+
+                let wc = wallets[wallet_num].key.clone();
+                let bsync_data = bsync_data.clone();
+                let transaction_metadata_set = wallets[wallet_num].transaction_metadata_set.clone();
+                let detected_transaction_id_sender =
+                    detected_transaction_id_senders[wallet_num].clone();
+                let timestamp = compact_block.time as u64;
+                let config = config.clone();
+
+                workers.push(tokio::spawn(async move {
+                    let Ok(fvk) = D::wc_to_fvk(&wc) else {
+                        // skip any scanning if the wallet doesn't have viewing capability
+                        return Ok::<_, String>(());
+                    };
+
+                    //TODO: Wrong. We don't have fvk import, all our keys are spending
+                    let have_spending_key = true;
+                    let uri = bsync_data.read().await.uri().clone();
+
+                    // Get the witness for the note
+                    let witness = bsync_data
+                        .read()
+                        .await
+                        .block_data
+                        .get_note_witness::<D>(
+                            uri,
+                            height,
+                            transaction_num,
+                            i,
+                            config.chain.activation_height(D::NU).unwrap().into(),
+                        )
+                        .await?;
+
+                    let spend_nullifier = D::get_nullifier_from_note_fvk_and_witness_position(
+                        &note,
+                        &fvk,
+                        u64::from(witness.witnessed_position()),
+                    );
+
+                    let status = ConfirmationStatus::Confirmed(height);
+                    transaction_metadata_set.write().await.add_new_note::<D>(
+                        transaction_id,
+                        status,
+                        timestamp,
+                        note,
+                        to,
+                        have_spending_key,
+                        Some(spend_nullifier),
+                        i as u32,
+                        witness.witnessed_position(),
+                    );
+
+                    debug!("Trial decrypt Detected txid {}", &transaction_id);
+                    bsync_data
+                        .read()
+                        .await
+                        .block_data
+                        .sync_events
+                        .send(SyncEvent::NoteFound {
+                            txid: transaction_id,
+                            height: u64::from(height),
+                            pool: match D::NU {
+                                NetworkUpgrade::Nu5 => Pool::Orchard,
+                                _ => Pool::Sapling,
+                            },
+                        });
+
+                    detected_transaction_id_sender
+                        .send((
                             transaction_id,
-                            status,
-                            timestamp,
-                            note,
-                            to,
-                            have_spending_key,
-                            Some(spend_nullifier),
-                            i as u32,
-                            witness.witnessed_position(),
-                        );
+                            spend_nullifier.into(),
+                            height,
+                            Some(i as u32),
+                            true,
+                        ))
+                        .unwrap();
 
-                        debug!("Trial decrypt Detected txid {}", &transaction_id);
+                    Ok::<_, String>(())
+                }));
+                (i, Some(wallet_num))
+            } else {
+                (maybe_decrypted_output.0, None)
+            };
+            if let Some(wallet_num) = witnessed_by {
+                notes_to_mark_position[wallet_num][output_num].3 = Retention::Marked
+            }
+        }
+    }
+}
 
-                        detected_transaction_id_sender
-                            .send((
-                                transaction_id,
-                                spend_nullifier.into(),
-                                height,
-                                Some(i as u32),
-                                true,
-                            ))
-                            .unwrap();
+/// The trial decryptions of a transaction's outputs in one pool, in order, each with the
+/// index of the wallet whose key decrypted it.
+type DecryptedOutputs<D> = Vec<Option<((<D as Domain>::Note, <D as Domain>::Recipient), usize)>>;
 
-                        Ok::<_, String>(())
-                    }));
-                    (i, true)
-                } else {
-                    (maybe_decrypted_output.0, false)
-                };
-            if witnessed {
-                notes_to_mark_position[output_num].3 = Retention::Marked
+/// Appends each wallet's positions in a transaction to its positions in the block.
+fn extend_each<T>(in_block: &mut [Vec<T>], in_transaction: Vec<Vec<T>>) {
+    for (in_block, in_transaction) in in_block.iter_mut().zip(in_transaction) {
+        in_block.extend(in_transaction);
+    }
+}
+
+/// Trial decrypts the Sapling and Orchard outputs of each of a block's transactions with
+/// the keys of every wallet at once, `None` where a wallet doesn't view the pool. Those
+/// with more outputs than the `transaction_size_filter` are skipped, as `None`.
+fn decrypt_block(
+    compact_block: &CompactBlock,
+    sapling_ivks: &[Option<SaplingIvk>],
+    orchard_ivks: &[Option<OrchardIvk>],
+    config: &ZingoConfig,
+    transaction_size_filter: Option<u32>,
+) -> Vec<
+    Option<(
+        DecryptedOutputs<SaplingDomain>,
+        DecryptedOutputs<OrchardDomain>,
+    )>,
+> {
+    let height = BlockHeight::from_u32(compact_block.height as u32);
+    compact_block
+        .vtx
+        .iter()
+        .map(|compact_transaction| {
+            if transaction_size_filter.is_some_and(|filter| {
+                compact_transaction.outputs.len() + compact_transaction.actions.len()
+                    > filter as usize
+            }) {
+                return None;
             }
-        }
+            Some((
+                decrypt_outputs::<SaplingDomain, _>(
+                    compact_transaction,
+                    sapling_ivks,
+                    sapling_crypto::note_encryption::PreparedIncomingViewingKey::new,
+                    config,
+                    height,
+                ),
+                decrypt_outputs::<OrchardDomain, _>(
+                    compact_transaction,
+                    orchard_ivks,
+                    orchard::keys::PreparedIncomingViewingKey::new,
+                    config,
+                    height,
+                ),
+            ))
+        })
+        .collect()
+}
+
+fn decrypt_outputs<D, Ivk>(
+    compact_transaction: &CompactTx,
+    ivks: &[Option<Ivk>],
+    prepare: impl Fn(&Ivk) -> D::IncomingViewingKey,
+    config: &ZingoConfig,
+    height: BlockHeight,
+) -> DecryptedOutputs<D>
+where
+    D: DomainWalletExt,
+    <D as Domain>::Recipient: crate::wallet::traits::Recipient + Send + 'static,
+    <D as Domain>::Note: PartialEq + Send + 'static + Clone,
+    <D as Domain>::ExtractedCommitmentBytes: Into<[u8; 32]>,
+    <<D as DomainWalletExt>::WalletNote as ShieldedNoteInterface>::Node: PartialEq,
+{
+    let (wallet_nums, ivks): (Vec<usize>, Vec<D::IncomingViewingKey>) = ivks
+        .iter()
+        .enumerate()
+        .filter_map(|(wallet_num, ivk)| ivk.as_ref().map(|ivk| (wallet_num, prepare(ivk))))
+        .unzip();
+    if ivks.is_empty() {
+        return vec![];
     }
+    let outputs = D::CompactOutput::from_compact_transaction(compact_transaction)
+        .iter()
+        .map(|output| {
+            (
+                output.domain(config.chain, height),
+                output.to_compact_output_impl(),
+            )
+        })
+        .collect::<Vec<_>>();
+    zcash_note_encryption::batch::try_compact_note_decryption(&ivks, &outputs)
+        .into_iter()
+        .map(|decrypted| {
+            decrypted
+                .map(|(note_and_recipient, ivk_num)| (note_and_recipient, wallet_nums[ivk_num]))
+        })
+        .collect()
 }
 
 #[allow(clippy::type_complexity)]
@@ -406,6 +597,10 @@ where
     .collect()
 }
 
+/// Appends each block's note commitments to the witness tree, and records the positions of
+/// the wallet's own notes. A block's first position is worked out from the tree size the
+/// server reports at its end, so that blocks need not be scanned in order. Servers that
+/// report no tree sizes are only scanned in order, so their blocks go after the last leaf.
 #[allow(clippy::type_complexity)]
 fn update_witnesses<D>(
     notes_to_mark_position: Vec<(
@@ -416,6 +611,7 @@ fn update_witnesses<D>(
             Retention<BlockHeight>,
         )>,
         BlockHeight,
+        Option<u32>,
     )>,
     txmds_writelock: &mut TransactionMetadataSet,
     wc: &Arc<WalletCapability>,
@@ -427,11 +623,18 @@ where
 {
     for block in notes_to_mark_position.into_iter().rev() {
         if let Some(witness_tree) = D::transaction_metadata_set_to_shardtree(&*txmds_writelock) {
-            let position = witness_tree
-                .max_leaf_position(0)
-                .unwrap()
-                .map(|pos| pos + 1)
-                .unwrap_or(Position::from(0));
+            let position = match block
+                .2
+                .filter(|tree_size| *tree_size != 0)
+                .and_then(|tree_size| u64::from(tree_size).checked_sub(block.0.len() as u64))
+            {
+                Some(start) => Position::from(start),
+                None => witness_tree
+                    .max_leaf_position(0)
+                    .unwrap()
+                    .map(|pos| pos + 1)
+                    .unwrap_or(Position::from(0)),
+            };
             let mut nodes_retention = Vec::new();
             for (i, (output_index, transaction_id, node, retention)) in
                 block.0.into_iter().enumerate()
@@ -458,3 +661,52 @@ where
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use incrementalmerkletree::{Hashable, Position, Retention};
+    use sapling_crypto::note_encryption::SaplingDomain;
+    use zcash_primitives::{consensus::BlockHeight, transaction::TxId};
+
+    use super::update_witnesses;
+    use crate::wallet::{keys::unified::WalletCapability, transactions::TransactionMetadataSet};
+
+    #[test]
+    fn blocks_start_where_their_tree_size_puts_them() {
+        let mut txmds = TransactionMetadataSet::new_with_witness_trees();
+        let wc = Arc::new(WalletCapability::default());
+        let block = |outputs: usize, height: u32, tree_size: Option<u32>| {
+            let commitments = (0..outputs)
+                .map(|_| {
+                    (
+                        0,
+                        TxId::from_bytes([0; 32]),
+                        sapling_crypto::Node::empty_leaf(),
+                        Retention::Ephemeral,
+                    )
+                })
+                .collect();
+            (commitments, BlockHeight::from_u32(height), tree_size)
+        };
+        let max_leaf = |txmds: &TransactionMetadataSet| {
+            txmds
+                .witness_trees
+                .as_ref()
+                .unwrap()
+                .witness_tree_sapling
+                .max_leaf_position(0)
+                .unwrap()
+        };
+
+        // A block scanned out of order ends where the server's tree size says, not after
+        // the leaves the tree has
+        update_witnesses::<SaplingDomain>(vec![block(2, 100, Some(10))], &mut txmds, &wc).unwrap();
+        assert_eq!(max_leaf(&txmds), Some(Position::from(9)));
+
+        // Without a tree size, the block goes after the last leaf
+        update_witnesses::<SaplingDomain>(vec![block(3, 101, None)], &mut txmds, &wc).unwrap();
+        assert_eq!(max_leaf(&txmds), Some(Position::from(12)));
+    }
+}
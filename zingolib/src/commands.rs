@@ -1,4 +1,6 @@
+use crate::wallet::batch::{PaymentBatch, DEFAULT_MAX_ACTIONS_PER_TRANSACTION};
 use crate::wallet::keys::is_shielded_address;
+use crate::wallet::proposal::PrivacyPolicy;
 use crate::wallet::{MemoDownloadOption, Pool};
 use crate::{lightclient::LightClient, wallet::utils};
 use indoc::indoc;
@@ -10,6 +12,7 @@ use std::str::FromStr;
 use tokio::runtime::Runtime;
 use zcash_address::unified::{Container, Encoding, Ufvk};
 use zcash_client_backend::address::Address;
+use zcash_primitives::memo::MemoBytes;
 use zcash_primitives::transaction::fees::zip317::MINIMUM_FEE;
 
 lazy_static! {
@@ -356,6 +359,7 @@ impl Command for SyncStatusCommand {
                     "total_blocks" => status.blocks_total,
                     "batch_num" => status.batch_num,
                     "batch_total" => status.batch_total,
+                    "batch_sizes" => status.batch_sizes,
                     "sync_interrupt" => lightclient.get_sync_interrupt().await
                 }
             } else {
@@ -559,9 +563,10 @@ impl Command for BalanceCommand {
         indoc! {r#"
             Show the current ZEC balance in the wallet
             Usage:
-            balance
+            balance [--account <account>]
 
             Transparent and Shielded balances, along with the addresses they belong to are displayed
+            The balance is of the primary account, unless another account of the seed is given.
         "#}
     }
 
@@ -569,9 +574,20 @@ impl Command for BalanceCommand {
         "Show the current ZEC balance in the wallet"
     }
 
-    fn exec(&self, _args: &[&str], lightclient: &LightClient) -> String {
+    fn exec(&self, args: &[&str], lightclient: &LightClient) -> String {
+        let account = match split_account(args, "--account") {
+            Ok((rest, account)) if rest.is_empty() => account,
+            Ok(_) => return self.help().to_string(),
+            Err(e) => return format!("{}\n{}", e, self.help()),
+        };
         RT.block_on(async move {
-            serde_json::to_string_pretty(&lightclient.do_balance().await).unwrap()
+            match account {
+                None => serde_json::to_string_pretty(&lightclient.do_balance().await).unwrap(),
+                Some(account) => match lightclient.do_account_balance(account).await {
+                    Ok(balance) => serde_json::to_string_pretty(&balance).unwrap(),
+                    Err(e) => object! { "error" => e }.pretty(2),
+                },
+            }
         })
     }
 }
@@ -582,8 +598,9 @@ impl Command for AddressCommand {
         indoc! {r#"
             List current addresses in the wallet
             Usage:
-            address
+            address [--account <account>]
 
+            The addresses are of the primary account, unless another account of the seed is given.
         "#}
     }
 
@@ -591,8 +608,131 @@ impl Command for AddressCommand {
         "List all addresses in the wallet"
     }
 
+    fn exec(&self, args: &[&str], lightclient: &LightClient) -> String {
+        let account = match split_account(args, "--account") {
+            Ok((rest, account)) if rest.is_empty() => account,
+            Ok(_) => return self.help().to_string(),
+            Err(e) => return format!("{}\n{}", e, self.help()),
+        };
+        RT.block_on(async move {
+            match account {
+                None => lightclient.do_addresses().await,
+                Some(account) => match lightclient.do_account_addresses(account).await {
+                    Ok(addresses) => addresses,
+                    Err(e) => object! { "error" => e },
+                },
+            }
+            .pretty(2)
+        })
+    }
+}
+
+struct ImportUfvkCommand {}
+impl Command for ImportUfvkCommand {
+    fn help(&self) -> &'static str {
+        indoc! {r#"
+            Import a third party's unified full viewing key, to watch its transactions in this wallet.
+            The blocks already synced from the birthday on are scanned for it once, as it is imported,
+            and from then on it is synced along with the wallet.
+            Usage:
+            importufvk <ufvk> <birthday>
+
+            The imported keys are listed with importedkeys, and their transactions with summaries imported.
+        "#}
+    }
+
+    fn short_help(&self) -> &'static str {
+        "Watch a third party's unified full viewing key"
+    }
+
+    fn exec(&self, args: &[&str], lightclient: &LightClient) -> String {
+        if args.len() != 2 {
+            return self.help().to_string();
+        }
+        let birthday = match args[1].parse::<u64>() {
+            Ok(birthday) => birthday,
+            Err(e) => return format!("Couldn't parse birthday: {}\n{}", e, self.help()),
+        };
+        RT.block_on(async move {
+            match lightclient
+                .do_import_ufvk(args[0].to_string(), birthday)
+                .await
+            {
+                Ok(imported) => imported,
+                Err(e) => object! { "error" => e },
+            }
+            .pretty(2)
+        })
+    }
+}
+
+struct ImportedKeysCommand {}
+impl Command for ImportedKeysCommand {
+    fn help(&self) -> &'static str {
+        indoc! {r#"
+            List the viewing keys imported into this wallet, with their birthdays and balances
+            Usage:
+            importedkeys
+        "#}
+    }
+
+    fn short_help(&self) -> &'static str {
+        "List the imported viewing keys"
+    }
+
+    fn exec(&self, _args: &[&str], lightclient: &LightClient) -> String {
+        RT.block_on(async move { lightclient.do_list_imported_keys().await.pretty(2) })
+    }
+}
+
+struct AccountsCommand {}
+impl Command for AccountsCommand {
+    fn help(&self) -> &'static str {
+        indoc! {r#"
+            List the accounts of the seed in this wallet, with the first address of each
+            Usage:
+            accounts
+
+            Accounts other than the primary one are added with the newaccount command.
+        "#}
+    }
+
+    fn short_help(&self) -> &'static str {
+        "List the accounts of the seed in this wallet"
+    }
+
+    fn exec(&self, _args: &[&str], lightclient: &LightClient) -> String {
+        RT.block_on(async move { lightclient.do_list_accounts().await.pretty(2) })
+    }
+}
+
+struct NewAccountCommand {}
+impl Command for NewAccountCommand {
+    fn help(&self) -> &'static str {
+        indoc! {r#"
+            Derive the next account of the seed. It has its own addresses and balance. The blocks
+            already synced are scanned for it once, as it is added, and from then on it is synced
+            along with the primary account.
+            Usage:
+            newaccount
+
+            Select the account in balance and addresses with --account, and in send, sendall, propose
+            and shield with --from-account.
+        "#}
+    }
+
+    fn short_help(&self) -> &'static str {
+        "Derive the next account of the seed"
+    }
+
     fn exec(&self, _args: &[&str], lightclient: &LightClient) -> String {
-        RT.block_on(async move { lightclient.do_addresses().await.pretty(2) })
+        RT.block_on(async move {
+            match lightclient.do_new_account().await {
+                Ok(account) => object! { "account" => account },
+                Err(e) => object! { "error" => e },
+            }
+            .pretty(2)
+        })
     }
 }
 
@@ -636,9 +776,10 @@ impl Command for ShieldCommand {
         indoc! {r#"
             Shield all your transparent and/or orchard funds
             Usage:
-            shield ['transparent' or 'sapling' or 'all'] [optional address]
+            shield ['transparent' or 'sapling' or 'all'] [optional address] [--from-account <account>]
+            Without an address, an account's funds are shielded to its own first address.
 
-            NOTE: The fee required to send this transaction (currently ZEC 0.0001) is additionally deducted from your balance.
+            NOTE: The ZIP-317 fee required to send this transaction (ZEC 0.00005 per logical action, minimum ZEC 0.0001) is additionally deducted from your balance.
             Example:
             shield all
 
@@ -650,6 +791,10 @@ impl Command for ShieldCommand {
     }
 
     fn exec(&self, args: &[&str], lightclient: &LightClient) -> String {
+        let (args, account) = match split_account(args, "--from-account") {
+            Ok(split) => split,
+            Err(e) => return format!("{}\n{}", e, self.help()),
+        };
         if args.is_empty() || args.len() > 2 {
             return self.help().to_string();
         }
@@ -666,7 +811,15 @@ impl Command for ShieldCommand {
             None
         };
         RT.block_on(async move {
-            match lightclient.do_shield(pools_to_shield, address).await {
+            let result = match account {
+                None => lightclient.do_shield(pools_to_shield, address).await,
+                Some(account) => {
+                    lightclient
+                        .do_shield_from_account(account, pools_to_shield, address)
+                        .await
+                }
+            };
+            match result {
                 Ok(transaction_id) => {
                     object! { "txid" => transaction_id }
                 }
@@ -679,240 +832,966 @@ impl Command for ShieldCommand {
     }
 }
 
-struct EncryptMessageCommand {}
-impl Command for EncryptMessageCommand {
+struct EncryptMessageCommand {}
+impl Command for EncryptMessageCommand {
+    fn help(&self) -> &'static str {
+        indoc! {r#"
+            Encrypt a memo to be sent to a z-address offline
+            Usage:
+            encryptmessage <address> "memo"
+            OR
+            encryptmessage "{'address': <address>, 'memo': <memo>}"
+
+            NOTE: This command only returns the encrypted payload. It does not broadcast it. You are expected to send the encrypted payload to the recipient offline
+            Example:
+            encryptmessage ztestsapling1x65nq4dgp0qfywgxcwk9n0fvm4fysmapgr2q00p85ju252h6l7mmxu2jg9cqqhtvzd69jwhgv8d "Hello from the command line"
+
+        "#}
+    }
+
+    fn short_help(&self) -> &'static str {
+        "Encrypt a memo to be sent to a z-address offline"
+    }
+
+    fn exec(&self, args: &[&str], lightclient: &LightClient) -> String {
+        if args.is_empty() || args.len() > 3 {
+            return self.help().to_string();
+        }
+
+        // Check for a single argument that can be parsed as JSON
+        let (to, memo) = if args.len() == 1 {
+            let arg_list = args[0];
+            let j = match json::parse(arg_list) {
+                Ok(j) => j,
+                Err(e) => {
+                    let es = format!("Couldn't understand JSON: {}", e);
+                    return format!("{}\n{}", es, self.help());
+                }
+            };
+
+            if !j.has_key("address") || !j.has_key("memo") {
+                let es = "Need 'address' and 'memo'\n".to_string();
+                return format!("{}\n{}", es, self.help());
+            }
+
+            let memo = utils::interpret_memo_string(j["memo"].as_str().unwrap().to_string());
+            if memo.is_err() {
+                return format!("{}\n{}", memo.err().unwrap(), self.help());
+            }
+            let to = j["address"].as_str().unwrap().to_string();
+
+            (to, memo.unwrap())
+        } else if args.len() == 2 {
+            let to = args[0].to_string();
+
+            let memo = utils::interpret_memo_string(args[1].to_string());
+            if memo.is_err() {
+                return format!("{}\n{}", memo.err().unwrap(), self.help());
+            }
+
+            (to, memo.unwrap())
+        } else {
+            return format!(
+                "Wrong number of arguments. Was expecting 1 or 2\n{}",
+                self.help()
+            );
+        };
+
+        if let Ok(m) = memo.try_into() {
+            lightclient.do_encrypt_message(to, m).pretty(2)
+        } else {
+            "Couldn't encode memo".to_string()
+        }
+    }
+}
+
+struct DecryptMessageCommand {}
+impl Command for DecryptMessageCommand {
+    fn help(&self) -> &'static str {
+        indoc! {r#"
+            Attempt to decrypt a message with all the view keys in the wallet.
+            Usage:
+            decryptmessage "encrypted_message_base64"
+
+            Example:
+            decryptmessage RW5jb2RlIGFyYml0cmFyeSBvY3RldHMgYXMgYmFzZTY0LiBSZXR1cm5zIGEgU3RyaW5nLg==
+
+        "#}
+    }
+
+    fn short_help(&self) -> &'static str {
+        "Attempt to decrypt a message with all the view keys in the wallet."
+    }
+
+    fn exec(&self, args: &[&str], lightclient: &LightClient) -> String {
+        if args.len() != 1 {
+            return self.help().to_string();
+        }
+
+        RT.block_on(async move {
+            lightclient
+                .do_decrypt_message(args[0].to_string())
+                .await
+                .pretty(2)
+        })
+    }
+}
+
+struct SendCommand {}
+impl Command for SendCommand {
+    fn help(&self) -> &'static str {
+        indoc! {r#"
+            Send ZEC to a given address(es)
+            Usage:
+            send <address> <amount in zatoshis> "optional_memo"
+            OR
+            send '[{'address': <address>, 'amount': <amount in zatoshis>, 'memo': <optional memo>}, ...]'
+            Either form accepts a trailing privacy policy:
+            send ... --privacy <full_privacy|allow_revealed_amounts|allow_revealed_recipients|allow_fully_transparent>
+            The default is allow_revealed_recipients. A send that would cross a boundary the policy forbids is refused.
+            To send from another account of the seed than the primary one:
+            send ... --from-account <account>
+
+            NOTE: The ZIP-317 fee required to send this transaction (ZEC 0.00005 per logical action, minimum ZEC 0.0001) is additionally deducted from your balance.
+            Example:
+            send ztestsapling1x65nq4dgp0qfywgxcwk9n0fvm4fysmapgr2q00p85ju252h6l7mmxu2jg9cqqhtvzd69jwhgv8d 200000 "Hello from the command line"
+
+        "#}
+    }
+
+    fn short_help(&self) -> &'static str {
+        "Send ZEC to the given address"
+    }
+
+    fn exec(&self, args: &[&str], lightclient: &LightClient) -> String {
+        let (args, account) = match split_account(args, "--from-account") {
+            Ok(split) => split,
+            Err(e) => return format!("{}\n{}", e, self.help()),
+        };
+        let (args, privacy_policy) = match split_privacy_policy(&args) {
+            Ok(split) => split,
+            Err(e) => return format!("{}\n{}", e, self.help()),
+        };
+        let send_args = match parse_send_args(&args, self.help(), lightclient) {
+            Ok(send_args) => send_args,
+            Err(e) => return e,
+        };
+        RT.block_on(async move {
+            let address_amount_memo_tuples = send_args
+                .iter()
+                .map(|(address, value, memo)| (address.as_str(), *value, memo.clone()))
+                .collect();
+            let result = match account {
+                None => {
+                    lightclient
+                        .do_send_with_privacy_policy(address_amount_memo_tuples, privacy_policy)
+                        .await
+                }
+                Some(account) => {
+                    lightclient
+                        .do_send_from_account(account, address_amount_memo_tuples, privacy_policy)
+                        .await
+                }
+            };
+            match result {
+                Ok(transaction_id) => {
+                    object! { "txid" => transaction_id }
+                }
+                Err(e) => {
+                    object! { "error" => e.to_string() }
+                }
+            }
+            .pretty(2)
+        })
+    }
+}
+
+struct SendAllCommand {}
+impl Command for SendAllCommand {
+    fn help(&self) -> &'static str {
+        indoc! {r#"
+            Send all spendable ZEC to a single address, less the fee.
+            Notes worth no more than the fee to spend them are left in the wallet and reported as dust.
+            Usage:
+            sendall <address> "optional_memo"
+            Accepts a trailing '--privacy <policy>', as for 'send'. Transparent funds are
+            only included with allow_fully_transparent.
+            And '--from-account <account>', to sweep another account of the seed.
+
+            Example:
+            sendall ztestsapling1x65nq4dgp0qfywgxcwk9n0fvm4fysmapgr2q00p85ju252h6l7mmxu2jg9cqqhtvzd69jwhgv8d "Sweeping the wallet"
+
+        "#}
+    }
+
+    fn short_help(&self) -> &'static str {
+        "Send all spendable ZEC to the given address"
+    }
+
+    fn exec(&self, args: &[&str], lightclient: &LightClient) -> String {
+        let (args, account) = match split_account(args, "--from-account") {
+            Ok(split) => split,
+            Err(e) => return format!("{}\n{}", e, self.help()),
+        };
+        let (args, privacy_policy) = match split_privacy_policy(&args) {
+            Ok(split) => split,
+            Err(e) => return format!("{}\n{}", e, self.help()),
+        };
+        if args.is_empty() || args.len() > 2 {
+            return self.help().to_string();
+        }
+        let address = args[0];
+        let memo = match args.get(1) {
+            Some(memo) => {
+                // Memo has to be None if not sending to a shielded address
+                if !is_shielded_address(address, &lightclient.config) {
+                    return format!("Can't send a memo to the non-shielded address {}", address);
+                }
+                match utils::interpret_memo_string(memo.to_string()) {
+                    Ok(memo) => Some(memo),
+                    Err(e) => return format!("Couldn't interpret memo: {}", e),
+                }
+            }
+            None => None,
+        };
+        RT.block_on(async move {
+            let result = match account {
+                None => lightclient.do_send_all(address, memo, privacy_policy).await,
+                Some(account) => {
+                    lightclient
+                        .do_send_all_from_account(account, address, memo, privacy_policy)
+                        .await
+                }
+            };
+            match result {
+                Ok(result) => result.to_json(),
+                Err(e) => {
+                    object! { "error" => e.to_string() }
+                }
+            }
+            .pretty(2)
+        })
+    }
+}
+
+struct ParsePaymentUriCommand {}
+impl Command for ParsePaymentUriCommand {
+    fn help(&self) -> &'static str {
+        indoc! {r#"
+            Decode a ZIP-321 payment request URI into its payments, without paying it.
+            Quote the URI, as it may contain '&'.
+            Usage:
+            parsepaymenturi "<zcash: uri>"
+
+            Example:
+            parsepaymenturi "zcash:ztestsapling1x65nq4dgp0qfywgxcwk9n0fvm4fysmapgr2q00p85ju252h6l7mmxu2jg9cqqhtvzd69jwhgv8d?amount=0.002&message=Coffee"
+
+        "#}
+    }
+
+    fn short_help(&self) -> &'static str {
+        "Decode a zcash: payment request URI"
+    }
+
+    fn exec(&self, args: &[&str], lightclient: &LightClient) -> String {
+        if args.len() != 1 {
+            return self.help().to_string();
+        }
+        match lightclient.do_parse_payment_uri(args[0]) {
+            Ok(request) => request.to_json(),
+            Err(e) => object! { "error" => e.to_string() },
+        }
+        .pretty(2)
+    }
+}
+
+struct SendPaymentUriCommand {}
+impl Command for SendPaymentUriCommand {
+    fn help(&self) -> &'static str {
+        indoc! {r#"
+            Pay a ZIP-321 payment request URI. Every payment in the URI must have an amount.
+            Quote the URI, as it may contain '&'.
+            Usage:
+            sendpaymenturi "<zcash: uri>"
+            Accepts a trailing '--privacy <policy>', as for 'send'.
+
+            Example:
+            sendpaymenturi "zcash:ztestsapling1x65nq4dgp0qfywgxcwk9n0fvm4fysmapgr2q00p85ju252h6l7mmxu2jg9cqqhtvzd69jwhgv8d?amount=0.002&message=Coffee"
+
+        "#}
+    }
+
+    fn short_help(&self) -> &'static str {
+        "Pay a zcash: payment request URI"
+    }
+
+    fn exec(&self, args: &[&str], lightclient: &LightClient) -> String {
+        let (args, privacy_policy) = match split_privacy_policy(args) {
+            Ok(split) => split,
+            Err(e) => return format!("{}\n{}", e, self.help()),
+        };
+        if args.len() != 1 {
+            return self.help().to_string();
+        }
+        RT.block_on(async move {
+            match lightclient
+                .do_send_payment_uri(args[0], privacy_policy)
+                .await
+            {
+                Ok(txid) => {
+                    object! { "txid" => txid }
+                }
+                Err(e) => {
+                    object! { "error" => e.to_string() }
+                }
+            }
+            .pretty(2)
+        })
+    }
+}
+
+struct MakePaymentUriCommand {}
+impl Command for MakePaymentUriCommand {
+    fn help(&self) -> &'static str {
+        indoc! {r#"
+            Create a ZIP-321 payment request URI for one of this wallet's addresses.
+            Usage:
+            makepaymenturi <address index> [amount in zatoshis] ["optional memo"] [--label <label>] [--message <message>]
+
+            Example:
+            makepaymenturi 0 200000 "Invoice 42" --message "Thanks for your order"
+
+        "#}
+    }
+
+    fn short_help(&self) -> &'static str {
+        "Create a zcash: payment request URI"
+    }
+
+    fn exec(&self, args: &[&str], lightclient: &LightClient) -> String {
+        let mut positional = vec![];
+        let mut label = None;
+        let mut message = None;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match *arg {
+                "--label" => label = args.next().map(|label| label.to_string()),
+                "--message" => message = args.next().map(|message| message.to_string()),
+                _ => positional.push(*arg),
+            }
+        }
+        if positional.is_empty() || positional.len() > 3 {
+            return self.help().to_string();
+        }
+        let address_index = match positional[0].parse::<usize>() {
+            Ok(index) => index,
+            Err(e) => return format!("Couldn't parse address index: {}", e),
+        };
+        let amount = match positional.get(1).map(|amount| amount.parse::<u64>()) {
+            Some(Ok(amount)) => Some(amount),
+            Some(Err(e)) => return format!("Couldn't parse amount: {}", e),
+            None => None,
+        };
+        let memo = match positional.get(2) {
+            Some(memo) => match utils::interpret_memo_string(memo.to_string()) {
+                Ok(memo) => Some(memo),
+                Err(e) => return format!("Couldn't interpret memo: {}", e),
+            },
+            None => None,
+        };
+        match lightclient.do_make_payment_uri(address_index, amount, memo, label, message) {
+            Ok(uri) => object! { "uri" => uri },
+            Err(e) => object! { "error" => e.to_string() },
+        }
+        .pretty(2)
+    }
+}
+
+/// Inputs spent per consolidation transaction, when not given
+const DEFAULT_CONSOLIDATION_MAX_INPUTS: usize = 20;
+
+struct ConsolidateCommand {}
+impl Command for ConsolidateCommand {
+    fn help(&self) -> &'static str {
+        indoc! {r#"
+            Merge many small notes of a pool into a few larger ones, by sending them to the wallet's own address.
+            Each transaction spends at most max_inputs notes (default 20), which bounds its number of actions and its fee.
+            Sapling notes are merged into the orchard pool, unless the privacy policy keeps value from crossing pools.
+            Usage:
+            consolidate <'orchard' or 'sapling'> [max_inputs] [--dry-run]
+            With --dry-run, only reports how many notes would be consolidated.
+            Accepts a trailing '--privacy <policy>', as for 'send'.
+
+            Example:
+            consolidate orchard 10
+
+        "#}
+    }
+
+    fn short_help(&self) -> &'static str {
+        "Merge small notes into larger ones"
+    }
+
+    fn exec(&self, args: &[&str], lightclient: &LightClient) -> String {
+        let (args, privacy_policy) = match split_privacy_policy(args) {
+            Ok(split) => split,
+            Err(e) => return format!("{}\n{}", e, self.help()),
+        };
+        let (args, dry_run) = match args.as_slice() {
+            [rest @ .., "--dry-run"] => (rest, true),
+            args => (args, false),
+        };
+        if args.is_empty() || args.len() > 2 {
+            return self.help().to_string();
+        }
+        let pool = match args[0] {
+            "orchard" => Pool::Orchard,
+            "sapling" => Pool::Sapling,
+            other => return format!("Can't consolidate the {} pool\n{}", other, self.help()),
+        };
+        let max_inputs = match args.get(1) {
+            Some(max_inputs) => match max_inputs.parse::<usize>() {
+                Ok(max_inputs) => max_inputs,
+                Err(e) => return format!("Couldn't parse max_inputs: {}", e),
+            },
+            None => DEFAULT_CONSOLIDATION_MAX_INPUTS,
+        };
+        RT.block_on(async move {
+            if dry_run {
+                return object! {
+                    "spendable_notes" => lightclient.do_count_spendable_notes(pool).await,
+                    "max_inputs" => max_inputs,
+                }
+                .pretty(2);
+            }
+            match lightclient
+                .do_consolidate(max_inputs, pool, privacy_policy)
+                .await
+            {
+                Ok(result) => result.to_json(),
+                Err(e) => {
+                    object! { "error" => e.to_string() }
+                }
+            }
+            .pretty(2)
+        })
+    }
+}
+
+struct CreateUnsignedCommand {}
+impl Command for CreateUnsignedCommand {
+    fn help(&self) -> &'static str {
+        indoc! {r#"
+            Build a send without signing it, and save it to a file.
+            Works in a view-only wallet. Carry the file to the wallet holding the spend keys and sign it with 'signtx'.
+            Usage:
+            createunsigned <file> <address> <amount in zatoshis> "optional_memo"
+            OR
+            createunsigned <file> '[{'address': <address>, 'amount': <amount in zatoshis>, 'memo': <optional memo>}, ...]'
+            Accepts a trailing '--privacy <policy>', as for 'send'.
+
+            Example:
+            createunsigned unsigned.hex ztestsapling1x65nq4dgp0qfywgxcwk9n0fvm4fysmapgr2q00p85ju252h6l7mmxu2jg9cqqhtvzd69jwhgv8d 200000
+
+        "#}
+    }
+
+    fn short_help(&self) -> &'static str {
+        "Build an unsigned send for offline signing"
+    }
+
+    fn exec(&self, args: &[&str], lightclient: &LightClient) -> String {
+        let (args, privacy_policy) = match split_privacy_policy(args) {
+            Ok(split) => split,
+            Err(e) => return format!("{}\n{}", e, self.help()),
+        };
+        let (path, send_args) = match args.split_first() {
+            Some(split) => split,
+            None => return self.help().to_string(),
+        };
+        let send_args = match parse_send_args(send_args, self.help(), lightclient) {
+            Ok(send_args) => send_args,
+            Err(e) => return e,
+        };
+        RT.block_on(async move {
+            match lightclient
+                .do_create_unsigned(
+                    send_args
+                        .iter()
+                        .map(|(address, value, memo)| (address.as_str(), *value, memo.clone()))
+                        .collect(),
+                    privacy_policy,
+                )
+                .await
+                .and_then(|(unsigned, bytes)| {
+                    std::fs::write(path, hex::encode(bytes))
+                        .map_err(|e| format!("Could not write {}: {}", path, e))?;
+                    Ok(unsigned)
+                }) {
+                Ok(unsigned) => {
+                    let mut result = unsigned.to_json(&lightclient.config.chain);
+                    result["file"] = (*path).into();
+                    result
+                }
+                Err(e) => {
+                    object! { "error" => e }
+                }
+            }
+            .pretty(2)
+        })
+    }
+}
+
+struct SignTxCommand {}
+impl Command for SignTxCommand {
+    fn help(&self) -> &'static str {
+        indoc! {r#"
+            Sign and prove a transaction saved by 'createunsigned', and save the signed transaction to a file.
+            Must be run in the wallet holding the spend keys of the view-only wallet that created it. Needs no sync.
+            Nothing is broadcast. Review the outputs shown, then broadcast the signed file with 'broadcasttx'.
+            Usage:
+            signtx <unsigned file> <signed file>
+
+            Example:
+            signtx unsigned.hex signed.hex
+
+        "#}
+    }
+
+    fn short_help(&self) -> &'static str {
+        "Sign an unsigned transaction offline"
+    }
+
+    fn exec(&self, args: &[&str], lightclient: &LightClient) -> String {
+        if args.len() != 2 {
+            return self.help().to_string();
+        }
+        let (unsigned_path, signed_path) = (args[0], args[1]);
+        let unsigned_bytes = match read_hex_file(unsigned_path) {
+            Ok(bytes) => bytes,
+            Err(e) => return object! { "error" => e }.pretty(2),
+        };
+        RT.block_on(async move {
+            match lightclient
+                .do_sign_unsigned(&unsigned_bytes)
+                .await
+                .and_then(|(unsigned, transaction_id, raw_transaction)| {
+                    std::fs::write(signed_path, hex::encode(raw_transaction))
+                        .map_err(|e| format!("Could not write {}: {}", signed_path, e))?;
+                    Ok((unsigned, transaction_id))
+                }) {
+                Ok((unsigned, transaction_id)) => {
+                    let mut result = unsigned.to_json(&lightclient.config.chain);
+                    result["txid"] = transaction_id.into();
+                    result["file"] = signed_path.into();
+                    result
+                }
+                Err(e) => {
+                    object! { "error" => e }
+                }
+            }
+            .pretty(2)
+        })
+    }
+}
+
+struct BroadcastTxCommand {}
+impl Command for BroadcastTxCommand {
+    fn help(&self) -> &'static str {
+        indoc! {r#"
+            Broadcast a raw transaction, given as hex or as a file holding hex, and track it as pending in this wallet.
+            Use this for a transaction signed by 'signtx', or to re-submit one that fell out of the mempool.
+            Transactions this wallet holds as confirmed are refused.
+            Usage:
+            broadcasttx <raw transaction hex or file>
+
+            Example:
+            broadcasttx signed.hex
+
+        "#}
+    }
+
+    fn short_help(&self) -> &'static str {
+        "Broadcast a raw transaction, such as one signed offline"
+    }
+
+    fn exec(&self, args: &[&str], lightclient: &LightClient) -> String {
+        if args.len() != 1 {
+            return self.help().to_string();
+        }
+        let raw_transaction = if std::path::Path::new(args[0]).is_file() {
+            read_hex_file(args[0])
+        } else {
+            hex::decode(args[0])
+                .map_err(|e| format!("{} is neither a file nor transaction hex: {}", args[0], e))
+        };
+        let raw_transaction = match raw_transaction {
+            Ok(bytes) => bytes,
+            Err(e) => return object! { "error" => e }.pretty(2),
+        };
+        RT.block_on(async move {
+            match lightclient.do_broadcast_raw(&raw_transaction).await {
+                Ok(transaction_id) => {
+                    object! { "txid" => transaction_id }
+                }
+                Err(e) => {
+                    object! { "error" => e }
+                }
+            }
+            .pretty(2)
+        })
+    }
+}
+
+struct PendingCommand {}
+impl Command for PendingCommand {
+    fn help(&self) -> &'static str {
+        indoc! {r#"
+            List the wallet's sends that were broadcast but not yet seen mined.
+            Pending sends are rebroadcast after each sync, as set by the 'rebroadcast' option.
+            A send that reaches its expiry height unmined is listed as expired, and the funds it spent become spendable again.
+            Usage:
+            pending
+
+        "#}
+    }
+
+    fn short_help(&self) -> &'static str {
+        "List pending sends and their rebroadcasts"
+    }
+
+    fn exec(&self, args: &[&str], lightclient: &LightClient) -> String {
+        if !args.is_empty() {
+            return self.help().to_string();
+        }
+        RT.block_on(async move { lightclient.do_list_pending().await.pretty(2) })
+    }
+}
+
+/// Reads a file holding hex, as written by `createunsigned` and `signtx`.
+fn read_hex_file(path: &str) -> Result<Vec<u8>, String> {
+    let contents =
+        std::fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+    hex::decode(contents.trim()).map_err(|e| format!("{} does not hold hex: {}", path, e))
+}
+
+struct SendBatchCommand {}
+impl Command for SendBatchCommand {
     fn help(&self) -> &'static str {
         indoc! {r#"
-            Encrypt a memo to be sent to a z-address offline
+            Pay many recipients listed in a CSV file, in as many transactions as needed.
+            Each row is: address,amount in zatoshis[,memo]. Quote memos that hold commas.
+            A header row, blank lines and lines starting with '#' are skipped.
+            The rows are split into transactions whose outputs, with change, come to at most max_actions
+            ZIP-317 logical actions (default 50).
+            Without --confirm, checks every row and shows the total with fees, without sending.
+            With --confirm, checks every row, then sends, and shows each transaction with its txid and rows.
+            Transactions are sent one after the other, and each needs notes the previous ones left unspent.
             Usage:
-            encryptmessage <address> "memo"
-            OR
-            encryptmessage "{'address': <address>, 'memo': <memo>}"
+            sendbatch <file.csv> [max_actions] [--privacy <policy>] [--confirm]
+            The privacy policy is as for 'send'.
 
-            NOTE: This command only returns the encrypted payload. It does not broadcast it. You are expected to send the encrypted payload to the recipient offline
             Example:
-            encryptmessage ztestsapling1x65nq4dgp0qfywgxcwk9n0fvm4fysmapgr2q00p85ju252h6l7mmxu2jg9cqqhtvzd69jwhgv8d "Hello from the command line"
+            sendbatch payroll.csv --confirm
 
         "#}
     }
 
     fn short_help(&self) -> &'static str {
-        "Encrypt a memo to be sent to a z-address offline"
+        "Pay the recipients of a CSV file"
     }
 
     fn exec(&self, args: &[&str], lightclient: &LightClient) -> String {
-        if args.is_empty() || args.len() > 3 {
+        let (args, confirm) = match args {
+            [rest @ .., "--confirm"] => (rest, true),
+            _ => (args, false),
+        };
+        let (args, privacy_policy) = match split_privacy_policy(args) {
+            Ok(split) => split,
+            Err(e) => return format!("{}\n{}", e, self.help()),
+        };
+        if args.is_empty() || args.len() > 2 {
             return self.help().to_string();
         }
-
-        // Check for a single argument that can be parsed as JSON
-        let (to, memo) = if args.len() == 1 {
-            let arg_list = args[0];
-            let j = match json::parse(arg_list) {
-                Ok(j) => j,
-                Err(e) => {
-                    let es = format!("Couldn't understand JSON: {}", e);
-                    return format!("{}\n{}", es, self.help());
+        let max_actions = match args.get(1) {
+            Some(max_actions) => match max_actions.parse::<usize>() {
+                Ok(max_actions) if max_actions > 0 => max_actions,
+                _ => return format!("Couldn't parse max_actions: {}", max_actions),
+            },
+            None => DEFAULT_MAX_ACTIONS_PER_TRANSACTION,
+        };
+        let batch = match std::fs::read_to_string(args[0])
+            .map_err(|e| format!("Could not read {}: {}", args[0], e))
+            .and_then(|contents| PaymentBatch::parse_csv(&contents, &lightclient.config.chain))
+        {
+            Ok(batch) => batch,
+            Err(e) => return object! { "error" => e }.pretty(2),
+        };
+        RT.block_on(async move {
+            if confirm {
+                match lightclient
+                    .do_send_batch(&batch, max_actions, privacy_policy)
+                    .await
+                {
+                    Ok(results) => {
+                        let rows = |sent: bool| {
+                            results
+                                .iter()
+                                .filter(|result| result.txid.is_ok() == sent)
+                                .map(|result| result.payments.len())
+                                .sum::<usize>()
+                        };
+                        object! {
+                            "transactions" => results.iter().map(|result| result.to_json()).collect::<Vec<_>>(),
+                            "paid" => rows(true),
+                            "failed" => rows(false),
+                            "total_fee" => results.iter().map(|result| result.fee).sum::<u64>(),
+                        }
+                    }
+                    Err(e) => {
+                        object! { "error" => e.to_string() }
+                    }
+                }
+            } else {
+                match lightclient
+                    .do_propose_batch(&batch, max_actions, privacy_policy)
+                    .await
+                {
+                    Ok(proposals) => {
+                        let fees = proposals.iter().map(|proposal| proposal.fee).sum::<u64>();
+                        object! {
+                            "payments" => batch.payments.len(),
+                            "transactions" => proposals.len(),
+                            "total_amount" => batch.total(),
+                            "estimated_fees" => fees,
+                            "total" => batch.total() + fees,
+                        }
+                    }
+                    Err(e) => {
+                        object! { "error" => e.to_string() }
+                    }
                 }
+            }
+            .pretty(2)
+        })
+    }
+}
+
+/// Removes a trailing `--privacy <policy>` from `args`, defaulting to
+/// [`PrivacyPolicy::AllowRevealedRecipients`].
+fn split_privacy_policy<'a>(args: &[&'a str]) -> Result<(Vec<&'a str>, PrivacyPolicy), String> {
+    match args {
+        [rest @ .., "--privacy", policy] => Ok((rest.to_vec(), policy.parse()?)),
+        [.., "--privacy"] => Err("Missing privacy policy after --privacy".to_string()),
+        _ => Ok((args.to_vec(), PrivacyPolicy::AllowRevealedRecipients)),
+    }
+}
+
+/// Takes `flag` and the account index following it out of `args`, wherever they are.
+fn split_account<'a>(args: &[&'a str], flag: &str) -> Result<(Vec<&'a str>, Option<u32>), String> {
+    match args.iter().position(|arg| *arg == flag) {
+        None => Ok((args.to_vec(), None)),
+        Some(position) => {
+            let account = match args.get(position + 1) {
+                Some(account) => account
+                    .parse()
+                    .map_err(|_| format!("Couldn't parse {} as an account index", account))?,
+                None => return Err(format!("Missing account index after {}", flag)),
             };
+            let mut rest = args.to_vec();
+            rest.drain(position..position + 2);
+            Ok((rest, Some(account)))
+        }
+    }
+}
 
-            if !j.has_key("address") || !j.has_key("memo") {
-                let es = "Need 'address' and 'memo'\n".to_string();
-                return format!("{}\n{}", es, self.help());
+/// Parses the arguments shared by `send` and `propose`. There are two argument types.
+/// 1 - A set of 2(+1 optional) arguments for a single address send representing address, value, memo?
+/// 2 - A single argument in the form of a JSON string that is "[{address: address, value: value, memo: memo},...]"
+/// The error is the message to show the user.
+fn parse_send_args(
+    args: &[&str],
+    help: &str,
+    lightclient: &LightClient,
+) -> Result<Vec<(String, u64, Option<MemoBytes>)>, String> {
+    if args.is_empty() || args.len() > 3 {
+        return Err(help.to_string());
+    }
+
+    // Check for a single argument that can be parsed as JSON
+    let send_args = if args.len() == 1 {
+        let arg_list = args[0];
+
+        let json_args = match json::parse(arg_list) {
+            Ok(j) => j,
+            Err(e) => {
+                let es = format!("Couldn't understand JSON: {}", e);
+                return Err(format!("{}\n{}", es, help));
             }
+        };
 
-            let memo = utils::interpret_memo_string(j["memo"].as_str().unwrap().to_string());
-            if memo.is_err() {
-                return format!("{}\n{}", memo.err().unwrap(), self.help());
-            }
-            let to = j["address"].as_str().unwrap().to_string();
+        if !json_args.is_array() {
+            return Err(format!("Couldn't parse argument as array\n{}", help));
+        }
 
-            (to, memo.unwrap())
-        } else if args.len() == 2 {
-            let to = args[0].to_string();
+        let maybe_send_args = json_args
+            .members()
+            .map(|j| {
+                if !j.has_key("address") || !j.has_key("amount") {
+                    Err("Need 'address' and 'amount'\n".to_string())
+                } else {
+                    match j["amount"].as_u64() {
+                        Some(amt) => Ok((
+                            j["address"].as_str().unwrap().to_string(),
+                            amt,
+                            j["memo"].as_str().map(|s| s.to_string()),
+                        )),
+                        None => Err(format!("Couldn't parse amount {}", j["amount"])),
+                    }
+                }
+            })
+            .collect::<Result<Vec<(String, u64, Option<String>)>, String>>();
 
-            let memo = utils::interpret_memo_string(args[1].to_string());
-            if memo.is_err() {
-                return format!("{}\n{}", memo.err().unwrap(), self.help());
+        match maybe_send_args {
+            Ok(a) => a,
+            Err(s) => {
+                return Err(format!("Error: {}\n{}", s, help));
             }
+        }
+    } else {
+        let address = args[0].to_string();
 
-            (to, memo.unwrap())
-        } else {
-            return format!(
-                "Wrong number of arguments. Was expecting 1 or 2\n{}",
-                self.help()
-            );
+        // Make sure we can parse the amount
+        let value = match args[1].parse::<u64>() {
+            Ok(amt) => amt,
+            Err(e) => return Err(format!("Couldn't parse amount: {}", e)),
         };
 
-        if let Ok(m) = memo.try_into() {
-            lightclient.do_encrypt_message(to, m).pretty(2)
+        let memo = if args.len() == 3 {
+            Some(args[2].to_string())
         } else {
-            "Couldn't encode memo".to_string()
+            None
+        };
+
+        // Memo has to be None if not sending to a shielded address
+        if memo.is_some() && !is_shielded_address(&address, &lightclient.config) {
+            return Err(format!(
+                "Can't send a memo to the non-shielded address {}",
+                address
+            ));
         }
-    }
+
+        vec![(address, value, memo)]
+    };
+
+    // Convert to the right format.
+    send_args
+        .into_iter()
+        .map(|(address, value, memo)| {
+            // If the string starts with an "0x", and contains only hex chars ([a-f0-9]+) then
+            // interpret it as a hex
+            let memo = match memo {
+                Some(s) => Some(
+                    utils::interpret_memo_string(s)
+                        .map_err(|e| format!("Couldn't interpret memo: {}", e))?,
+                ),
+                None => None,
+            };
+            Ok((address, value, memo))
+        })
+        .collect()
 }
 
-struct DecryptMessageCommand {}
-impl Command for DecryptMessageCommand {
+struct ProposeCommand {}
+impl Command for ProposeCommand {
     fn help(&self) -> &'static str {
         indoc! {r#"
-            Attempt to decrypt a message with all the view keys in the wallet.
+            Propose a send to the given address(es) without proving or broadcasting it.
+            Shows the notes that would be spent, the outputs, the change, the exact fee
+            and the pools that would be revealed. Run 'confirm' to send it.
             Usage:
-            decryptmessage "encrypted_message_base64"
+            propose <address> <amount in zatoshis> "optional_memo"
+            OR
+            propose '[{'address': <address>, 'amount': <amount in zatoshis>, 'memo': <optional memo>}, ...]'
+            Either form accepts a trailing '--privacy <policy>' and '--from-account <account>', as for 'send'.
 
             Example:
-            decryptmessage RW5jb2RlIGFyYml0cmFyeSBvY3RldHMgYXMgYmFzZTY0LiBSZXR1cm5zIGEgU3RyaW5nLg==
+            propose ztestsapling1x65nq4dgp0qfywgxcwk9n0fvm4fysmapgr2q00p85ju252h6l7mmxu2jg9cqqhtvzd69jwhgv8d 200000 "Hello from the command line"
+            confirm
 
         "#}
     }
 
     fn short_help(&self) -> &'static str {
-        "Attempt to decrypt a message with all the view keys in the wallet."
+        "Propose a send and show its fee and inputs before confirming"
     }
 
     fn exec(&self, args: &[&str], lightclient: &LightClient) -> String {
-        if args.len() != 1 {
-            return self.help().to_string();
-        }
-
+        let (args, account) = match split_account(args, "--from-account") {
+            Ok(split) => split,
+            Err(e) => return format!("{}\n{}", e, self.help()),
+        };
+        let (args, privacy_policy) = match split_privacy_policy(&args) {
+            Ok(split) => split,
+            Err(e) => return format!("{}\n{}", e, self.help()),
+        };
+        let send_args = match parse_send_args(&args, self.help(), lightclient) {
+            Ok(send_args) => send_args,
+            Err(e) => return e,
+        };
         RT.block_on(async move {
-            lightclient
-                .do_decrypt_message(args[0].to_string())
-                .await
-                .pretty(2)
+            let address_amount_memo_tuples = send_args
+                .iter()
+                .map(|(address, value, memo)| (address.as_str(), *value, memo.clone()))
+                .collect();
+            let result = match account {
+                None => {
+                    lightclient
+                        .propose_send(address_amount_memo_tuples, privacy_policy)
+                        .await
+                }
+                Some(account) => {
+                    lightclient
+                        .propose_send_from_account(
+                            account,
+                            address_amount_memo_tuples,
+                            privacy_policy,
+                        )
+                        .await
+                }
+            };
+            match result {
+                Ok(proposal) => proposal.to_json(&lightclient.config.chain),
+                Err(e) => {
+                    object! { "error" => e.to_string() }
+                }
+            }
+            .pretty(2)
         })
     }
 }
 
-struct SendCommand {}
-impl Command for SendCommand {
+struct ConfirmCommand {}
+impl Command for ConfirmCommand {
     fn help(&self) -> &'static str {
         indoc! {r#"
-            Send ZEC to a given address(es)
+            Prove and broadcast the latest proposal made with 'propose'.
             Usage:
-            send <address> <amount in zatoshis> "optional_memo"
-            OR
-            send '[{'address': <address>, 'amount': <amount in zatoshis>, 'memo': <optional memo>}, ...]'
-
-            NOTE: The fee required to send this transaction (currently ZEC 0.0001) is additionally deducted from your balance.
-            Example:
-            send ztestsapling1x65nq4dgp0qfywgxcwk9n0fvm4fysmapgr2q00p85ju252h6l7mmxu2jg9cqqhtvzd69jwhgv8d 200000 "Hello from the command line"
+            confirm
 
         "#}
     }
 
     fn short_help(&self) -> &'static str {
-        "Send ZEC to the given address"
+        "Send the latest proposal"
     }
 
     fn exec(&self, args: &[&str], lightclient: &LightClient) -> String {
-        // Parse the args. There are two argument types.
-        // 1 - A set of 2(+1 optional) arguments for a single address send representing address, value, memo?
-        // 2 - A single argument in the form of a JSON string that is "[{address: address, value: value, memo: memo},...]"
-        if args.is_empty() || args.len() > 3 {
+        if !args.is_empty() {
             return self.help().to_string();
         }
-
         RT.block_on(async move {
-            // Check for a single argument that can be parsed as JSON
-            let send_args = if args.len() == 1 {
-                let arg_list = args[0];
-
-                let json_args = match json::parse(arg_list) {
-                    Ok(j) => j,
-                    Err(e) => {
-                        let es = format!("Couldn't understand JSON: {}", e);
-                        return format!("{}\n{}", es, self.help());
-                    }
-                };
-
-                if !json_args.is_array() {
-                    return format!("Couldn't parse argument as array\n{}", self.help());
-                }
-
-                let fee = u64::from(MINIMUM_FEE);
-                let maybe_send_args = json_args
-                    .members()
-                    .map(|j| {
-                        if !j.has_key("address") || !j.has_key("amount") {
-                            Err("Need 'address' and 'amount'\n".to_string())
-                        } else {
-                            let amount = Some(j["amount"].as_u64().unwrap());
-
-                            match amount {
-                                Some(amt) => Ok((
-                                    j["address"].as_str().unwrap().to_string(),
-                                    amt,
-                                    j["memo"].as_str().map(|s| s.to_string()),
-                                )),
-                                None => Err(format!(
-                                    "Not enough in wallet to pay transaction fee of {}",
-                                    fee
-                                )),
-                            }
-                        }
-                    })
-                    .collect::<Result<Vec<(String, u64, Option<String>)>, String>>();
-
-                match maybe_send_args {
-                    Ok(a) => a.clone(),
-                    Err(s) => {
-                        return format!("Error: {}\n{}", s, self.help());
-                    }
-                }
-            } else if args.len() == 2 || args.len() == 3 {
-                let address = args[0].to_string();
-
-                // Make sure we can parse the amount
-                let value = match args[1].parse::<u64>() {
-                    Ok(amt) => amt,
-                    Err(e) => return format!("Couldn't parse amount: {}", e),
-                };
-
-                let memo = if args.len() == 3 {
-                    Some(args[2].to_string())
-                } else {
-                    None
-                };
-
-                // Memo has to be None if not sending to a shielded address
-                if memo.is_some() && !is_shielded_address(&address, &lightclient.config) {
-                    return format!("Can't send a memo to the non-shielded address {}", address);
-                }
-
-                vec![(args[0].to_string(), value, memo)]
-            } else {
-                return self.help().to_string();
-            };
-
-            // Convert to the right format.
-            let mut error = None;
-            let tos = send_args
-                .iter()
-                .map(|(a, v, m)| {
-                    (
-                        a.as_str(),
-                        *v,
-                        match m {
-                            // If the string starts with an "0x", and contains only hex chars ([a-f0-9]+) then
-                            // interpret it as a hex
-                            Some(s) => match utils::interpret_memo_string(s.clone()) {
-                                Ok(m) => Some(m),
-                                Err(e) => {
-                                    error = Some(format!("Couldn't interpret memo: {}", e));
-                                    None
-                                }
-                            },
-                            None => None,
-                        },
-                    )
-                })
-                .collect::<Vec<_>>();
-            if let Some(e) = error {
-                return e;
-            }
-
-            match lightclient.do_send(tos).await {
+            match lightclient.confirm_proposal().await {
                 Ok(transaction_id) => {
                     object! { "txid" => transaction_id }
                 }
@@ -1025,7 +1904,9 @@ impl Command for ValueTxSummariesCommand {
         indoc! {r#"
             List summaries of value transfers for this seed.
             Usage:
-            summaries
+            summaries [imported]
+
+            With 'imported', list those of the imported viewing keys instead, tagged by key.
         "#}
     }
 
@@ -1038,9 +1919,17 @@ impl Command for ValueTxSummariesCommand {
             return format!("Didn't understand arguments\n{}", self.help());
         }
 
-        RT.block_on(async move {
-            json::JsonValue::from(lightclient.do_list_txsummaries().await).pretty(2)
-        })
+        match args.first() {
+            None => RT.block_on(async move {
+                json::JsonValue::from(lightclient.do_list_txsummaries().await).pretty(2)
+            }),
+            Some(&"imported") => {
+                RT.block_on(
+                    async move { lightclient.do_list_imported_txsummaries().await.pretty(2) },
+                )
+            }
+            Some(_) => format!("Didn't understand arguments\n{}", self.help()),
+        }
     }
 }
 struct MemoBytesToAddressCommand {}
@@ -1124,6 +2013,14 @@ impl Command for SetOptionCommand {
             setoption <optionname>=<optionvalue>
             List of available options:
             download_memos : none | wallet | all
+            change_target : default | sapling | <address index>
+            note_selection : largest_first | smallest_first | exact_match | fee_minimizing
+            rebroadcast : never | <blocks between broadcasts of a pending send>
+            receiver_preference : orchard_first | sapling_first | match_funding
+            ephemeral_shield_depth : off | <confirmations before an ephemeral address is shielded>
+            auto_shield : off | <min balance>:<orchard|sapling>:<min blocks between>
+                Shields the verified transparent balance after each sync once it exceeds the
+                minimum balance in zatoshis, at most once in the given number of blocks
 
         "#}
     }
@@ -1186,6 +2083,72 @@ impl Command for SetOptionCommand {
                     }
                     Err(e) => return format!("Error {e}, couldn't parse {option_value} as number"),
                 },
+                "change_target" => match option_value.parse() {
+                    Ok(change_target) => {
+                        lightclient
+                            .wallet
+                            .wallet_options
+                            .write()
+                            .await
+                            .change_target = change_target
+                    }
+                    Err(e) => return format!("Error: {e}"),
+                },
+                "note_selection" => match option_value.parse() {
+                    Ok(note_selection) => {
+                        lightclient
+                            .wallet
+                            .wallet_options
+                            .write()
+                            .await
+                            .note_selection = note_selection
+                    }
+                    Err(e) => return format!("Error: {e}"),
+                },
+                "rebroadcast" => match option_value.parse() {
+                    Ok(rebroadcast) => {
+                        lightclient.wallet.wallet_options.write().await.rebroadcast = rebroadcast
+                    }
+                    Err(e) => return format!("Error: {e}"),
+                },
+                "receiver_preference" => match option_value.parse() {
+                    Ok(receiver_preference) => {
+                        lightclient
+                            .wallet
+                            .wallet_options
+                            .write()
+                            .await
+                            .receiver_preference = receiver_preference
+                    }
+                    Err(e) => return format!("Error: {e}"),
+                },
+                "ephemeral_shield_depth" => {
+                    let depth = match option_value {
+                        "off" => None,
+                        depth => match depth.parse() {
+                            Ok(depth) => Some(depth),
+                            Err(e) => {
+                                return format!("Error {e}, couldn't parse {depth} as number")
+                            }
+                        },
+                    };
+                    lightclient
+                        .wallet
+                        .wallet_options
+                        .write()
+                        .await
+                        .ephemeral_shield_depth = depth
+                }
+                "auto_shield" => {
+                    let policy = match option_value {
+                        "off" => None,
+                        policy => match policy.parse() {
+                            Ok(policy) => Some(policy),
+                            Err(e) => return format!("Error: {e}"),
+                        },
+                    };
+                    lightclient.wallet.wallet_options.write().await.auto_shield = policy
+                }
                 _ => return format!("Error: Couldn't understand {}", option_name),
             }
 
@@ -1203,7 +2166,9 @@ impl Command for GetOptionCommand {
     fn help(&self) -> &'static str {
         indoc! {r#"
             Get a wallet option
-            Argument is either "download_memos" and "transaction_filter_threshold"
+            Argument is one of "download_memos", "transaction_filter_threshold", "change_target",
+            "note_selection", "rebroadcast", "receiver_preference", "ephemeral_shield_depth"
+            and "auto_shield"
 
             Usage:
             getoption <optionname>
@@ -1243,6 +2208,50 @@ impl Command for GetOptionCommand {
                     .transaction_size_filter
                     .map(|filter| filter.to_string())
                     .unwrap_or("No filter".to_string()),
+                "change_target" => lightclient
+                    .wallet
+                    .wallet_options
+                    .read()
+                    .await
+                    .change_target
+                    .to_string(),
+                "note_selection" => lightclient
+                    .wallet
+                    .wallet_options
+                    .read()
+                    .await
+                    .note_selection
+                    .to_string(),
+                "rebroadcast" => lightclient
+                    .wallet
+                    .wallet_options
+                    .read()
+                    .await
+                    .rebroadcast
+                    .to_string(),
+                "receiver_preference" => lightclient
+                    .wallet
+                    .wallet_options
+                    .read()
+                    .await
+                    .receiver_preference
+                    .to_string(),
+                "ephemeral_shield_depth" => lightclient
+                    .wallet
+                    .wallet_options
+                    .read()
+                    .await
+                    .ephemeral_shield_depth
+                    .map(|depth| depth.to_string())
+                    .unwrap_or("off".to_string()),
+                "auto_shield" => lightclient
+                    .wallet
+                    .wallet_options
+                    .read()
+                    .await
+                    .auto_shield
+                    .map(|policy| policy.to_string())
+                    .unwrap_or("off".to_string()),
                 _ => return format!("Error: Couldn't understand {}", option_name),
             };
 
@@ -1341,6 +2350,61 @@ impl Command for NewAddressCommand {
     }
 }
 
+struct NewTaddrCommand {}
+impl Command for NewTaddrCommand {
+    fn help(&self) -> &'static str {
+        indoc! {r#"
+            Create a new transparent address, with a transparent key of its own and no unified
+            address around it
+            Usage:
+            newtaddr [--ephemeral]
+
+            An ephemeral address is for a single payment, such as an exchange withdrawal. A second
+            payment to it is listed under "reused_ephemeral_addresses" in the sync result. With the ephemeral_shield_depth option set,
+            the funds of each ephemeral address are shielded on their own once they have that
+            many confirmations. Note that shield sweeps all transparent funds together.
+        "#}
+    }
+
+    fn short_help(&self) -> &'static str {
+        "Create a new transparent address, optionally for a single payment"
+    }
+
+    fn exec(&self, args: &[&str], lightclient: &LightClient) -> String {
+        let ephemeral = match args {
+            [] => false,
+            ["--ephemeral"] => true,
+            _ => return self.help().to_string(),
+        };
+        RT.block_on(async move {
+            match lightclient.do_new_taddr(ephemeral).await {
+                Ok(j) => j,
+                Err(e) => object! { "error" => e },
+            }
+            .pretty(2)
+        })
+    }
+}
+
+struct EphemeralTaddrsCommand {}
+impl Command for EphemeralTaddrsCommand {
+    fn help(&self) -> &'static str {
+        indoc! {r#"
+            List the ephemeral transparent addresses, with how many transactions paid each
+            Usage:
+            ephemeraltaddrs
+        "#}
+    }
+
+    fn short_help(&self) -> &'static str {
+        "List the ephemeral transparent addresses"
+    }
+
+    fn exec(&self, _args: &[&str], lightclient: &LightClient) -> String {
+        RT.block_on(async move { lightclient.do_list_ephemeral_taddrs().await.pretty(2) })
+    }
+}
+
 struct NotesCommand {}
 impl Command for NotesCommand {
     fn help(&self) -> &'static str {
@@ -1476,6 +2540,10 @@ pub fn get_commands() -> HashMap<&'static str, Box<dyn Command>> {
         ("help", Box::new(HelpCommand {})),
         ("balance", Box::new(BalanceCommand {})),
         ("addresses", Box::new(AddressCommand {})),
+        ("accounts", Box::new(AccountsCommand {})),
+        ("newaccount", Box::new(NewAccountCommand {})),
+        ("importufvk", Box::new(ImportUfvkCommand {})),
+        ("importedkeys", Box::new(ImportedKeysCommand {})),
         ("height", Box::new(HeightCommand {})),
         ("sendprogress", Box::new(SendProgressCommand {})),
         ("setoption", Box::new(SetOptionCommand {})),
@@ -1491,11 +2559,25 @@ pub fn get_commands() -> HashMap<&'static str, Box<dyn Command>> {
         ("info", Box::new(InfoCommand {})),
         ("updatecurrentprice", Box::new(UpdateCurrentPriceCommand {})),
         ("send", Box::new(SendCommand {})),
+        ("sendall", Box::new(SendAllCommand {})),
+        ("consolidate", Box::new(ConsolidateCommand {})),
+        ("createunsigned", Box::new(CreateUnsignedCommand {})),
+        ("signtx", Box::new(SignTxCommand {})),
+        ("broadcasttx", Box::new(BroadcastTxCommand {})),
+        ("pending", Box::new(PendingCommand {})),
+        ("sendbatch", Box::new(SendBatchCommand {})),
+        ("parsepaymenturi", Box::new(ParsePaymentUriCommand {})),
+        ("sendpaymenturi", Box::new(SendPaymentUriCommand {})),
+        ("makepaymenturi", Box::new(MakePaymentUriCommand {})),
+        ("propose", Box::new(ProposeCommand {})),
+        ("confirm", Box::new(ConfirmCommand {})),
         ("shield", Box::new(ShieldCommand {})),
         ("save", Box::new(DeprecatedNoCommand {})),
         ("quit", Box::new(QuitCommand {})),
         ("notes", Box::new(NotesCommand {})),
         ("new", Box::new(NewAddressCommand {})),
+        ("newtaddr", Box::new(NewTaddrCommand {})),
+        ("ephemeraltaddrs", Box::new(EphemeralTaddrsCommand {})),
         ("defaultfee", Box::new(DefaultFeeCommand {})),
         ("seed", Box::new(SeedCommand {})),
         ("get_birthday", Box::new(GetBirthdayCommand {})),
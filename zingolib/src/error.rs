@@ -3,6 +3,8 @@ use std::fmt;
 
 use zcash_primitives::transaction::TxId;
 
+use crate::wallet::proposal::{PrivacyBoundary, PrivacyPolicy};
+
 #[derive(Debug)]
 pub enum ZingoLibError {
     UnknownError,
@@ -19,6 +21,15 @@ pub enum ZingoLibError {
     NoSuchNullifierInTx(TxId),
     MissingOutputIndex(TxId),
     CouldNotDecodeMemo(std::io::Error),
+    InsufficientPrivacy(PrivacyPolicy, PrivacyBoundary),
+    InvalidPaymentUri(String),
+    PaymentUriAddress(usize, String),
+    PaymentUriAmount(usize, String),
+    PaymentUriMemo(usize, String),
+    PaymentUriMissingAddress(usize),
+    PaymentUriMissingAmount(usize),
+    PaymentUriDuplicateParameter(String),
+    PaymentUriUnsupportedRequirement(String),
 }
 
 pub type ZingoLibResult<T> = Result<T, ZingoLibError>;
@@ -102,13 +113,73 @@ impl std::fmt::Display for ZingoLibError {
                 f,
                 "{txid} is missing output_index for note, cannot mark change"
             ),
+            InsufficientPrivacy(policy, boundary) => write!(
+                f,
+                "Privacy policy {} forbids this transaction: {}. Use {} or a more permissive policy to allow it.",
+                policy,
+                boundary,
+                boundary.required_policy(),
+            ),
+            InvalidPaymentUri(reason) => write!(
+                f,
+                "Invalid payment URI: {}",
+                reason,
+            ),
+            PaymentUriAddress(index, address) => write!(
+                f,
+                "Payment {} of the URI is to '{}', which is not a valid address for this chain",
+                index,
+                address,
+            ),
+            PaymentUriAmount(index, amount) => write!(
+                f,
+                "Payment {} of the URI has an invalid amount '{}'. Amounts are in ZEC, with at most 8 decimal places",
+                index,
+                amount,
+            ),
+            PaymentUriMemo(index, reason) => write!(
+                f,
+                "Payment {} of the URI has an invalid memo: {}",
+                index,
+                reason,
+            ),
+            PaymentUriMissingAddress(index) => write!(
+                f,
+                "Payment {} of the URI has no address",
+                index,
+            ),
+            PaymentUriMissingAmount(index) => write!(
+                f,
+                "Payment {} of the URI has no amount, so it can't be paid directly",
+                index,
+            ),
+            PaymentUriDuplicateParameter(param) => write!(
+                f,
+                "The URI repeats the parameter {}",
+                param,
+            ),
+            PaymentUriUnsupportedRequirement(param) => write!(
+                f,
+                "The URI requires {}, which this wallet does not support",
+                param,
+            ),
         }
     }
 }
 
 impl From<ZingoLibError> for String {
     fn from(value: ZingoLibError) -> Self {
-        format!("{value}")
+        match value {
+            // The message an untyped error was made from
+            ZingoLibError::Error(message) => message,
+            value => format!("{value}"),
+        }
+    }
+}
+
+impl From<String> for ZingoLibError {
+    fn from(value: String) -> Self {
+        ZingoLibError::Error(value)
     }
 }
 
@@ -17,8 +17,8 @@ use tower::{util::BoxCloneService, ServiceExt};
 use zcash_client_backend::proto::compact_formats::CompactBlock;
 use zcash_client_backend::proto::service::compact_tx_streamer_client::CompactTxStreamerClient;
 use zcash_client_backend::proto::service::{
-    BlockId, BlockRange, ChainSpec, Empty, LightdInfo, RawTransaction,
-    TransparentAddressBlockFilter, TreeState, TxFilter,
+    BlockId, BlockRange, ChainSpec, Empty, GetSubtreeRootsArg, LightdInfo, RawTransaction,
+    ShieldedProtocol, SubtreeRoot, TransparentAddressBlockFilter, TreeState, TxFilter,
 };
 use zcash_primitives::consensus::{BlockHeight, BranchId, Parameters};
 use zcash_primitives::transaction::{Transaction, TxId};
@@ -231,7 +231,7 @@ impl GrpcConnector {
         &self,
         start_height: u64,
         end_height: u64,
-        senders: &[UnboundedSender<CompactBlock>; 2],
+        senders: &[UnboundedSender<CompactBlock>],
     ) -> Result<(), String> {
         let mut client = self.get_client().await.map_err(|e| format!("{}", e))?;
 
@@ -256,16 +256,15 @@ impl GrpcConnector {
             .into_inner();
 
         while let Some(block) = response.message().await.map_err(|e| format!("{}", e))? {
-            senders[0]
-                .send(block.clone())
-                .map_err(|e| format!("{}", e))?;
-            senders[1].send(block).map_err(|e| format!("{}", e))?;
+            for sender in senders {
+                sender.send(block.clone()).map_err(|e| format!("{}", e))?;
+            }
         }
 
         Ok(())
     }
 
-    async fn get_full_transaction(
+    pub(crate) async fn get_full_transaction(
         uri: http::Uri,
         transaction_id: &TxId,
         network: impl Parameters,
@@ -358,6 +357,23 @@ impl GrpcConnector {
         Ok(())
     }
 
+    /// The transactions `taddr` appears in between the heights.
+    pub async fn get_taddr_transactions_between(
+        uri: http::Uri,
+        taddr: String,
+        start_height: u64,
+        end_height: u64,
+    ) -> Result<Vec<RawTransaction>, String> {
+        let (transactions_sender, mut transactions_receiver) = unbounded_channel();
+        Self::get_taddr_transactions(uri, taddr, start_height, end_height, transactions_sender)
+            .await?;
+        let mut transactions = vec![];
+        while let Ok(transaction) = transactions_receiver.try_recv() {
+            transactions.push(transaction?);
+        }
+        Ok(transactions)
+    }
+
     pub async fn get_info(uri: http::Uri) -> Result<LightdInfo, String> {
         let client = Arc::new(GrpcConnector::new(uri.clone()));
 
@@ -421,6 +437,37 @@ impl GrpcConnector {
         Ok(response.into_inner())
     }
 
+    /// The roots of the complete 2^16-leaf subtrees of a pool's note commitment tree, from
+    /// the `start_index`th on, with the heights of the blocks that completed them.
+    pub async fn get_subtree_roots(
+        uri: http::Uri,
+        protocol: ShieldedProtocol,
+        start_index: u32,
+    ) -> Result<Vec<SubtreeRoot>, String> {
+        let client = Arc::new(GrpcConnector::new(uri.clone()));
+        let mut client = client
+            .get_client()
+            .await
+            .map_err(|e| format!("Error getting client: {:?}", e))?;
+
+        let request = Request::new(GetSubtreeRootsArg {
+            start_index,
+            shielded_protocol: protocol as i32,
+            max_entries: 0,
+        });
+        let mut response = client
+            .get_subtree_roots(request)
+            .await
+            .map_err(|e| format!("Error with get_subtree_roots response at {uri}: {:?}", e))?
+            .into_inner();
+
+        let mut roots = vec![];
+        while let Some(root) = response.message().await.map_err(|e| format!("{}", e))? {
+            roots.push(root);
+        }
+        Ok(roots)
+    }
+
     // get_latest_block GRPC call
     pub async fn get_latest_block(uri: http::Uri) -> Result<BlockId, String> {
         let client = Arc::new(GrpcConnector::new(uri.clone()));
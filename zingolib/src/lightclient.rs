@@ -1,28 +1,44 @@
 use crate::{
     blaze::{
+        batch_sizing::BatchSizer,
         block_management_reorg_detection::BlockManagementData,
-        fetch_compact_blocks::FetchCompactBlocks, fetch_full_transaction::TransactionContext,
-        fetch_taddr_transactions::FetchTaddrTransactions, sync_status::BatchSyncStatus,
-        syncdata::BlazeSyncData, trial_decryptions::TrialDecryptions, update_notes::UpdateNotes,
+        fetch_compact_blocks::FetchCompactBlocks,
+        fetch_full_transaction::TransactionContext,
+        fetch_taddr_transactions::FetchTaddrTransactions,
+        scan_priority,
+        sync_events::{SyncEvent, SyncEventSender},
+        sync_status::BatchSyncStatus,
+        syncdata::BlazeSyncData,
+        trial_decryptions::TrialDecryptions,
+        update_notes::UpdateNotes,
     },
     error::{ZingoLibError, ZingoLibResult},
     grpc_connector::GrpcConnector,
     wallet::{
+        auto_shield::{AutoShieldResult, ShieldedPool},
+        batch::{BatchPayment, BatchTransactionResult, PaymentBatch},
         data::{
-            finsight, summaries::ValueTransfer, summaries::ValueTransferKind, OutgoingTxData,
-            TransactionRecord,
+            finsight, summaries::ValueTransfer, summaries::ValueTransferKind, BlockData,
+            OutgoingTxData, PoolNullifier, TransactionRecord,
         },
         keys::{address_from_pubkeyhash, unified::ReceiverSelection},
         message::Message,
         notes::ShieldedNoteInterface,
         now,
+        payment_uri::{Payment, PaymentRequest},
+        proposal::{Dust, PrivacyPolicy, Proposal},
+        prover::SaplingProver,
+        receivers::decode_tex_address,
+        unsigned::UnsignedTransaction,
         utils::get_price,
         LightWallet, Pool, SendProgress, WalletBase,
     },
 };
 use futures::future::join_all;
 use json::{array, object, JsonValue};
-use log::{debug, error, warn};
+use log::{debug, error, info, warn};
+use orchard::note_encryption::OrchardDomain;
+use sapling_crypto::note_encryption::SaplingDomain;
 use serde::Serialize;
 use std::{
     cmp::{self},
@@ -31,12 +47,12 @@ use std::{
     io::{self, BufReader, Error, ErrorKind, Read, Write},
     path::{Path, PathBuf},
     sync::Arc,
-    time::Duration,
+    time::{Duration, Instant},
 };
 use tokio::{
     join,
     runtime::Runtime,
-    sync::{mpsc::unbounded_channel, oneshot, Mutex, RwLock},
+    sync::{broadcast, mpsc::unbounded_channel, oneshot, Mutex, RwLock},
     task::yield_now,
     time::sleep,
 };
@@ -45,17 +61,14 @@ use zingo_status::confirmation_status::ConfirmationStatus;
 
 use zcash_client_backend::{
     encoding::{decode_payment_address, encode_payment_address},
-    proto::service::RawTransaction,
+    proto::service::{RawTransaction, ShieldedProtocol},
 };
 use zcash_primitives::{
     consensus::{BlockHeight, BranchId, Parameters},
     memo::{Memo, MemoBytes},
-    transaction::{
-        components::amount::NonNegativeAmount, fees::zip317::MINIMUM_FEE, Transaction, TxId,
-    },
+    transaction::{components::amount::NonNegativeAmount, Transaction, TxId},
 };
-use zcash_proofs::prover::LocalTxProver;
-use zingoconfig::{ZingoConfig, MAX_REORG};
+use zingoconfig::{SyncMode, ZingoConfig, MAX_REORG};
 
 static LOG_INIT: std::sync::Once = std::sync::Once::new();
 
@@ -66,16 +79,28 @@ pub struct SyncResult {
     pub success: bool,
     pub latest_block: u64,
     pub total_blocks_synced: u64,
+    /// What the wallet's auto-shield policy did after the sync, if it has one
+    pub auto_shield: Option<AutoShieldResult>,
+    /// The single-use transparent addresses that were paid more than once, which links
+    /// those payments
+    pub reused_ephemeral_taddrs: Vec<String>,
 }
 
 impl SyncResult {
     /// Converts this object to a JSON object that meets the contract expected by Zingo Mobile.
     pub fn to_json(&self) -> JsonValue {
-        object! {
+        let mut json = object! {
             "result" => if self.success { "success" } else { "failure" },
             "latest_block" => self.latest_block,
             "total_blocks_synced" => self.total_blocks_synced,
+        };
+        if let Some(auto_shield) = &self.auto_shield {
+            json["auto_shield"] = auto_shield.into();
+        }
+        if !self.reused_ephemeral_taddrs.is_empty() {
+            json["reused_ephemeral_addresses"] = self.reused_ephemeral_taddrs.clone().into();
         }
+        json
     }
 }
 
@@ -91,6 +116,47 @@ impl std::fmt::Display for SyncResult {
     }
 }
 
+#[derive(Clone, Debug)]
+pub struct SendAllResult {
+    pub txid: String,
+    pub value_sent: u64,
+    pub fee: u64,
+    /// Value that was left behind because it is not worth the fee to spend
+    pub dust: Dust,
+}
+
+impl SendAllResult {
+    pub fn to_json(&self) -> JsonValue {
+        object! {
+            "txid" => self.txid.clone(),
+            "value_sent" => self.value_sent,
+            "fee" => self.fee,
+            "dust_notes" => self.dust.count,
+            "dust_value" => self.dust.value,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ConsolidateResult {
+    /// Spendable notes in the pool before consolidating
+    pub notes_before: usize,
+    pub notes_merged: usize,
+    pub txids: Vec<String>,
+    pub total_fee: u64,
+}
+
+impl ConsolidateResult {
+    pub fn to_json(&self) -> JsonValue {
+        object! {
+            "notes_before" => self.notes_before,
+            "notes_merged" => self.notes_merged,
+            "txids" => self.txids.clone(),
+            "total_fee" => self.total_fee,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct WalletStatus {
     pub is_syncing: bool,
@@ -246,6 +312,9 @@ pub struct LightClient {
     bsync_data: Arc<RwLock<BlazeSyncData>>,
     interrupt_sync: Arc<RwLock<bool>>,
 
+    /// The latest proposal, with the account it spends from, `None` for the primary one
+    latest_proposal: Arc<RwLock<Option<(Option<u32>, Proposal)>>>,
+
     save_buffer: ZingoSaveBuffer,
 }
 
@@ -267,6 +336,7 @@ impl LightClient {
             sync_lock: Mutex::new(()),
             bsync_data: Arc::new(RwLock::new(BlazeSyncData::new(&config))),
             interrupt_sync: Arc::new(RwLock::new(false)),
+            latest_proposal: Arc::new(RwLock::new(None)),
             save_buffer: ZingoSaveBuffer::new(buffer),
         })
     }
@@ -512,14 +582,30 @@ impl LightClient {
         let birthday = self.wallet.get_birthday().await;
         self.set_wallet_initial_state(birthday).await;
         debug!("Cleared wallet state, with birthday at {}", birthday);
+
+        // The others start no higher than the primary account, to sync along with it
+        for other in self.wallet.other_wallets().await.iter() {
+            other.clear_all().await;
+            let start = cmp::min(other.get_birthday().await, birthday);
+            self.set_initial_state_of(other, start).await;
+        }
     }
     pub fn config(&self) -> &ZingoConfig {
         &self.config
     }
 
     pub async fn do_addresses(&self) -> JsonValue {
+        self.addresses_of(&self.wallet)
+    }
+
+    pub async fn do_account_addresses(&self, account: u32) -> Result<JsonValue, String> {
+        let other = self.other_account(account).await?;
+        Ok(self.addresses_of(other.as_deref().unwrap_or(&self.wallet)))
+    }
+
+    fn addresses_of(&self, wallet: &LightWallet) -> JsonValue {
         let mut objectified_addresses = Vec::new();
-        for address in self.wallet.wallet_capability().addresses().iter() {
+        for address in wallet.wallet_capability().addresses().iter() {
             let encoded_ua = address.encode(&self.config.chain);
             let transparent = address
                 .transparent()
@@ -537,19 +623,139 @@ impl LightClient {
     }
 
     pub async fn do_balance(&self) -> PoolBalances {
+        Self::balance_of(&self.wallet).await
+    }
+
+    pub async fn do_account_balance(&self, account: u32) -> Result<PoolBalances, String> {
+        let other = self.other_account(account).await?;
+        Ok(Self::balance_of(other.as_deref().unwrap_or(&self.wallet)).await)
+    }
+
+    async fn balance_of(wallet: &LightWallet) -> PoolBalances {
         PoolBalances {
-            sapling_balance: self.wallet.maybe_verified_sapling_balance(None).await,
-            verified_sapling_balance: self.wallet.verified_sapling_balance(None).await,
-            spendable_sapling_balance: self.wallet.spendable_sapling_balance(None).await,
-            unverified_sapling_balance: self.wallet.unverified_sapling_balance(None).await,
-            orchard_balance: self.wallet.maybe_verified_orchard_balance(None).await,
-            verified_orchard_balance: self.wallet.verified_orchard_balance(None).await,
-            spendable_orchard_balance: self.wallet.spendable_orchard_balance(None).await,
-            unverified_orchard_balance: self.wallet.unverified_orchard_balance(None).await,
-            transparent_balance: self.wallet.tbalance(None).await,
+            sapling_balance: wallet.maybe_verified_sapling_balance(None).await,
+            verified_sapling_balance: wallet.verified_sapling_balance(None).await,
+            spendable_sapling_balance: wallet.spendable_sapling_balance(None).await,
+            unverified_sapling_balance: wallet.unverified_sapling_balance(None).await,
+            orchard_balance: wallet.maybe_verified_orchard_balance(None).await,
+            verified_orchard_balance: wallet.verified_orchard_balance(None).await,
+            spendable_orchard_balance: wallet.spendable_orchard_balance(None).await,
+            unverified_orchard_balance: wallet.unverified_orchard_balance(None).await,
+            transparent_balance: wallet.tbalance(None).await,
         }
     }
 
+    /// The wallet of `account`, unless it is the primary account, whose wallet is
+    /// [`Self::wallet`].
+    async fn other_account(&self, account: u32) -> Result<Option<Arc<LightWallet>>, String> {
+        if self.wallet.account_index() == Some(account) {
+            return Ok(None);
+        }
+        match self.wallet.account(account).await {
+            Some(wallet) => Ok(Some(wallet)),
+            None => Err(format!(
+                "This wallet has no account {}. List them with the accounts command",
+                account
+            )),
+        }
+    }
+
+    /// Lists the seed's accounts in this wallet, primary first.
+    pub async fn do_list_accounts(&self) -> JsonValue {
+        let mut accounts = vec![object! {
+            "account" => self.wallet.account_index(),
+            "primary" => true,
+            "address" => self.first_address_of(&self.wallet),
+        }];
+        for account in self.wallet.accounts.read().await.iter() {
+            accounts.push(object! {
+                "account" => account.account_index(),
+                "primary" => false,
+                "address" => self.first_address_of(account),
+            });
+        }
+        accounts.into()
+    }
+
+    fn first_address_of(&self, wallet: &LightWallet) -> Option<String> {
+        wallet
+            .wallet_capability()
+            .addresses()
+            .iter()
+            .next()
+            .map(|address| address.encode(&self.config.chain))
+    }
+
+    /// The key an imported wallet is tagged by: its UFVK, or its first address when the
+    /// key doesn't view every pool.
+    fn key_tag(&self, imported: &LightWallet) -> Option<String> {
+        imported
+            .encoded_ufvk()
+            .or_else(|| self.first_address_of(imported))
+    }
+
+    /// Adds a view-only wallet of a third party's UFVK, and scans the blocks the primary
+    /// account has synced from `birthday` for it. From then on it syncs along with the
+    /// primary account. When the primary account hasn't synced as far as `birthday`, the key
+    /// starts from where it has.
+    pub async fn do_import_ufvk(&self, ufvk: String, birthday: u64) -> Result<JsonValue, String> {
+        let _lock = self.sync_lock.lock().await;
+        let imported = self.wallet.import_ufvk(ufvk, birthday).await?;
+        let birthday = imported.get_birthday().await;
+        let start = cmp::min(birthday, self.wallet.last_synced_height().await);
+        self.set_initial_state_of(&imported, start).await;
+        self.sync_events().await.begin();
+        if let Err(e) = self.backfill(&imported).await {
+            warn!(
+                "Could not scan the imported key, it is backfilled at the next sync: {}",
+                e
+            );
+        }
+        self.save_internal_rust().await?;
+        Ok(object! {
+            "key" => self.key_tag(&imported),
+            "birthday" => birthday,
+        })
+    }
+
+    /// Lists the imported keys, with their birthdays and balances.
+    pub async fn do_list_imported_keys(&self) -> JsonValue {
+        let mut imported_keys = vec![];
+        for imported in self.wallet.imported_keys.read().await.iter() {
+            let balance = Self::balance_of(imported).await;
+            imported_keys.push(object! {
+                "key" => self.key_tag(imported),
+                "birthday" => imported.get_birthday().await,
+                "address" => self.first_address_of(imported),
+                "orchard_balance" => balance.orchard_balance,
+                "sapling_balance" => balance.sapling_balance,
+                "transparent_balance" => balance.transparent_balance,
+            });
+        }
+        imported_keys.into()
+    }
+
+    /// Derives the seed's next account and scans the blocks the primary account has synced
+    /// for it, from the wallet's birthday. From then on it syncs along with the primary
+    /// account. Returns the new account's index.
+    pub async fn do_new_account(&self) -> Result<u32, String> {
+        let _lock = self.sync_lock.lock().await;
+        let account = self.wallet.add_account().await?;
+        let birthday = account.get_birthday().await;
+        self.set_initial_state_of(&account, birthday).await;
+        self.sync_events().await.begin();
+        if let Err(e) = self.backfill(&account).await {
+            warn!(
+                "Could not scan the new account, it is backfilled at the next sync: {}",
+                e
+            );
+        }
+        self.save_internal_rust().await?;
+        Ok(account
+            .account_index()
+            .expect("an account derived from the seed"))
+    }
+
     /// Returns the wallet balance, broken out into several figures that are expected to be meaningful to the user.
     /// # Parameters
     /// * `auto_shielding` - if true, UTXOs will be considered immature rather than spendable.
@@ -750,16 +956,32 @@ impl LightClient {
     }
 
     pub async fn do_list_txsummaries(&self) -> Vec<ValueTransfer> {
+        Self::txsummaries_of(&self.wallet).await
+    }
+
+    /// The value transfers of each imported key, tagged by the key.
+    pub async fn do_list_imported_txsummaries(&self) -> JsonValue {
+        let mut imported_summaries = vec![];
+        for imported in self.wallet.imported_keys.read().await.iter() {
+            imported_summaries.push(object! {
+                "key" => self.key_tag(imported),
+                "summaries" => Self::txsummaries_of(imported).await,
+            });
+        }
+        imported_summaries.into()
+    }
+
+    async fn txsummaries_of(wallet: &LightWallet) -> Vec<ValueTransfer> {
         let mut summaries: Vec<ValueTransfer> = Vec::new();
 
-        for (txid, transaction_md) in self
-            .wallet
+        for (txid, transaction_md) in wallet
             .transaction_context
             .transaction_metadata_set
             .read()
             .await
             .current
             .iter()
+            .filter(|(_, transaction_md)| !transaction_md.status.is_expired())
         {
             LightClient::tx_summary_matcher(&mut summaries, *txid, transaction_md);
 
@@ -787,6 +1009,95 @@ impl LightClient {
         summaries
     }
 
+    /// Derives a fresh transparent address, and saves the wallet so that its child index
+    /// isn't handed out again. An ephemeral one is for a single payment: a second payment
+    /// to it is reported by sync, and with the `ephemeral_shield_depth` option its funds
+    /// are shielded on their own.
+    pub async fn do_new_taddr(&self, ephemeral: bool) -> Result<JsonValue, String> {
+        let taddr = self.wallet.new_taddr(ephemeral).await?;
+        self.save_internal_rust().await?;
+        Ok(object! {
+            "address" => taddr,
+            "ephemeral" => ephemeral,
+        })
+    }
+
+    pub async fn do_list_ephemeral_taddrs(&self) -> JsonValue {
+        self.wallet
+            .ephemeral_receipts()
+            .await
+            .into_iter()
+            .map(|(taddr, receipts)| {
+                object! {
+                    "address" => taddr,
+                    "receipts" => receipts,
+                    "reused" => receipts > 1,
+                }
+            })
+            .collect::<Vec<JsonValue>>()
+            .into()
+    }
+
+    /// The ephemeral addresses that were paid more than once, each warned about.
+    async fn reused_ephemeral_taddrs(&self) -> Vec<String> {
+        let mut reused = vec![];
+        for (taddr, receipts) in self.wallet.ephemeral_receipts().await {
+            if receipts > 1 {
+                warn!(
+                    "Single-use address {} was paid {} times, which links those payments",
+                    taddr, receipts
+                );
+                reused.push(taddr);
+            }
+        }
+        reused
+    }
+
+    /// Shields each ephemeral address's deep enough funds in a transaction of its own, so
+    /// that they aren't linked. Returns whether any were shielded.
+    async fn update_ephemeral_taddrs(&self) -> bool {
+        let Some(depth) = self
+            .wallet
+            .wallet_options
+            .read()
+            .await
+            .ephemeral_shield_depth
+        else {
+            return false;
+        };
+        let mut shielded = false;
+        let taddrs = self.wallet.ephemeral_taddrs.read().await.clone();
+        if taddrs.is_empty() {
+            return false;
+        }
+        let height = match self.get_submission_height().await {
+            Ok(height) => height,
+            Err(e) => {
+                warn!("Could not shield the ephemeral addresses: {}", e);
+                return false;
+            }
+        };
+        for taddr in taddrs {
+            let result = match self
+                .wallet
+                .propose_address_shielding(&taddr, depth, height)
+                .await
+            {
+                Ok(Some(proposal)) => self.send_proposal(&self.wallet, &proposal, height).await,
+                Ok(None) => continue,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(txid) => {
+                    info!("Shielded the funds of {} in {}", taddr, txid);
+                    shielded = true;
+                }
+                Err(e) => warn!("Could not shield the funds of {}: {}", taddr, e),
+            }
+        }
+        shielded
+    }
+
     /// Create a new address, deriving it from the seed.
     pub async fn do_new_address(&self, addr_type: &str) -> Result<JsonValue, String> {
         //TODO: Placeholder interface
@@ -810,7 +1121,8 @@ impl LightClient {
 
         self.clear_state().await;
 
-        // Then, do a sync, which will force a full rescan from the initial state
+        // Then, do a sync, which will force a full rescan from the initial state. The compact
+        // blocks come from the `ZingoConfig::compact_block_cache_limit` cache where they can.
         let response = self.do_sync(true).await;
 
         if response.is_ok() {
@@ -855,17 +1167,19 @@ impl LightClient {
         }
         tos.iter()
             .map(|to| {
-                let ra = match zcash_client_backend::address::Address::decode(
-                    &self.config.chain,
-                    to.0,
-                ) {
-                    Some(to) => to,
-                    None => {
-                        let e = format!("Invalid recipient address: '{}'", to.0);
-                        error!("{}", e);
-                        return Err(e);
-                    }
-                };
+                let ra =
+                    match zcash_client_backend::address::Address::decode(&self.config.chain, to.0)
+                        .or_else(|| {
+                            decode_tex_address(&self.config.chain, to.0)
+                                .map(zcash_client_backend::address::Address::Transparent)
+                        }) {
+                        Some(to) => to,
+                        None => {
+                            let e = format!("Invalid recipient address: '{}'", to.0);
+                            error!("{}", e);
+                            return Err(e);
+                        }
+                    };
 
                 let value = NonNegativeAmount::from_u64(to.1).unwrap();
 
@@ -874,137 +1188,863 @@ impl LightClient {
             .collect()
     }
 
+    /// Whether any of `tos` is a TEX address, which may only be paid from transparent funds.
+    fn pays_tex_address(&self, tos: &[(&str, u64, Option<MemoBytes>)]) -> bool {
+        tos.iter()
+            .any(|to| decode_tex_address(&self.config.chain, to.0).is_some())
+    }
+
     //TODO: Add migrate_sapling_to_orchard argument
+    /// Sends with [`PrivacyPolicy::AllowRevealedRecipients`]: shielded funds may cross pools
+    /// and pay transparent recipients, but transparent funds are never spent.
     pub async fn do_send(
         &self,
         address_amount_memo_tuples: Vec<(&str, u64, Option<MemoBytes>)>,
     ) -> Result<String, String> {
+        self.do_send_with_privacy_policy(
+            address_amount_memo_tuples,
+            PrivacyPolicy::AllowRevealedRecipients,
+        )
+        .await
+        .map_err(String::from)
+    }
+
+    /// Fails with [`ZingoLibError::InsufficientPrivacy`] when the send needs a more
+    /// permissive `privacy_policy`.
+    pub async fn do_send_with_privacy_policy(
+        &self,
+        address_amount_memo_tuples: Vec<(&str, u64, Option<MemoBytes>)>,
+        privacy_policy: PrivacyPolicy,
+    ) -> ZingoLibResult<String> {
+        self.send_from(&self.wallet, address_amount_memo_tuples, privacy_policy)
+            .await
+    }
+
+    /// Sends from another account of the seed, as [`Self::do_send_with_privacy_policy`]
+    /// sends from the primary account.
+    pub async fn do_send_from_account(
+        &self,
+        account: u32,
+        address_amount_memo_tuples: Vec<(&str, u64, Option<MemoBytes>)>,
+        privacy_policy: PrivacyPolicy,
+    ) -> ZingoLibResult<String> {
+        let other = self.other_account(account).await?;
+        self.send_from(
+            other.as_deref().unwrap_or(&self.wallet),
+            address_amount_memo_tuples,
+            privacy_policy,
+        )
+        .await
+    }
+
+    async fn send_from(
+        &self,
+        wallet: &LightWallet,
+        address_amount_memo_tuples: Vec<(&str, u64, Option<MemoBytes>)>,
+        privacy_policy: PrivacyPolicy,
+    ) -> ZingoLibResult<String> {
+        let transparent_source_only = self.pays_tex_address(&address_amount_memo_tuples);
         let receivers = self.map_tos_to_receivers(address_amount_memo_tuples)?;
         let transaction_submission_height = self.get_submission_height().await?;
         // First, get the consensus branch ID
         debug!("Creating transaction");
 
-        let result = {
-            let _lock = self.sync_lock.lock().await;
-            // I am not clear on how long this operation may take, but it's
-            // clearly unnecessary in a send that doesn't include sapling
-            // TODO: Remove from sends that don't include Sapling
-            let (sapling_output, sapling_spend) = self.read_sapling_params()?;
+        let _lock = self.sync_lock.lock().await;
+        let proposal = self
+            .create_proposal(
+                wallet,
+                receivers,
+                transparent_source_only,
+                privacy_policy,
+                transaction_submission_height,
+            )
+            .await?;
+        Ok(self
+            .send_proposal(wallet, &proposal, transaction_submission_height)
+            .await?)
+    }
+
+    /// Sends everything spendable to `address`, less the ZIP-317 fee.  Transparent funds are
+    /// only included under [`PrivacyPolicy::AllowFullyTransparent`].
+    pub async fn do_send_all(
+        &self,
+        address: &str,
+        memo: Option<MemoBytes>,
+        privacy_policy: PrivacyPolicy,
+    ) -> ZingoLibResult<SendAllResult> {
+        self.send_all_from(&self.wallet, address, memo, privacy_policy)
+            .await
+    }
+
+    /// Sends everything spendable of another account of the seed, as [`Self::do_send_all`]
+    /// does of the primary account.
+    pub async fn do_send_all_from_account(
+        &self,
+        account: u32,
+        address: &str,
+        memo: Option<MemoBytes>,
+        privacy_policy: PrivacyPolicy,
+    ) -> ZingoLibResult<SendAllResult> {
+        let other = self.other_account(account).await?;
+        self.send_all_from(
+            other.as_deref().unwrap_or(&self.wallet),
+            address,
+            memo,
+            privacy_policy,
+        )
+        .await
+    }
+
+    async fn send_all_from(
+        &self,
+        wallet: &LightWallet,
+        address: &str,
+        memo: Option<MemoBytes>,
+        privacy_policy: PrivacyPolicy,
+    ) -> ZingoLibResult<SendAllResult> {
+        let tos = vec![(address, 0, memo)];
+        let transparent_source_only = self.pays_tex_address(&tos);
+        let receivers = self.map_tos_to_receivers(tos)?;
+        let pools = self
+            .note_selection_policy(wallet, privacy_policy, &receivers, transparent_source_only)
+            .await?;
+        let (recipient, _, memo) = receivers
+            .into_iter()
+            .next()
+            .expect("map_tos_to_receivers to return the one receiver");
+        let transaction_submission_height = self.get_submission_height().await?;
+
+        let _lock = self.sync_lock.lock().await;
+        let (proposal, dust) = wallet
+            .create_send_all_proposal(&pools, recipient, memo, transaction_submission_height)
+            .await?;
+        proposal.check_privacy(privacy_policy)?;
+        let txid = self
+            .send_proposal(wallet, &proposal, transaction_submission_height)
+            .await?;
+        Ok(SendAllResult {
+            txid,
+            value_sent: proposal.total_to_receivers(),
+            fee: proposal.fee,
+            dust,
+        })
+    }
 
-            let sapling_prover = LocalTxProver::from_bytes(&sapling_spend, &sapling_output);
+    /// Proposes each transaction of `batch`, without sending, so that the fees can be
+    /// reviewed. The proposals may select the same notes, so later transactions of the
+    /// batch can turn out to have different fees, or to be unaffordable, when sent.
+    pub async fn do_propose_batch(
+        &self,
+        batch: &PaymentBatch,
+        max_actions: usize,
+        privacy_policy: PrivacyPolicy,
+    ) -> ZingoLibResult<Vec<Proposal>> {
+        let transaction_submission_height = self.get_submission_height().await?;
+        let mut proposals = vec![];
+        for (_, receivers, transparent_source_only) in self
+            .batch_transactions(batch, max_actions, privacy_policy)
+            .await?
+        {
+            proposals.push(
+                self.create_proposal(
+                    &self.wallet,
+                    receivers,
+                    transparent_source_only,
+                    privacy_policy,
+                    transaction_submission_height,
+                )
+                .await?,
+            );
+        }
+        Ok(proposals)
+    }
 
-            self.wallet
-                .send_to_addresses(
-                    sapling_prover,
-                    vec![crate::wallet::Pool::Orchard, crate::wallet::Pool::Sapling], // This policy doesn't allow
-                    // spend from transparent.
+    /// Pays `batch` in transactions of at most `max_actions` logical actions of outputs
+    /// each, one after the other. Every recipient is checked before the first is paid. A
+    /// transaction that fails after that doesn't stop the rest.
+    /// Returns what became of each transaction.
+    pub async fn do_send_batch(
+        &self,
+        batch: &PaymentBatch,
+        max_actions: usize,
+        privacy_policy: PrivacyPolicy,
+    ) -> ZingoLibResult<Vec<BatchTransactionResult>> {
+        let transaction_submission_height = self.get_submission_height().await?;
+        let transactions = self
+            .batch_transactions(batch, max_actions, privacy_policy)
+            .await?;
+
+        let _lock = self.sync_lock.lock().await;
+        let mut results = vec![];
+        for (payments, receivers, transparent_source_only) in transactions {
+            let result = match self
+                .create_proposal(
+                    &self.wallet,
                     receivers,
+                    transparent_source_only,
+                    privacy_policy,
                     transaction_submission_height,
-                    |transaction_bytes| {
-                        GrpcConnector::send_transaction(self.get_server_uri(), transaction_bytes)
-                    },
                 )
                 .await
-        };
+            {
+                Ok(proposal) => {
+                    match self
+                        .send_proposal(&self.wallet, &proposal, transaction_submission_height)
+                        .await
+                    {
+                        Ok(txid) => BatchTransactionResult::new(payments, Ok(txid), proposal.fee),
+                        Err(e) => BatchTransactionResult::new(payments, Err(e), 0),
+                    }
+                }
+                Err(e) => BatchTransactionResult::new(payments, Err(e.to_string()), 0),
+            };
+            results.push(result);
+        }
+        Ok(results)
+    }
 
-        result.map(|(transaction_id, _)| transaction_id)
+    /// Splits `batch` into the payments of each transaction, with their receivers and
+    /// whether they pay a TEX address. Fails if any recipient can't be paid under
+    /// `privacy_policy`.
+    #[allow(clippy::type_complexity)]
+    async fn batch_transactions<'a>(
+        &self,
+        batch: &'a PaymentBatch,
+        max_actions: usize,
+        privacy_policy: PrivacyPolicy,
+    ) -> ZingoLibResult<Vec<(&'a [BatchPayment], crate::wallet::Receivers, bool)>> {
+        let receiver_preference = self.wallet.wallet_options.read().await.receiver_preference;
+        let mut transactions = vec![];
+        for payments in batch.transactions(max_actions, receiver_preference) {
+            let tos = Self::batch_tuples(payments);
+            let transparent_source_only = self.pays_tex_address(&tos);
+            let receivers = self.map_tos_to_receivers(tos)?;
+            self.note_selection_policy(
+                &self.wallet,
+                privacy_policy,
+                &receivers,
+                transparent_source_only,
+            )
+            .await?;
+            transactions.push((payments, receivers, transparent_source_only));
+        }
+        Ok(transactions)
     }
 
-    pub async fn do_send_progress(&self) -> Result<LightWalletSendProgress, String> {
-        let progress = self.wallet.get_send_progress().await;
-        Ok(LightWalletSendProgress {
-            progress: progress.clone(),
-            interrupt_sync: *self.interrupt_sync.read().await,
-        })
+    fn batch_tuples(payments: &[BatchPayment]) -> Vec<(&str, u64, Option<MemoBytes>)> {
+        payments
+            .iter()
+            .map(|payment| {
+                (
+                    payment.address.as_str(),
+                    payment.amount,
+                    payment.memo.clone(),
+                )
+            })
+            .collect()
     }
 
-    pub async fn do_shield(
+    /// Decodes a ZIP-321 `zcash:` payment request URI.
+    pub fn do_parse_payment_uri(&self, uri: &str) -> ZingoLibResult<PaymentRequest> {
+        PaymentRequest::parse(uri, &self.config.chain)
+    }
+
+    /// Pays every payment of a ZIP-321 URI in one transaction.
+    pub async fn do_send_payment_uri(
         &self,
-        pools_to_shield: &[Pool],
-        address: Option<String>,
-    ) -> Result<String, String> {
-        let transaction_submission_height = self.get_submission_height().await?;
-        let fee = u64::from(MINIMUM_FEE); // TODO: This can no longer be hard coded, and must be calced
-                                          // as a fn of the transactions structure.
-        let tbal = self
-            .wallet
-            .tbalance(None)
+        uri: &str,
+        privacy_policy: PrivacyPolicy,
+    ) -> ZingoLibResult<String> {
+        let request = self.do_parse_payment_uri(uri)?;
+        self.do_send_with_privacy_policy(request.send_tuples()?, privacy_policy)
             .await
-            .expect("to receive a balance");
-        let sapling_bal = self
+    }
+
+    /// Creates a ZIP-321 URI requesting payment to the wallet's address at `address_index`.
+    pub fn do_make_payment_uri(
+        &self,
+        address_index: usize,
+        amount: Option<u64>,
+        memo: Option<MemoBytes>,
+        label: Option<String>,
+        message: Option<String>,
+    ) -> ZingoLibResult<String> {
+        let address = self
             .wallet
-            .spendable_sapling_balance(None)
-            .await
-            .unwrap_or(0);
+            .wallet_capability()
+            .addresses()
+            .iter()
+            .nth(address_index)
+            .map(|address| address.encode(&self.config.chain))
+            .ok_or_else(|| {
+                ZingoLibError::Error(format!(
+                    "The wallet has no address at index {address_index}"
+                ))
+            })?;
+        Ok(PaymentRequest {
+            payments: vec![Payment {
+                address,
+                amount,
+                memo,
+                label,
+                message,
+            }],
+        }
+        .to_uri())
+    }
 
-        // Make sure there is a balance, and it is greater than the amount
-        let balance_to_shield = if pools_to_shield.contains(&Pool::Transparent) {
-            tbal
-        } else {
-            0
-        } + if pools_to_shield.contains(&Pool::Sapling) {
-            sapling_bal
-        } else {
-            0
+    /// Counts the notes in `pool` that are spendable now, as listed by [`Self::do_list_notes`].
+    pub async fn do_count_spendable_notes(&self, pool: Pool) -> usize {
+        let notes = self.do_list_notes(false).await;
+        let key = match pool {
+            Pool::Orchard => "unspent_orchard_notes",
+            Pool::Sapling => "unspent_sapling_notes",
+            Pool::Transparent => "utxos",
         };
-        if balance_to_shield <= fee {
-            return Err(format!(
-                "Not enough transparent/sapling balance to shield. Have {} zats, need more than {} zats to cover tx fee",
-                balance_to_shield, fee
-            ));
-        }
+        notes[key]
+            .members()
+            .filter(|note| note["spendable"].as_bool().unwrap_or(true))
+            .count()
+    }
 
-        let addr = address
-            .unwrap_or(self.wallet.wallet_capability().addresses()[0].encode(&self.config.chain));
+    /// Merges the smallest spendable notes in `pool` into larger ones, with self-sends
+    /// to the wallet's own address that each spend at most `max_inputs` notes.
+    /// Nothing is sent unless every transaction keeps to `privacy_policy`.
+    pub async fn do_consolidate(
+        &self,
+        max_inputs: usize,
+        pool: Pool,
+        privacy_policy: PrivacyPolicy,
+    ) -> ZingoLibResult<ConsolidateResult> {
+        let notes_before = self.do_count_spendable_notes(pool).await;
+        let transaction_submission_height = self.get_submission_height().await?;
 
-        let receiver = self
-            .map_tos_to_receivers(vec![(&addr, balance_to_shield - fee, None)])
-            .expect("To build shield receiver.");
-        let result = {
-            let _lock = self.sync_lock.lock().await;
-            let (sapling_output, sapling_spend) = self.read_sapling_params()?;
+        let _lock = self.sync_lock.lock().await;
+        let proposals = self
+            .wallet
+            .create_consolidation_proposals(
+                pool,
+                max_inputs,
+                privacy_policy,
+                transaction_submission_height,
+            )
+            .await?;
+        for proposal in &proposals {
+            proposal.check_privacy(privacy_policy)?;
+        }
+        let mut result = ConsolidateResult {
+            notes_before,
+            ..Default::default()
+        };
+        for proposal in proposals {
+            let txid = self
+                .send_proposal(&self.wallet, &proposal, transaction_submission_height)
+                .await
+                .map_err(|e| {
+                    format!(
+                        "{e}. Consolidation stopped after sending {:?}",
+                        result.txids
+                    )
+                })?;
+            result.notes_merged += proposal.orchard_notes.len() + proposal.sapling_notes.len();
+            result.total_fee += proposal.fee;
+            result.txids.push(txid);
+        }
+        Ok(result)
+    }
 
-            let sapling_prover = LocalTxProver::from_bytes(&sapling_spend, &sapling_output);
+    /// Builds a send without signing it, so that a view-only wallet can hand it to the
+    /// wallet holding the spend keys. Returns the serialized [`UnsignedTransaction`].
+    pub async fn do_create_unsigned(
+        &self,
+        address_amount_memo_tuples: Vec<(&str, u64, Option<MemoBytes>)>,
+        privacy_policy: PrivacyPolicy,
+    ) -> Result<(UnsignedTransaction, Vec<u8>), String> {
+        let transparent_source_only = self.pays_tex_address(&address_amount_memo_tuples);
+        let receivers = self.map_tos_to_receivers(address_amount_memo_tuples)?;
+        let transaction_submission_height = self.get_submission_height().await?;
 
-            self.wallet
-                .send_to_addresses(
-                    sapling_prover,
-                    pools_to_shield.to_vec(),
-                    receiver,
-                    transaction_submission_height,
-                    |transaction_bytes| {
-                        GrpcConnector::send_transaction(self.get_server_uri(), transaction_bytes)
-                    },
+        let _lock = self.sync_lock.lock().await;
+        let unsigned = self
+            .wallet
+            .create_unsigned_transaction(
+                self.note_selection_policy(
+                    &self.wallet,
+                    privacy_policy,
+                    &receivers,
+                    transparent_source_only,
                 )
-                .await
-        };
+                .await?,
+                privacy_policy,
+                receivers,
+                transaction_submission_height,
+            )
+            .await?;
+        let mut bytes = vec![];
+        unsigned
+            .write(&mut bytes)
+            .map_err(|e| format!("Could not serialize unsigned transaction: {}", e))?;
+        Ok((unsigned, bytes))
+    }
+
+    /// Signs and proves a serialized [`UnsignedTransaction`] created by this wallet's
+    /// view-only counterpart. Nothing is broadcast.
+    /// Returns the unsigned transaction, its transaction id and the raw signed transaction.
+    pub async fn do_sign_unsigned(
+        &self,
+        unsigned_bytes: &[u8],
+    ) -> Result<(UnsignedTransaction, String, Vec<u8>), String> {
+        let unsigned = UnsignedTransaction::read(unsigned_bytes, &self.wallet.wallet_capability())
+            .map_err(|e| format!("Could not read unsigned transaction: {}", e))?;
+        let (transaction_id, raw_transaction) = self
+            .wallet
+            .sign_unsigned_transaction(&unsigned, self.sapling_prover()?)
+            .await?;
+        Ok((unsigned, transaction_id.to_string(), raw_transaction))
+    }
 
-        result.map(|(transaction_id, _)| transaction_id)
+    /// Broadcasts a raw transaction, such as one signed by [`Self::do_sign_unsigned`] or one
+    /// that fell out of the mempool. It is recorded as pending, and so is listed by
+    /// [`Self::do_list_txsummaries`] until it is mined.
+    pub async fn do_broadcast_raw(&self, raw_transaction: &[u8]) -> Result<String, String> {
+        let transaction_submission_height = self.get_submission_height().await?;
+
+        let _lock = self.sync_lock.lock().await;
+        self.wallet
+            .broadcast_raw_transaction(
+                raw_transaction,
+                transaction_submission_height,
+                |transaction_bytes| {
+                    GrpcConnector::send_transaction(self.get_server_uri(), transaction_bytes)
+                },
+            )
+            .await
     }
 
-    pub async fn do_sync(&self, print_updates: bool) -> Result<SyncResult, String> {
-        // Remember the previous sync id first
-        let prev_sync_id = self
-            .bsync_data
+    /// Lists the wallet's sends that were broadcast but not yet seen mined, with their
+    /// rebroadcasts.
+    pub async fn do_list_pending(&self) -> JsonValue {
+        let latest_height = BlockHeight::from_u32(self.wallet.last_synced_height().await as u32);
+        let txmds_readlock = self
+            .wallet
+            .transaction_context
+            .transaction_metadata_set
             .read()
-            .await
-            .block_data
-            .sync_status
+            .await;
+        self.wallet
+            .pending_transactions
             .read()
             .await
-            .sync_id;
+            .iter()
+            .map(|pending| {
+                let mined_height = txmds_readlock
+                    .current
+                    .get(&pending.txid)
+                    .and_then(|record| record.status.get_confirmed_height());
+                let status = if mined_height.is_some() {
+                    "mined"
+                } else if pending.is_expired(latest_height) {
+                    "expired"
+                } else {
+                    "pending"
+                };
+                object! {
+                    "txid" => pending.txid.to_string(),
+                    "status" => status,
+                    "mined_height" => mined_height.map(u32::from),
+                    "expiry_height" => u32::from(pending.expiry_height),
+                    "last_broadcast_height" => u32::from(pending.last_broadcast_height),
+                    "broadcasts" => pending.broadcasts,
+                    "last_error" => pending.last_error.clone(),
+                }
+            })
+            .collect::<Vec<JsonValue>>()
+            .into()
+    }
+
+    /// Expires the pending sends the chain has passed the expiry height of, and rebroadcasts
+    /// those that are due under the wallet's rebroadcast policy.
+    /// Returns whether any expired or were broadcast.
+    async fn update_pending(&self, wallet: &LightWallet) -> bool {
+        let latest_height = BlockHeight::from_u32(wallet.last_synced_height().await as u32);
+        let expired = wallet.expire_pending_transactions(latest_height).await;
+        for txid in &expired {
+            info!(
+                "Pending transaction {} expired unmined, its inputs are spendable again",
+                txid
+            );
+        }
+        let results = wallet
+            .rebroadcast_pending(latest_height, |transaction_bytes| {
+                GrpcConnector::send_transaction(self.get_server_uri(), transaction_bytes)
+            })
+            .await;
+        for (txid, result) in &results {
+            match result {
+                Ok(_) => debug!("Rebroadcast pending transaction {}", txid),
+                Err(e) => warn!("Could not rebroadcast pending transaction {}: {}", txid, e),
+            }
+        }
+        !expired.is_empty() || !results.is_empty()
+    }
 
-        // Start the sync
-        let r_fut = self.start_sync();
+    /// Runs [`Self::update_pending`] for every account, unless a sync is running, which does
+    /// so itself, and saves if any sends expired or were broadcast.
+    async fn update_all_pending(&self) {
+        let Ok(_lightclient_exclusion_lock) = self.sync_lock.try_lock() else {
+            return;
+        };
+        let mut updated_pending = self.update_pending(&self.wallet).await;
+        for other in self.wallet.other_wallets().await.iter() {
+            updated_pending |= self.update_pending(other).await;
+        }
+        if updated_pending {
+            if let Err(e) = self.save_internal_rust().await {
+                warn!("Could not save after rebroadcasting: {}", e);
+            }
+        }
+    }
 
-        // If printing updates, start a new task to print updates every 2 seconds.
-        let sync_result = if print_updates {
-            let sync_status_clone = self.bsync_data.read().await.block_data.sync_status.clone();
-            let (transmitter, mut receiver) = oneshot::channel::<i32>();
+    /// The pools to select notes from, as [`PrivacyPolicy::note_selection_pools`] with the
+    /// receiver preference of `wallet`.
+    async fn note_selection_policy(
+        &self,
+        wallet: &LightWallet,
+        privacy_policy: PrivacyPolicy,
+        receivers: &crate::wallet::Receivers,
+        transparent_source_only: bool,
+    ) -> ZingoLibResult<crate::wallet::NoteSelectionPolicy> {
+        let receiver_preference = wallet.wallet_options.read().await.receiver_preference;
+        privacy_policy.note_selection_pools(receiver_preference, receivers, transparent_source_only)
+    }
 
-            tokio::spawn(async move {
-                while sync_status_clone.read().await.sync_id == prev_sync_id {
-                    yield_now().await;
+    async fn create_proposal(
+        &self,
+        wallet: &LightWallet,
+        receivers: crate::wallet::Receivers,
+        transparent_source_only: bool,
+        privacy_policy: PrivacyPolicy,
+        transaction_submission_height: BlockHeight,
+    ) -> ZingoLibResult<Proposal> {
+        let proposal = wallet
+            .create_proposal(
+                self.note_selection_policy(
+                    wallet,
+                    privacy_policy,
+                    &receivers,
+                    transparent_source_only,
+                )
+                .await?,
+                receivers,
+                transaction_submission_height,
+            )
+            .await?;
+        proposal.check_privacy(privacy_policy)?;
+        Ok(proposal)
+    }
+
+    async fn send_proposal(
+        &self,
+        wallet: &LightWallet,
+        proposal: &Proposal,
+        transaction_submission_height: BlockHeight,
+    ) -> Result<String, String> {
+        // I am not clear on how long this operation may take, but it's
+        // clearly unnecessary in a send that doesn't include sapling
+        // TODO: Remove from sends that don't include Sapling
+        let sapling_prover = self.sapling_prover()?;
+
+        wallet
+            .send_proposal(
+                sapling_prover,
+                proposal,
+                transaction_submission_height,
+                |transaction_bytes| {
+                    GrpcConnector::send_transaction(self.get_server_uri(), transaction_bytes)
+                },
+            )
+            .await
+            .map(|(transaction_id, _)| transaction_id)
+    }
+
+    /// Selects notes and computes the fee for a send without proving or broadcasting it.
+    /// The proposal is held until [`Self::confirm_proposal`] is called, or replaced by the
+    /// next proposal.
+    pub async fn propose_send(
+        &self,
+        address_amount_memo_tuples: Vec<(&str, u64, Option<MemoBytes>)>,
+        privacy_policy: PrivacyPolicy,
+    ) -> ZingoLibResult<Proposal> {
+        self.propose_from(None, address_amount_memo_tuples, privacy_policy)
+            .await
+    }
+
+    /// Proposes a send from another account of the seed, as [`Self::propose_send`] does
+    /// from the primary account. Confirming it spends from the same account.
+    pub async fn propose_send_from_account(
+        &self,
+        account: u32,
+        address_amount_memo_tuples: Vec<(&str, u64, Option<MemoBytes>)>,
+        privacy_policy: PrivacyPolicy,
+    ) -> ZingoLibResult<Proposal> {
+        self.propose_from(Some(account), address_amount_memo_tuples, privacy_policy)
+            .await
+    }
+
+    async fn propose_from(
+        &self,
+        account: Option<u32>,
+        address_amount_memo_tuples: Vec<(&str, u64, Option<MemoBytes>)>,
+        privacy_policy: PrivacyPolicy,
+    ) -> ZingoLibResult<Proposal> {
+        let other = match account {
+            Some(account) => self.other_account(account).await?,
+            None => None,
+        };
+        let transparent_source_only = self.pays_tex_address(&address_amount_memo_tuples);
+        let receivers = self.map_tos_to_receivers(address_amount_memo_tuples)?;
+        let transaction_submission_height = self.get_submission_height().await?;
+        let proposal = self
+            .create_proposal(
+                other.as_deref().unwrap_or(&self.wallet),
+                receivers,
+                transparent_source_only,
+                privacy_policy,
+                transaction_submission_height,
+            )
+            .await?;
+        // The primary account is kept as `None`, so that confirming needs no lookup
+        let account = other.and_then(|other| other.account_index());
+        *self.latest_proposal.write().await = Some((account, proposal.clone()));
+        Ok(proposal)
+    }
+
+    /// Proves and broadcasts the proposal made by the latest call to [`Self::propose_send`]
+    /// or [`Self::propose_send_from_account`], from the account it was made for.
+    pub async fn confirm_proposal(&self) -> Result<String, String> {
+        let (account, proposal) = self
+            .latest_proposal
+            .write()
+            .await
+            .take()
+            .ok_or("No proposal to confirm. Create one with propose first.".to_string())?;
+        let other = match account {
+            Some(account) => self.other_account(account).await?,
+            None => None,
+        };
+        let transaction_submission_height = self.get_submission_height().await?;
+        let _lock = self.sync_lock.lock().await;
+        self.send_proposal(
+            other.as_deref().unwrap_or(&self.wallet),
+            &proposal,
+            transaction_submission_height,
+        )
+        .await
+    }
+
+    pub async fn do_send_progress(&self) -> Result<LightWalletSendProgress, String> {
+        let progress = self.wallet.get_send_progress().await;
+        Ok(LightWalletSendProgress {
+            progress: progress.clone(),
+            interrupt_sync: *self.interrupt_sync.read().await,
+        })
+    }
+
+    pub async fn do_shield(
+        &self,
+        pools_to_shield: &[Pool],
+        address: Option<String>,
+    ) -> Result<String, String> {
+        let _lock = self.sync_lock.lock().await;
+        self.shield(&self.wallet, pools_to_shield, address)
+            .await
+            .map(|(transaction_id, _)| transaction_id)
+    }
+
+    /// Shields the funds of another account of the seed, as [`Self::do_shield`] does those
+    /// of the primary account. The default address is the account's own first address.
+    pub async fn do_shield_from_account(
+        &self,
+        account: u32,
+        pools_to_shield: &[Pool],
+        address: Option<String>,
+    ) -> Result<String, String> {
+        let other = self.other_account(account).await?;
+        let _lock = self.sync_lock.lock().await;
+        self.shield(
+            other.as_deref().unwrap_or(&self.wallet),
+            pools_to_shield,
+            address,
+        )
+        .await
+        .map(|(transaction_id, _)| transaction_id)
+    }
+
+    /// Sweeps `pools_to_shield` of `wallet` into `address`, or the wallet's first address,
+    /// for the ZIP-317 fee of the sweep. Returns the txid and the value shielded. The caller
+    /// holds the sync lock.
+    async fn shield(
+        &self,
+        wallet: &LightWallet,
+        pools_to_shield: &[Pool],
+        address: Option<String>,
+    ) -> Result<(String, u64), String> {
+        let transaction_submission_height = self.get_submission_height().await?;
+        let destination = match address {
+            Some(address) => {
+                self.map_tos_to_receivers(vec![(&address, 0, None)])?
+                    .remove(0)
+                    .0
+            }
+            None => zcash_client_backend::address::Address::Unified(
+                wallet.wallet_capability().addresses()[0].clone(),
+            ),
+        };
+        let utxos = if pools_to_shield.contains(&Pool::Transparent) {
+            wallet.confirmed_utxos(1, |_| true).await
+        } else {
+            vec![]
+        };
+        let proposal = match wallet
+            .propose_shielding(
+                utxos,
+                pools_to_shield.contains(&Pool::Sapling),
+                destination,
+                transaction_submission_height,
+            )
+            .await?
+        {
+            Ok(proposal) => proposal,
+            Err(unaffordable) => {
+                return Err(format!(
+                    "Not enough transparent/sapling balance to shield. Have {} zats, need more than {} zats to cover tx fee",
+                    unaffordable.total_selected(),
+                    unaffordable.fee
+                ))
+            }
+        };
+        let value = proposal.total_to_receivers();
+        self.send_proposal(wallet, &proposal, transaction_submission_height)
+            .await
+            .map(|transaction_id| (transaction_id, value))
+    }
+
+    /// Shields the transparent funds under the wallet's auto-shield policy, unless another
+    /// sync or send holds the sync lock. `None` without a policy. The sweep is proposed as
+    /// [`Self::do_shield`]'s is, but only from the primary account.
+    async fn auto_shield(&self) -> Option<AutoShieldResult> {
+        let policy = self.wallet.wallet_options.read().await.auto_shield?;
+        let Ok(_lock) = self.sync_lock.try_lock() else {
+            return Some(AutoShieldResult::LockContended);
+        };
+        let synced_height = self.wallet.last_synced_height().await;
+        let last_height = self
+            .wallet
+            .wallet_options
+            .read()
+            .await
+            .last_auto_shield_height;
+        if let Some(last_height) = last_height {
+            if synced_height < last_height + policy.min_blocks_between as u64 {
+                return Some(AutoShieldResult::TooSoon { last_height });
+            }
+        }
+        // The threshold is checked against the very utxos that are shielded
+        let utxos = self.wallet.auto_shield_utxos().await;
+        let balance = utxos.iter().map(|utxo| utxo.value).sum::<u64>();
+        if balance <= policy.min_balance {
+            return Some(AutoShieldResult::BelowThreshold { balance });
+        }
+        let destination = match self.shielding_destination(policy.pool) {
+            Some(destination) => destination,
+            None => {
+                return Some(AutoShieldResult::Failed(format!(
+                    "The wallet's first address has no {:?} receiver",
+                    policy.pool
+                )))
+            }
+        };
+        let shielded = match self.get_submission_height().await {
+            Ok(height) => match self
+                .wallet
+                .propose_shielding(utxos, false, destination, height)
+                .await
+            {
+                Ok(Ok(proposal)) => {
+                    let value = proposal.total_to_receivers();
+                    self.send_proposal(&self.wallet, &proposal, height)
+                        .await
+                        .map(|txid| (txid, value))
+                }
+                Ok(Err(unaffordable)) => Err(format!(
+                    "Have {} zats, need more than {} zats to cover the fee",
+                    balance, unaffordable.fee
+                )),
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        };
+        Some(match shielded {
+            Ok((txid, value)) => {
+                self.wallet
+                    .wallet_options
+                    .write()
+                    .await
+                    .last_auto_shield_height = Some(synced_height);
+                if let Err(e) = self.save_internal_rust().await {
+                    warn!("Could not save after shielding: {}", e);
+                }
+                AutoShieldResult::Shielded { txid, value }
+            }
+            Err(e) => AutoShieldResult::Failed(e),
+        })
+    }
+
+    /// An address of the wallet that is only paid in `pool`.
+    fn shielding_destination(
+        &self,
+        pool: ShieldedPool,
+    ) -> Option<zcash_client_backend::address::Address> {
+        let wc = self.wallet.wallet_capability();
+        let first = wc.addresses().iter().next()?.clone();
+        match pool {
+            ShieldedPool::Sapling => first
+                .sapling()
+                .map(|sapling| zcash_client_backend::address::Address::Sapling(*sapling)),
+            ShieldedPool::Orchard => first.orchard().and_then(|orchard| {
+                zcash_client_backend::address::UnifiedAddress::from_receivers(
+                    Some(*orchard),
+                    None,
+                    None,
+                )
+                .map(zcash_client_backend::address::Address::Unified)
+            }),
+        }
+    }
+
+    pub async fn do_sync(&self, print_updates: bool) -> Result<SyncResult, String> {
+        // Remember the previous sync id first
+        let prev_sync_id = self
+            .bsync_data
+            .read()
+            .await
+            .block_data
+            .sync_status
+            .read()
+            .await
+            .sync_id;
+
+        // Start the sync
+        let r_fut = self.start_sync();
+
+        // If printing updates, start a new task to print updates every 2 seconds.
+        let sync_result = if print_updates {
+            let sync_status_clone = self.bsync_data.read().await.block_data.sync_status.clone();
+            let (transmitter, mut receiver) = oneshot::channel::<i32>();
+
+            tokio::spawn(async move {
+                while sync_status_clone.read().await.sync_id == prev_sync_id {
+                    yield_now().await;
                     sleep(Duration::from_secs(3)).await;
                 }
 
@@ -1029,6 +2069,15 @@ impl LightClient {
 
         // Mark the sync data as finished, which should clear everything
         self.bsync_data.read().await.finish().await;
+        if let Err(e) = &sync_result {
+            self.sync_events().await.send(SyncEvent::Error(e.clone()));
+        }
+
+        let mut sync_result = sync_result;
+        if let Ok(result) = sync_result.as_mut() {
+            result.auto_shield = self.auto_shield().await;
+            result.reused_ephemeral_taddrs = self.reused_ephemeral_taddrs().await;
+        }
         sync_result
     }
 
@@ -1043,6 +2092,17 @@ impl LightClient {
             .clone()
     }
 
+    /// Subscribes to the events of the syncs from now on, so that their progress need not be
+    /// polled with `do_sync_status`. A subscriber that falls too far behind misses the
+    /// oldest events, and its receiver says how many.
+    pub async fn subscribe_sync_events(&self) -> broadcast::Receiver<SyncEvent> {
+        self.sync_events().await.subscribe()
+    }
+
+    async fn sync_events(&self) -> SyncEventSender {
+        self.bsync_data.read().await.block_data.sync_events.clone()
+    }
+
     pub async fn do_total_memobytes_to_address(&self) -> finsight::TotalMemoBytesToAddress {
         let summaries = self.do_list_txsummaries().await;
         let mut memobytes_by_address = HashMap::new();
@@ -1142,6 +2202,11 @@ impl LightClient {
     pub async fn interrupt_sync_after_batch(&self, set_interrupt: bool) {
         *self.interrupt_sync.write().await = set_interrupt;
     }
+    /// The prover chosen by the config.
+    fn sapling_prover(&self) -> Result<SaplingProver, String> {
+        SaplingProver::new(self.config.prover, || self.read_sapling_params())
+    }
+
     #[cfg(feature = "embed_params")]
     fn read_sapling_params(&self) -> Result<(Vec<u8>, Vec<u8>), String> {
         // Read Sapling Params
@@ -1260,13 +2325,17 @@ impl LightClient {
     }
 
     pub async fn set_wallet_initial_state(&self, height: u64) {
+        self.set_initial_state_of(&self.wallet, height).await
+    }
+
+    async fn set_initial_state_of(&self, wallet: &LightWallet, height: u64) {
         let state = self
             .download_initial_tree_state_from_lightwalletd(height)
             .await;
 
         if let Some((height, hash, tree)) = state {
             debug!("Setting initial state to height {}, tree {}", height, tree);
-            self.wallet
+            wallet
                 .set_initial_block(height, hash.as_str(), tree.as_str())
                 .await;
         }
@@ -1336,6 +2405,8 @@ impl LightClient {
 
                 let h2 = tokio::spawn(async move {
                     loop {
+                        // Sends the server dropped are retried between syncs as well
+                        lci.update_all_pending().await;
                         //debug!("Monitoring mempool");
                         let r = GrpcConnector::monitor_mempool(
                             uri.clone(),
@@ -1359,36 +2430,105 @@ impl LightClient {
     }
 
     /// Start syncing in batches with the max size, to manage memory consumption.
+    /// The seed's other accounts and the imported keys sync along with the primary account:
+    /// each batch is downloaded and trial decrypted once, with the keys of all of them.
     async fn start_sync(&self) -> Result<SyncResult, String> {
         // We can only do one sync at a time because we sync blocks in serial order
         // If we allow multiple syncs, they'll all get jumbled up.
-        // TODO:  We run on resource constrained systems, where a single thread of
-        // execution often consumes most of the memory available, on other systems
-        // we might parallelize sync.
+        // We run on resource constrained systems, where a single thread of
+        // execution often consumes most of the memory available. On other systems
+        // trial decryption can be spread over cores, see
+        // `ZingoConfig::trial_decryption_threads`.
         let lightclient_exclusion_lock = self.sync_lock.lock().await;
 
-        // The top of the wallet
-        let last_synced_height = self.wallet.last_synced_height().await;
+        self.sync_events().await.begin();
+        let others = self.other_wallets_in_step().await;
+        let wallets = std::iter::once(&*self.wallet)
+            .chain(others.iter().map(|other| &**other))
+            .collect::<Vec<_>>();
+        let res = self.sync_wallets(&wallets, None).await;
+        let mut updated_pending = false;
+        for wallet in &wallets {
+            updated_pending |= self.update_pending(wallet).await;
+        }
+        if res.is_ok() {
+            updated_pending |= self.update_ephemeral_taddrs().await;
+        }
 
-        // If our internal state gets damaged somehow (for example,
-        // a resync that gets interrupted partway through) we need to make sure
-        // our witness trees are aligned with our blockchain data
-        self.wallet
-            .ensure_witness_tree_not_above_wallet_blocks()
-            .await;
+        if updated_pending {
+            if let Err(e) = self.save_internal_rust().await {
+                warn!("Could not save after rebroadcasting: {}", e);
+            }
+        }
 
-        // This is a fresh wallet. We need to get the initial trees
-        if self.wallet.has_any_empty_commitment_trees().await
-            && last_synced_height >= self.config.sapling_activation_height()
-        {
-            let trees = crate::grpc_connector::GrpcConnector::get_trees(
-                self.get_server_uri(),
-                last_synced_height,
-            )
-            .await
-            .unwrap();
-            self.wallet.initiate_witness_trees(trees).await;
-        };
+        drop(lightclient_exclusion_lock);
+        res
+    }
+
+    /// The seed's other accounts and the imported keys that have synced as far as the
+    /// primary account, once those behind it are backfilled. One is only behind when its
+    /// backfill on being added was cut short, or after a rescan when its birthday is below
+    /// the primary account's.
+    async fn other_wallets_in_step(&self) -> Vec<Arc<LightWallet>> {
+        let height = self.wallet.last_synced_height().await;
+        let mut in_step = vec![];
+        for other in self.wallet.other_wallets().await {
+            if let Err(e) = self.backfill(&other).await {
+                warn!(
+                    "Could not backfill {}: {}",
+                    self.first_address_of(&other).unwrap_or_default(),
+                    e
+                );
+                continue;
+            }
+            if other.last_synced_height().await == height {
+                in_step.push(other);
+            }
+        }
+        in_step
+    }
+
+    /// Scans `wallet`, a new account or imported key, on its own from its last synced block
+    /// up to the primary account's. From there on, it syncs along with the primary account.
+    async fn backfill(&self, wallet: &LightWallet) -> Result<(), String> {
+        let height = self.wallet.last_synced_height().await;
+        if wallet.last_synced_height().await < height {
+            self.sync_wallets(&[wallet], Some(height)).await?;
+        }
+        Ok(())
+    }
+
+    /// Syncs the `wallets`, which have all synced to the same block, together: each batch
+    /// is downloaded once and trial decrypted with the keys of all of them. The first one's
+    /// blocks are checked for reorgs. The sync stops at `up_to`, if given, rather than at
+    /// the chain tip.
+    async fn sync_wallets(
+        &self,
+        wallets: &[&LightWallet],
+        up_to: Option<u64>,
+    ) -> Result<SyncResult, String> {
+        // The top of the wallets
+        let last_synced_height = wallets[0].last_synced_height().await;
+
+        for wallet in wallets {
+            // If our internal state gets damaged somehow (for example,
+            // a resync that gets interrupted partway through) we need to make sure
+            // our witness trees are aligned with our blockchain data
+            wallet.ensure_witness_tree_not_above_wallet_blocks().await;
+
+            // This is a fresh wallet. We need to get the initial trees
+            if wallet.has_any_empty_commitment_trees().await
+                && last_synced_height >= self.config.sapling_activation_height()
+            {
+                let trees = crate::grpc_connector::GrpcConnector::get_trees(
+                    self.get_server_uri(),
+                    last_synced_height,
+                )
+                .await
+                .unwrap();
+                wallet.initiate_witness_trees(trees).await;
+            };
+        }
 
         let latest_blockid =
             GrpcConnector::get_latest_block(self.config.get_lightwalletd_uri()).await?;
@@ -1406,80 +2546,552 @@ impl LightClient {
 
         if latest_blockid.height == last_synced_height
             && !latest_blockid.hash().is_empty()
-            && latest_blockid.hash() != self.wallet.last_synced_hash().await
+            && latest_blockid.hash() != wallets[0].last_synced_hash().await
         {
             log::warn!("One block reorg at height {}", last_synced_height);
+            self.sync_events().await.send(SyncEvent::ReorgDetected {
+                height: last_synced_height,
+            });
             // This is a one-block reorg, so pop the last block. Even if there are more blocks to reorg, this is enough
             // to trigger a sync, which will then reorg the remaining blocks
-            BlockManagementData::invalidate_block(
-                last_synced_height,
-                self.wallet.blocks.clone(),
-                self.wallet
-                    .transaction_context
-                    .transaction_metadata_set
-                    .clone(),
-            )
-            .await;
+            Self::invalidate_top_blocks(wallets).await;
         }
+        let latest_height = up_to.map_or(latest_blockid.height, |up_to| {
+            cmp::min(up_to, latest_blockid.height)
+        });
 
-        // Re-read the last scanned height
-        let last_scanned_height = self.wallet.last_synced_height().await;
+        let mut spend_before_sync = self.config.sync_mode == SyncMode::SpendBeforeSync;
+        for wallet in wallets {
+            // A restored wallet, or one from before discovery, may have used transparent
+            // addresses past those it has
+            self.discover_transparent_addresses(wallet, latest_height)
+                .await;
+            // A wallet part way through a spend-before-sync carries on with it in either mode
+            spend_before_sync |= !wallet.unscanned_ranges.read().await.is_empty();
+        }
 
-        let mut latest_block_batches = vec![];
-        let mut prev = last_scanned_height;
-        while latest_block_batches.is_empty() || prev != latest_blockid.height {
-            let batch = cmp::min(latest_blockid.height, prev + zingoconfig::BATCH_SIZE);
-            prev = batch;
-            latest_block_batches.push(batch);
+        if spend_before_sync {
+            match self.seed_subtree_roots(wallets).await {
+                Ok(shard_ends) => {
+                    return self
+                        .sync_by_priority(wallets, latest_height, shard_ends)
+                        .await
+                }
+                Err(e) if Self::unscanned_of(wallets).await.is_empty() => {
+                    warn!("No subtree roots from the server, syncing linearly: {}", e);
+                }
+                Err(e) => return Err(e),
+            }
         }
 
-        // Increment the sync ID so the caller can determine when it is over
-        self.bsync_data
-            .write()
-            .await
-            .block_data
-            .sync_status
-            .write()
+        // Re-read the last scanned height
+        let last_scanned_height = wallets[0].last_synced_height().await;
+
+        // Each batch is sized from the ones before it, so the total is an estimate until the last
+        let mut batch_sizer = BatchSizer::new(&self.config);
+        let sync_status = self.bsync_data.read().await.block_data.sync_status.clone();
+        self.sync_events()
             .await
-            .start_new(latest_block_batches.len());
+            .start(latest_height.saturating_sub(last_scanned_height));
+
+        // Increment the sync ID so the caller can determine when it is over
+        sync_status.write().await.start_new(
+            batch_sizer
+                .batches_in(latest_height.saturating_sub(last_scanned_height))
+                .max(1),
+        );
 
         let mut res = Err("No batches were run!".to_string());
-        for (batch_num, batch_latest_block) in latest_block_batches.into_iter().enumerate() {
-            res = self.sync_nth_batch(batch_latest_block, batch_num).await;
+        let mut prev = last_scanned_height;
+        let mut batch_num = 0;
+        loop {
+            let batch_latest_block = cmp::min(latest_height, prev + batch_sizer.size());
+            sync_status.write().await.batch_total =
+                batch_num + 1 + batch_sizer.batches_in(latest_height - batch_latest_block);
+
+            let batch_started = Instant::now();
+            res = self
+                .sync_nth_batch(wallets, batch_latest_block, batch_num)
+                .await;
             if res.is_err() {
                 // If something went wrong during a batch, reset the wallet state to
                 // how it was before the latest batch
-                BlockManagementData::invalidate_block(
-                    self.wallet.last_synced_height().await,
-                    self.wallet.blocks.clone(),
-                    self.wallet
-                        .transaction_context
-                        .transaction_metadata_set
-                        .clone(),
-                )
-                .await;
+                Self::invalidate_top_blocks(wallets).await;
             }
             res.as_ref()?;
+            {
+                let sync_status = sync_status.read().await;
+                batch_sizer.record(
+                    sync_status.blocks_done,
+                    sync_status.outputs_done,
+                    batch_started.elapsed(),
+                );
+            }
+            prev = batch_latest_block;
+            batch_num += 1;
             if *self.interrupt_sync.read().await {
                 log::debug!("LightClient interrupt_sync is true");
                 break;
             }
+            if prev >= latest_height {
+                break;
+            }
         }
 
-        drop(lightclient_exclusion_lock);
         res
     }
 
+    /// Pops the top block of each of the `wallets`, with their transactions in it.
+    async fn invalidate_top_blocks(wallets: &[&LightWallet]) {
+        for wallet in wallets {
+            BlockManagementData::invalidate_block(
+                wallet.last_synced_height().await,
+                wallet.blocks.clone(),
+                wallet.transaction_context.transaction_metadata_set.clone(),
+            )
+            .await;
+        }
+    }
+
+    /// The blocks that any of the `wallets` has yet to scan.
+    async fn unscanned_of(wallets: &[&LightWallet]) -> Vec<(u64, u64)> {
+        let mut unscanned = vec![];
+        for wallet in wallets {
+            for range in wallet.unscanned_ranges.read().await.iter() {
+                scan_priority::insert(&mut unscanned, *range);
+            }
+        }
+        unscanned
+    }
+
+    /// Inserts the server's roots of the shards completed since each wallet's birthday into
+    /// its witness trees, from the first one they don't hold yet. The roots are fetched once
+    /// for all the `wallets`. Returns the heights that completed each Sapling and each
+    /// Orchard shard.
+    async fn seed_subtree_roots(&self, wallets: &[&LightWallet]) -> Result<[Vec<u64>; 2], String> {
+        let mut held = vec![];
+        for wallet in wallets {
+            held.push(wallet.subtree_root_ends.read().await.clone());
+        }
+        let mut shard_ends = [vec![], vec![]];
+        for (pool, protocol) in [ShieldedProtocol::Sapling, ShieldedProtocol::Orchard]
+            .into_iter()
+            .enumerate()
+        {
+            let start_index = held.iter().map(|ends| ends[pool].len()).min().unwrap_or(0);
+            let roots = GrpcConnector::get_subtree_roots(
+                self.get_server_uri(),
+                protocol,
+                start_index as u32,
+            )
+            .await?;
+            let ends = &mut shard_ends[pool];
+            ends.extend_from_slice(&held[0][pool][..start_index]);
+            ends.extend(roots.iter().map(|root| root.completing_block_height));
+            for (wallet, held) in wallets.iter().zip(&held) {
+                // Shards completed before the birthday hold none of the wallet's notes
+                let birthday = wallet.get_birthday().await;
+                let first = cmp::max(
+                    held[pool].len(),
+                    ends.partition_point(|end| *end < birthday),
+                )
+                .min(ends.len());
+                let new_roots = &roots[first - start_index..];
+                let transactions = wallet.transactions();
+                let mut transactions = transactions.write().await;
+                if let Some(ref mut trees) = transactions.witness_trees {
+                    match protocol {
+                        ShieldedProtocol::Sapling => {
+                            trees.insert_subtree_roots::<SaplingDomain>(first as u64, new_roots)?
+                        }
+                        ShieldedProtocol::Orchard => {
+                            trees.insert_subtree_roots::<OrchardDomain>(first as u64, new_roots)?
+                        }
+                    }
+                }
+            }
+        }
+        for wallet in wallets {
+            *wallet.subtree_root_ends.write().await = shard_ends.clone();
+        }
+        Ok(shard_ends)
+    }
+
+    /// Scans the blocks the `wallets` have not seen a batch at a time, in the order of
+    /// [`scan_priority::plan`], planning again after each batch as the notes it finds change
+    /// which shards come first. Each batch is scanned for the wallets that have yet to scan
+    /// it. The blocks left are saved with the wallets for the next sync.
+    async fn sync_by_priority(
+        &self,
+        wallets: &[&LightWallet],
+        latest_height: u64,
+        shard_ends: [Vec<u64>; 2],
+    ) -> Result<SyncResult, String> {
+        for wallet in wallets {
+            let last_synced_height = wallet.last_synced_height().await;
+            if latest_height > last_synced_height {
+                scan_priority::insert(
+                    &mut *wallet.unscanned_ranges.write().await,
+                    (last_synced_height + 1, latest_height),
+                );
+            }
+        }
+        // The block that completed the last shard may also hold notes of the open one
+        let tip_start = shard_ends
+            .iter()
+            .map(|ends| ends.last().copied().unwrap_or(0))
+            .min()
+            .unwrap_or(0);
+        // Notes below the tip range are witnessed from the subtree roots, and those in it
+        // from the server's frontier as of the block before it. The frontier goes in once,
+        // while none of the tip range is scanned, so it is never put behind blocks the
+        // trees already hold.
+        let mut tip_unscanned = vec![];
+        for wallet in wallets {
+            if wallet
+                .unscanned_ranges
+                .read()
+                .await
+                .iter()
+                .any(|(start, end)| (*start..=*end).contains(&tip_start))
+            {
+                tip_unscanned.push(wallet);
+            }
+        }
+        if !tip_unscanned.is_empty() && tip_start > self.config.sapling_activation_height() {
+            let trees = GrpcConnector::get_trees(self.get_server_uri(), tip_start - 1).await?;
+            for wallet in tip_unscanned {
+                wallet.initiate_witness_trees(trees.clone()).await;
+            }
+        }
+
+        let sync_status = self.bsync_data.read().await.block_data.sync_status.clone();
+        sync_status.write().await.start_new(0);
+        let unscanned_blocks = Self::unscanned_of(wallets)
+            .await
+            .iter()
+            .map(|(start, end)| end - start + 1)
+            .sum();
+        self.sync_events().await.start(unscanned_blocks);
+
+        // Spends found out of order, for the notes found after them
+        let mut revealed_nullifiers = HashMap::new();
+        let mut batch_sizer = BatchSizer::new(&self.config);
+        let mut batch_num = 0;
+        let mut total_blocks_synced = 0;
+        loop {
+            let mut unscanned = vec![];
+            let mut found_note_shards = vec![];
+            for wallet in wallets {
+                unscanned.push(wallet.unscanned_ranges.read().await.clone());
+                found_note_shards.extend(
+                    self.found_note_shards(wallet, &shard_ends, latest_height)
+                        .await,
+                );
+            }
+            let plan = scan_priority::plan(&unscanned.concat(), tip_start, &found_note_shards);
+            let Some(range) = plan.first().copied() else {
+                break;
+            };
+            sync_status.write().await.batch_total = batch_num
+                + plan
+                    .iter()
+                    .map(|range| batch_sizer.batches_in(range.end - range.start + 1))
+                    .sum::<usize>();
+
+            let batch_start = range.start;
+            let (scanned_for, batch_end) = scan_priority::wallets_to_scan(
+                &unscanned,
+                batch_start,
+                cmp::min(range.end, batch_start + batch_sizer.size() - 1),
+            );
+            let batch_wallets = scanned_for
+                .into_iter()
+                .map(|index| wallets[index])
+                .collect::<Vec<_>>();
+            let batch_started = Instant::now();
+            debug!(
+                "Scanning blocks {} to {}, {:?}",
+                batch_start, batch_end, range.priority
+            );
+
+            let last_synced_height = batch_wallets[0].last_synced_height().await;
+            let existing_blocks = if batch_start == last_synced_height + 1 {
+                batch_wallets[0].get_blocks().await
+            } else {
+                vec![]
+            };
+            let revealed = match range.priority {
+                scan_priority::ScanPriority::Historic => None,
+                _ => Some(&mut revealed_nullifiers),
+            };
+            let res = self
+                .scan_batch(
+                    &batch_wallets,
+                    batch_end,
+                    batch_start,
+                    batch_num,
+                    existing_blocks,
+                    revealed,
+                )
+                .await;
+            match res {
+                Ok(res) => total_blocks_synced += res.total_blocks_synced,
+                Err(e) => {
+                    if batch_end > last_synced_height {
+                        Self::invalidate_top_blocks(&batch_wallets).await;
+                    }
+                    return Err(e);
+                }
+            }
+            for wallet in &batch_wallets {
+                scan_priority::remove(
+                    &mut *wallet.unscanned_ranges.write().await,
+                    (batch_start, batch_end),
+                );
+            }
+            for wallet in wallets {
+                self.scan_revealed_spends(wallet, &revealed_nullifiers)
+                    .await?;
+            }
+            let forgotten = scan_priority::prune_revealed(
+                &mut revealed_nullifiers,
+                &Self::unscanned_of(wallets).await,
+                scan_priority::MAX_REVEALED_NULLIFIERS,
+            );
+            if forgotten > 0 {
+                warn!(
+                    "Forgot the {} lowest nullifiers revealed out of order. A note found below \
+                     them that they spend shows as unspent until a rescan.",
+                    forgotten
+                );
+            }
+            self.save_internal_rust().await?;
+            let outputs_done = sync_status.read().await.outputs_done;
+            batch_sizer.record(
+                batch_end - batch_start + 1,
+                outputs_done,
+                batch_started.elapsed(),
+            );
+
+            batch_num += 1;
+            if *self.interrupt_sync.read().await {
+                log::debug!("LightClient interrupt_sync is true");
+                break;
+            }
+        }
+
+        Ok(SyncResult {
+            success: true,
+            latest_block: wallets[0].last_synced_height().await,
+            total_blocks_synced,
+            auto_shield: None,
+            reused_ephemeral_taddrs: vec![],
+        })
+    }
+
+    /// The blocks of the shards that hold the wallet's unspent notes.
+    async fn found_note_shards(
+        &self,
+        wallet: &LightWallet,
+        shard_ends: &[Vec<u64>; 2],
+        tip: u64,
+    ) -> Vec<(u64, u64)> {
+        let transactions = wallet.transactions();
+        let transactions = transactions.read().await;
+        let mut shards = vec![];
+        for transaction in transactions
+            .current
+            .values()
+            .filter(|transaction| transaction.status.is_confirmed())
+        {
+            let height = u64::from(transaction.status.get_height());
+            let unspent = [
+                transaction
+                    .sapling_notes
+                    .iter()
+                    .any(|note| note.spent().is_none()),
+                transaction
+                    .orchard_notes
+                    .iter()
+                    .any(|note| note.spent().is_none()),
+            ];
+            for (ends, _) in shard_ends
+                .iter()
+                .zip(unspent)
+                .filter(|(_, unspent)| *unspent)
+            {
+                shards.push(scan_priority::shard_containing(ends, height, tip));
+            }
+        }
+        shards
+    }
+
+    /// Notes found below blocks that were scanned before them may have been spent in those
+    /// blocks. Scans the transactions that revealed their nullifiers, which marks them spent.
+    async fn scan_revealed_spends(
+        &self,
+        wallet: &LightWallet,
+        revealed_nullifiers: &HashMap<Vec<u8>, (TxId, u64, u32)>,
+    ) -> Result<(), String> {
+        if revealed_nullifiers.is_empty() {
+            return Ok(());
+        }
+        let last_synced_height = wallet.last_synced_height().await;
+        let spends = wallet
+            .transactions()
+            .read()
+            .await
+            .get_notes_for_updating(last_synced_height)
+            .into_iter()
+            .filter_map(|(_, nullifier, _)| {
+                let nullifier = match nullifier {
+                    PoolNullifier::Sapling(nullifier) => nullifier.to_vec(),
+                    PoolNullifier::Orchard(nullifier) => nullifier.to_bytes().to_vec(),
+                };
+                revealed_nullifiers.get(&nullifier).copied()
+            })
+            .map(|(txid, height, time)| (txid, (height, time)))
+            .collect::<HashMap<_, _>>();
+        for (txid, (height, time)) in spends {
+            let transaction = GrpcConnector::get_full_transaction(
+                self.get_server_uri(),
+                &txid,
+                self.config.chain,
+            )
+            .await?;
+            TransactionContext::new(
+                &self.config,
+                wallet.wallet_capability(),
+                wallet.transactions(),
+            )
+            .scan_full_tx(
+                &transaction,
+                ConfirmationStatus::Confirmed(BlockHeight::from_u32(height as u32)),
+                time,
+                None,
+            )
+            .await;
+        }
+        Ok(())
+    }
+
+    /// Looks up the transparent addresses past the wallet's own, one at a time, until
+    /// [`ZingoConfig::transparent_gap_limit`] in a row have no transactions. The wallet gets
+    /// addresses up to the last one that was used, and the transactions of those below its
+    /// synced height are scanned here, as sync only fetches the ones above it. Runs once
+    /// each time the wallet is loaded; a failure is logged, and retried on the next sync.
+    async fn discover_transparent_addresses(&self, wallet: &LightWallet, end_height: u64) {
+        let gap_limit = self.config.transparent_gap_limit;
+        let capability = wallet.wallet_capability();
+        if gap_limit == 0
+            || !capability.transparent.can_view()
+            || wallet
+                .transparent_addresses_discovered
+                .load(std::sync::atomic::Ordering::Acquire)
+        {
+            return;
+        }
+        if let Err(e) = self
+            .discover_transparent_addresses_from(wallet, gap_limit, end_height)
+            .await
+        {
+            warn!("Stopped discovering transparent addresses: {}", e);
+            return;
+        }
+        wallet
+            .transparent_addresses_discovered
+            .store(true, std::sync::atomic::Ordering::Release);
+    }
+
+    async fn discover_transparent_addresses_from(
+        &self,
+        wallet: &LightWallet,
+        gap_limit: usize,
+        end_height: u64,
+    ) -> Result<(), String> {
+        let capability = wallet.wallet_capability();
+        let start_height = wallet.get_birthday().await;
+        let last_synced_height = wallet.last_synced_height().await;
+        let mut index = capability.next_transparent_index();
+        let mut unused = 0;
+        while unused < gap_limit {
+            let taddr = match capability.transparent_address_at(index)? {
+                Some(taddr) => address_from_pubkeyhash(&self.config, taddr),
+                None => return Ok(()),
+            };
+            let transactions = GrpcConnector::get_taddr_transactions_between(
+                self.get_server_uri(),
+                taddr,
+                start_height,
+                end_height,
+            )
+            .await?;
+            if transactions.is_empty() {
+                unused += 1;
+            } else {
+                while capability.next_transparent_index() <= index {
+                    let address = capability.new_address(capability.can_view())?;
+                    info!(
+                        "Found a used transparent address, added {}",
+                        address.encode(&self.config.chain)
+                    );
+                }
+                for raw_transaction in transactions
+                    .iter()
+                    .filter(|raw_transaction| raw_transaction.height <= last_synced_height)
+                {
+                    self.scan_raw_transaction(wallet, raw_transaction).await?;
+                }
+                unused = 0;
+            }
+            index += 1;
+        }
+        Ok(())
+    }
+
+    /// Scans a mined transaction the server returned, taking its block time from the tree
+    /// state at its height.
+    async fn scan_raw_transaction(
+        &self,
+        wallet: &LightWallet,
+        raw_transaction: &RawTransaction,
+    ) -> Result<(), String> {
+        let height = BlockHeight::from_u32(raw_transaction.height as u32);
+        let transaction = Transaction::read(
+            &raw_transaction.data[..],
+            BranchId::for_height(&self.config.chain, height),
+        )
+        .map_err(|e| format!("Error reading transaction: {}", e))?;
+        let block_time = GrpcConnector::get_trees(self.get_server_uri(), raw_transaction.height)
+            .await?
+            .time;
+        TransactionContext::new(
+            &self.config,
+            wallet.wallet_capability(),
+            wallet.transactions(),
+        )
+        .scan_full_tx(
+            &transaction,
+            ConfirmationStatus::Confirmed(height),
+            block_time,
+            None,
+        )
+        .await;
+        Ok(())
+    }
+
     /// start_sync will start synchronizing the blockchain from the wallet's last height. This function will
     /// return immediately after starting the sync.  Use the `do_sync_status` LightClient method to
     /// get the status of the sync
     async fn sync_nth_batch(
         &self,
+        wallets: &[&LightWallet],
         start_block: u64,
         batch_num: usize,
     ) -> Result<SyncResult, String> {
-        // The top of the wallet
-        let last_synced_height = self.wallet.last_synced_height().await;
+        // The top of the wallets
+        let last_synced_height = wallets[0].last_synced_height().await;
 
         debug!(
             "Latest block is {}, wallet block is {}",
@@ -1492,12 +3104,49 @@ impl LightClient {
                 success: true,
                 latest_block: last_synced_height,
                 total_blocks_synced: 0,
+                auto_shield: None,
+                reused_ephemeral_taddrs: vec![],
             });
         }
 
-        let bsync_data = self.bsync_data.clone();
+        let res = self
+            .scan_batch(
+                wallets,
+                start_block,
+                last_synced_height + 1,
+                batch_num,
+                wallets[0].get_blocks().await,
+                None,
+            )
+            .await?;
+
+        debug!("About to run save after syncing {}th batch!", batch_num);
+
+        // #[cfg(not(any(target_os = "ios", target_os = "android")))]
+        self.save_internal_rust().await.unwrap();
 
-        let end_block = last_synced_height + 1;
+        Ok(res)
+    }
+
+    /// Scans the blocks from `end_block` up to `start_block` for the `wallets`, which have
+    /// synced to the same block. Each block is downloaded and trial decrypted once, and what
+    /// is found goes to the wallet whose key found it. A batch above the wallets' last
+    /// synced block is checked for reorgs against `existing_blocks`, and becomes their new
+    /// top. One below it, from a spend-before-sync, only adds what it finds. The nullifiers
+    /// that the blocks reveal are added to `revealed_nullifiers`, if given.
+    async fn scan_batch(
+        &self,
+        wallets: &[&LightWallet],
+        start_block: u64,
+        end_block: u64,
+        batch_num: usize,
+        existing_blocks: Vec<BlockData>,
+        revealed_nullifiers: Option<&mut HashMap<Vec<u8>, (TxId, u64, u32)>>,
+    ) -> Result<SyncResult, String> {
+        let wallet = wallets[0];
+        let extends_tip = start_block > wallet.last_synced_height().await;
+
+        let bsync_data = self.bsync_data.clone();
 
         // Before we start, we need to do a few things
         // 1. Pre-populate the last 100 blocks, in case of reorgs
@@ -1508,9 +3157,9 @@ impl LightClient {
                 start_block,
                 end_block,
                 batch_num,
-                self.wallet.get_blocks().await,
-                self.wallet.verified_tree.read().await.clone(),
-                *self.wallet.wallet_options.read().await,
+                existing_blocks,
+                wallet.verified_tree.read().await.clone(),
+                *wallet.wallet_options.read().await,
             )
             .await;
 
@@ -1531,7 +3180,7 @@ impl LightClient {
             .handle_reorgs_and_populate_block_mangement_data(
                 start_block,
                 end_block,
-                self.wallet.transactions(),
+                wallets.iter().map(|wallet| wallet.transactions()).collect(),
                 reorg_transmitter,
             )
             .await;
@@ -1541,52 +3190,72 @@ impl LightClient {
             grpc_connector
                 .start_full_transaction_fetcher(self.config.chain)
                 .await;
-        // Transparent Transactions Fetcher
-        let (taddr_fetcher_handle, taddr_fetcher_transmitter) =
-            grpc_connector.start_taddr_transaction_fetcher().await;
+        let mut fetcher_handles = vec![full_transaction_fetcher_handle];
+        let mut scan_handles = vec![];
 
-        // Local state necessary for a transaction fetch
-        let transaction_context = TransactionContext::new(
-            &self.config,
-            self.wallet.wallet_capability(),
-            self.wallet.transactions(),
-        );
-        let (
-            fetch_full_transactions_handle,
-            fetch_full_transaction_transmitter,
-            fetch_taddr_transactions_transmitter,
-        ) = crate::blaze::fetch_full_transaction::start(
-            transaction_context,
-            full_transaction_fetcher_transmitter.clone(),
-            bsync_data.clone(),
-        )
-        .await;
+        // Local state necessary for a transaction fetch, for each wallet
+        let transaction_contexts = wallets
+            .iter()
+            .map(|wallet| {
+                TransactionContext::new(
+                    &self.config,
+                    wallet.wallet_capability(),
+                    wallet.transactions(),
+                )
+            })
+            .collect::<Vec<_>>();
+        // Each wallet fetches the transactions of its own transparent addresses, and follows
+        // the spends of its own notes
+        let mut taddr_fetchers = vec![];
+        let mut blocks_done_transmitters = vec![];
+        let mut detected_transactions_transmitters = vec![];
+        for transaction_context in &transaction_contexts {
+            // Transparent Transactions Fetcher
+            let (taddr_fetcher_handle, taddr_fetcher_transmitter) =
+                grpc_connector.start_taddr_transaction_fetcher().await;
+            fetcher_handles.push(taddr_fetcher_handle);
+
+            let (
+                fetch_full_transactions_handle,
+                fetch_full_transaction_transmitter,
+                fetch_taddr_transactions_transmitter,
+            ) = crate::blaze::fetch_full_transaction::start(
+                transaction_context.clone(),
+                full_transaction_fetcher_transmitter.clone(),
+                bsync_data.clone(),
+            )
+            .await;
+            scan_handles.push(fetch_full_transactions_handle);
+            taddr_fetchers.push((
+                transaction_context.key.clone(),
+                taddr_fetcher_transmitter,
+                fetch_taddr_transactions_transmitter,
+            ));
 
-        // The processor to process Transactions detected by the trial decryptions processor
-        let update_notes_processor = UpdateNotes::new(self.wallet.transactions());
-        let (update_notes_handle, blocks_done_transmitter, detected_transactions_transmitter) =
-            update_notes_processor
-                .start(bsync_data.clone(), fetch_full_transaction_transmitter)
-                .await;
+            // The processor to process Transactions detected by the trial decryptions processor
+            let update_notes_processor =
+                UpdateNotes::new(transaction_context.transaction_metadata_set.clone());
+            let (update_notes_handle, blocks_done_transmitter, detected_transactions_transmitter) =
+                update_notes_processor
+                    .start(bsync_data.clone(), fetch_full_transaction_transmitter)
+                    .await;
+            scan_handles.push(update_notes_handle);
+            blocks_done_transmitters.push(blocks_done_transmitter);
+            detected_transactions_transmitters.push(detected_transactions_transmitter);
+        }
 
         // Do Trial decryptions of all the outputs, and pass on the successful ones to the update_notes processor
-        let trial_decryptions_processor = TrialDecryptions::new(
-            Arc::new(self.config.clone()),
-            self.wallet.wallet_capability(),
-            self.wallet.transactions(),
-        );
+        let trial_decryptions_processor =
+            TrialDecryptions::new(Arc::new(self.config.clone()), transaction_contexts);
         let (trial_decrypts_handle, trial_decrypts_transmitter) = trial_decryptions_processor
             .start(
                 bsync_data.clone(),
-                detected_transactions_transmitter,
-                self.wallet
-                    .wallet_options
-                    .read()
-                    .await
-                    .transaction_size_filter,
+                detected_transactions_transmitters,
+                wallet.wallet_options.read().await.transaction_size_filter,
                 full_transaction_fetcher_transmitter,
             )
             .await;
+        fetcher_handles.push(trial_decrypts_handle);
 
         // Fetch Compact blocks and send them to nullifier cache, node-and-witness cache and the trial-decryption processor
         let fetch_compact_blocks = Arc::new(FetchCompactBlocks::new(&self.config));
@@ -1603,33 +3272,40 @@ impl LightClient {
                 )
                 .await
         });
+        scan_handles.push(fetch_compact_blocks_handle);
 
         // We wait first for the nodes to be updated. This is where reorgs will be handled, so all the steps done after this phase will
         // assume that the reorgs are done.
         let Some(earliest_block) = block_and_witness_handle.await.unwrap().unwrap() else {
             return Ok(SyncResult {
                 success: false,
-                latest_block: self.wallet.last_synced_height().await,
+                latest_block: wallet.last_synced_height().await,
                 total_blocks_synced: 0,
+                auto_shield: None,
+                reused_ephemeral_taddrs: vec![],
             });
         };
 
         // 1. Fetch the transparent txns only after reorgs are done.
-        let taddr_transactions_handle = FetchTaddrTransactions::new(
-            self.wallet.wallet_capability(),
-            Arc::new(self.config.clone()),
-        )
-        .start(
-            start_block,
-            earliest_block,
-            taddr_fetcher_transmitter,
-            fetch_taddr_transactions_transmitter,
-            self.config.chain,
-        )
-        .await;
+        for (key, taddr_fetcher_transmitter, fetch_taddr_transactions_transmitter) in taddr_fetchers
+        {
+            let taddr_transactions_handle =
+                FetchTaddrTransactions::new(key, Arc::new(self.config.clone()))
+                    .start(
+                        start_block,
+                        earliest_block,
+                        taddr_fetcher_transmitter,
+                        fetch_taddr_transactions_transmitter,
+                        self.config.chain,
+                    )
+                    .await;
+            scan_handles.push(taddr_transactions_handle);
+        }
 
-        // 2. Notify the notes updater that the blocks are done updating
-        blocks_done_transmitter.send(earliest_block).unwrap();
+        // 2. Notify the notes updaters that the blocks are done updating
+        for blocks_done_transmitter in blocks_done_transmitters {
+            blocks_done_transmitter.send(earliest_block).unwrap();
+        }
 
         // 3. Verify all the downloaded data
         let block_data = bsync_data.clone();
@@ -1638,26 +3314,17 @@ impl LightClient {
 
         // Await all the futures
         let r1 = tokio::spawn(async move {
-            join_all(vec![
-                trial_decrypts_handle,
-                full_transaction_fetcher_handle,
-                taddr_fetcher_handle,
-            ])
-            .await
-            .into_iter()
-            .try_for_each(|r| r.map_err(|e| format!("{}", e)))
+            join_all(fetcher_handles)
+                .await
+                .into_iter()
+                .try_for_each(|r| r.map_err(|e| format!("{}", e)))
         });
+        scan_handles.push(r1);
 
-        join_all(vec![
-            update_notes_handle,
-            taddr_transactions_handle,
-            fetch_compact_blocks_handle,
-            fetch_full_transactions_handle,
-            r1,
-        ])
-        .await
-        .into_iter()
-        .try_for_each(|r| r.map_err(|e| format!("{}", e))?)?;
+        join_all(scan_handles)
+            .await
+            .into_iter()
+            .try_for_each(|r| r.map_err(|e| format!("{}", e))?)?;
 
         let verify_handle =
             tokio::spawn(async move { block_data.read().await.block_data.verify_trees().await });
@@ -1677,13 +3344,20 @@ impl LightClient {
         debug!("Batch: {batch_num} synced, doing post-processing");
 
         let blaze_sync_data = bsync_data.read().await;
+        if let Some(revealed_nullifiers) = revealed_nullifiers {
+            revealed_nullifiers.extend(blaze_sync_data.block_data.revealed_nullifiers().await);
+        }
         // Post sync, we have to do a bunch of stuff
         // 1. Get the last 100 blocks and store it into the wallet, needed for future re-orgs
-        let blocks = blaze_sync_data
-            .block_data
-            .drain_existingblocks_into_blocks_with_truncation(MAX_REORG)
-            .await;
-        self.wallet.set_blocks(blocks).await;
+        if extends_tip {
+            let blocks = blaze_sync_data
+                .block_data
+                .drain_existingblocks_into_blocks_with_truncation(MAX_REORG)
+                .await;
+            for wallet in wallets {
+                wallet.set_blocks(blocks.clone()).await;
+            }
+        }
 
         // 2. If sync was successful, also try to get historical prices
         // self.update_historical_prices().await;
@@ -1691,28 +3365,37 @@ impl LightClient {
 
         // 3. Mark the sync finished, which will clear the nullifier cache etc...
         blaze_sync_data.finish().await;
+        blaze_sync_data
+            .block_data
+            .sync_events
+            .send(SyncEvent::BatchFinished {
+                batch_num,
+                blocks_synced: start_block - end_block + 1,
+                eta: blaze_sync_data.block_data.sync_events.eta(),
+            });
 
-        // 5. Remove expired mempool transactions, if any
-        self.wallet
-            .transactions()
-            .write()
-            .await
-            .clear_expired_mempool(start_block);
+        if extends_tip {
+            for wallet in wallets {
+                // 5. Remove expired mempool transactions, if any
+                wallet
+                    .transactions()
+                    .write()
+                    .await
+                    .clear_expired_mempool(start_block);
 
-        // 6. Set the highest verified tree
-        if highest_tree.is_some() {
-            *self.wallet.verified_tree.write().await = highest_tree;
+                // 6. Set the highest verified tree
+                if highest_tree.is_some() {
+                    *wallet.verified_tree.write().await = highest_tree.clone();
+                }
+            }
         }
 
-        debug!("About to run save after syncing {}th batch!", batch_num);
-
-        // #[cfg(not(any(target_os = "ios", target_os = "android")))]
-        self.save_internal_rust().await.unwrap();
-
         Ok(SyncResult {
             success: true,
             latest_block: start_block,
             total_blocks_synced: start_block - end_block + 1,
+            auto_shield: None,
+            reused_ephemeral_taddrs: vec![],
         })
     }
 
@@ -2069,7 +3752,7 @@ impl LightClient {
                                 "value"              => utxo.value,
                                 "scriptkey"          => hex::encode(utxo.script.clone()),
                                 "is_change"          => false, // TODO: Identify notes as change if we send change to our own taddrs
-                                "address"            => self.wallet.wallet_capability().get_ua_from_contained_transparent_receiver(&taddr).map(|ua| ua.encode(&self.config.chain)),
+                                "address"            => self.wallet.wallet_capability().get_ua_from_contained_transparent_receiver(&taddr).map(|ua| ua.encode(&self.config.chain)).unwrap_or_else(|| utxo.address.clone()),
                                 "spent_at_height"    => utxo.spent_at_height,
                                 "spent"              => utxo.spent.map(|spent_transaction_id| format!("{}", spent_transaction_id)),
                                 "unconfirmed_spent"  => utxo.unconfirmed_spent.map(|(spent_transaction_id, _)| format!("{}", spent_transaction_id)),
@@ -2296,6 +3979,27 @@ mod tests {
             );
         });
     }
+
+    #[test]
+    fn confirm_needs_a_proposal() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config = ZingoConfig::build(ChainType::FakeMainnet)
+            .set_wallet_dir(temp_dir.path().to_path_buf())
+            .create();
+        let lc = LightClient::create_from_wallet_base(
+            WalletBase::MnemonicPhrase(CHIMNEY_BETTER_SEED.to_string()),
+            &config,
+            0,
+            false,
+        )
+        .unwrap();
+        Runtime::new().unwrap().block_on(async move {
+            assert_eq!(
+                lc.confirm_proposal().await,
+                Err("No proposal to confirm. Create one with propose first.".to_string())
+            );
+        });
+    }
 }
 
 #[cfg(feature = "lightclient-deprecated")]
@@ -86,6 +86,8 @@ impl LightClient {
                     // Is this address ever different than the address in the containing struct
                     // this is the full UA.
                     "address" => om.recipient_ua.clone().unwrap_or(om.to_address.clone()),
+                    // The receiver of the address that was paid
+                    "receiver" => om.to_address.clone(),
                     "value"   => om.value,
                     "memo"    => LightWallet::memo_str(Some(om.memo.clone()))
                 }
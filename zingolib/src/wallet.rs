@@ -15,7 +15,6 @@ use orchard::tree::MerkleHashOrchard;
 use rand::rngs::OsRng;
 use rand::Rng;
 use sapling_crypto::note_encryption::SaplingDomain;
-use sapling_crypto::prover::{OutputProver, SpendProver};
 use sapling_crypto::SaplingIvk;
 use shardtree::error::{QueryError, ShardTreeError};
 use shardtree::store::memory::MemoryShardStore;
@@ -24,7 +23,11 @@ use std::convert::Infallible;
 use std::{
     cmp,
     io::{self, Error, ErrorKind, Read, Write},
-    sync::{atomic::AtomicU64, mpsc::channel, Arc},
+    sync::{
+        atomic::{AtomicBool, AtomicU64},
+        mpsc::channel,
+        Arc,
+    },
     time::SystemTime,
 };
 use tokio::sync::RwLock;
@@ -36,24 +39,33 @@ use zcash_primitives::memo::MemoBytes;
 use zcash_primitives::transaction::builder::{BuildResult, Progress};
 use zcash_primitives::transaction::components::amount::NonNegativeAmount;
 use zcash_primitives::transaction::fees::fixed::FeeRule as FixedFeeRule;
+use zcash_primitives::transaction::fees::zip317::FeeRule as Zip317FeeRule;
 use zcash_primitives::transaction::{self, Transaction};
 use zcash_primitives::{
-    consensus::BlockHeight,
+    consensus::{BlockHeight, BranchId},
     legacy::Script,
     memo::Memo,
     transaction::{
         builder::Builder,
         components::{Amount, OutPoint, TxOut},
-        fees::zip317::MINIMUM_FEE,
+        fees::zip317::{MARGINAL_FEE, MINIMUM_FEE},
     },
 };
 use zingo_memo::create_wallet_internal_memo_version_0;
 use zingo_status::confirmation_status::ConfirmationStatus;
 
+use self::auto_shield::AutoShieldPolicy;
 use self::data::{SpendableOrchardNote, WitnessTrees, COMMITMENT_TREE_LEVELS, MAX_SHARD_LEVEL};
+use self::keys::address_from_pubkeyhash;
 use self::keys::unified::{Capability, WalletCapability};
+use self::note_selection::NoteSelectionStrategy;
+use self::pending::{PendingTransaction, RebroadcastPolicy};
+use self::proposal::{ChangeReceiver, Dust, PrivacyPolicy, Proposal};
+use self::prover::SaplingProver;
+use self::receivers::ReceiverPreference;
 use self::traits::Recipient;
 use self::traits::{DomainWalletExt, SpendableNote};
+use self::unsigned::{UnsignedOrchardSpend, UnsignedSaplingSpend, UnsignedTransaction};
 use self::utils::get_price;
 use self::{
     data::{BlockData, WalletZecPriceInfo},
@@ -62,13 +74,23 @@ use self::{
 };
 use zingoconfig::ZingoConfig;
 
+pub mod auto_shield;
+pub mod batch;
 pub mod data;
+pub mod fees;
 pub mod keys;
 pub(crate) mod message;
+pub mod note_selection;
 pub mod notes;
+pub mod payment_uri;
+pub mod pending;
+pub mod proposal;
+pub mod prover;
+pub mod receivers;
 pub mod traits;
 pub mod transaction_record;
 pub(crate) mod transactions;
+pub mod unsigned;
 pub mod utils;
 
 pub fn now() -> u64 {
@@ -126,10 +148,58 @@ pub enum MemoDownloadOption {
     AllMemos,
 }
 
+/// Where the wallet returns the change of its own transactions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeTarget {
+    /// The orchard receiver of the wallet's first address, or its sapling receiver if it
+    /// has no orchard receiver.
+    DefaultAddress,
+    /// The wallet's address at this index, preferring its orchard receiver.
+    AddressIndex(u32),
+    /// The sapling receiver of the wallet's first address that has one.
+    SaplingPool,
+}
+
+impl std::str::FromStr for ChangeTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(ChangeTarget::DefaultAddress),
+            "sapling" => Ok(ChangeTarget::SaplingPool),
+            index => index.parse().map(ChangeTarget::AddressIndex).map_err(|_| {
+                format!("Expected 'default', 'sapling' or an address index, found '{index}'")
+            }),
+        }
+    }
+}
+
+impl std::fmt::Display for ChangeTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChangeTarget::DefaultAddress => write!(f, "default"),
+            ChangeTarget::AddressIndex(index) => write!(f, "{index}"),
+            ChangeTarget::SaplingPool => write!(f, "sapling"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct WalletOptions {
     pub(crate) download_memos: MemoDownloadOption,
     pub transaction_size_filter: Option<u32>,
+    pub change_target: ChangeTarget,
+    pub note_selection: NoteSelectionStrategy,
+    pub rebroadcast: RebroadcastPolicy,
+    pub receiver_preference: ReceiverPreference,
+    /// The confirmations after which the funds of each single-use transparent address are
+    /// shielded on their own. `None` leaves them for the user to shield.
+    pub ephemeral_shield_depth: Option<u32>,
+    /// Shields transparent funds after each sync. `None` leaves them for the user to shield.
+    pub auto_shield: Option<AutoShieldPolicy>,
+    /// The synced height of the last automatic shielding, which the policy's spacing is
+    /// counted from
+    pub last_auto_shield_height: Option<u64>,
 }
 
 pub const MAX_TRANSACTION_SIZE_DEFAULT: u32 = 500;
@@ -139,13 +209,20 @@ impl Default for WalletOptions {
         WalletOptions {
             download_memos: MemoDownloadOption::WalletMemos,
             transaction_size_filter: Some(MAX_TRANSACTION_SIZE_DEFAULT),
+            change_target: ChangeTarget::DefaultAddress,
+            note_selection: NoteSelectionStrategy::default(),
+            rebroadcast: RebroadcastPolicy::default(),
+            receiver_preference: ReceiverPreference::default(),
+            ephemeral_shield_depth: None,
+            auto_shield: None,
+            last_auto_shield_height: None,
         }
     }
 }
 
 impl WalletOptions {
     pub const fn serialized_version() -> u64 {
-        2
+        9
     }
 
     pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
@@ -164,14 +241,94 @@ impl WalletOptions {
         };
 
         let transaction_size_filter = if external_version > 1 {
-            Optional::read(reader, |mut r| r.read_u32::<LittleEndian>())?
+            Optional::read(&mut reader, |r| r.read_u32::<LittleEndian>())?
         } else {
             Some(500)
         };
 
+        let change_target = if external_version > 2 {
+            match reader.read_u8()? {
+                0 => ChangeTarget::DefaultAddress,
+                1 => ChangeTarget::AddressIndex(reader.read_u32::<LittleEndian>()?),
+                2 => ChangeTarget::SaplingPool,
+                v => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Bad change target {}", v),
+                    ));
+                }
+            }
+        } else {
+            ChangeTarget::DefaultAddress
+        };
+
+        let note_selection = if external_version > 3 {
+            let v = reader.read_u8()?;
+            NoteSelectionStrategy::from_u8(v).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Bad note selection strategy {}", v),
+                )
+            })?
+        } else {
+            NoteSelectionStrategy::default()
+        };
+
+        let rebroadcast = if external_version > 4 {
+            match reader.read_u8()? {
+                0 => RebroadcastPolicy::Never,
+                1 => RebroadcastPolicy::EveryBlocks(reader.read_u32::<LittleEndian>()?),
+                v => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Bad rebroadcast policy {}", v),
+                    ));
+                }
+            }
+        } else {
+            RebroadcastPolicy::default()
+        };
+
+        let receiver_preference = if external_version > 5 {
+            let v = reader.read_u8()?;
+            ReceiverPreference::from_u8(v).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Bad receiver preference {}", v),
+                )
+            })?
+        } else {
+            ReceiverPreference::default()
+        };
+
+        let ephemeral_shield_depth = if external_version > 6 {
+            Optional::read(&mut reader, |r| r.read_u32::<LittleEndian>())?
+        } else {
+            None
+        };
+
+        let auto_shield = if external_version > 7 {
+            Optional::read(&mut reader, |r| AutoShieldPolicy::read(r))?
+        } else {
+            None
+        };
+
+        let last_auto_shield_height = if external_version > 8 {
+            Optional::read(&mut reader, |r| r.read_u64::<LittleEndian>())?
+        } else {
+            None
+        };
+
         Ok(Self {
             download_memos,
             transaction_size_filter,
+            change_target,
+            note_selection,
+            rebroadcast,
+            receiver_preference,
+            ephemeral_shield_depth,
+            auto_shield,
+            last_auto_shield_height,
         })
     }
 
@@ -180,8 +337,32 @@ impl WalletOptions {
         writer.write_u64::<LittleEndian>(Self::serialized_version())?;
 
         writer.write_u8(self.download_memos as u8)?;
-        Optional::write(writer, self.transaction_size_filter, |mut w, filter| {
+        Optional::write(&mut writer, self.transaction_size_filter, |w, filter| {
             w.write_u32::<LittleEndian>(filter)
+        })?;
+        match self.change_target {
+            ChangeTarget::DefaultAddress => writer.write_u8(0)?,
+            ChangeTarget::AddressIndex(index) => {
+                writer.write_u8(1)?;
+                writer.write_u32::<LittleEndian>(index)?
+            }
+            ChangeTarget::SaplingPool => writer.write_u8(2)?,
+        }
+        writer.write_u8(self.note_selection.as_u8())?;
+        match self.rebroadcast {
+            RebroadcastPolicy::Never => writer.write_u8(0)?,
+            RebroadcastPolicy::EveryBlocks(blocks) => {
+                writer.write_u8(1)?;
+                writer.write_u32::<LittleEndian>(blocks)?
+            }
+        }
+        writer.write_u8(self.receiver_preference.as_u8())?;
+        Optional::write(&mut writer, self.ephemeral_shield_depth, |w, depth| {
+            w.write_u32::<LittleEndian>(depth)
+        })?;
+        Optional::write(&mut writer, self.auto_shield, |w, policy| policy.write(w))?;
+        Optional::write(&mut writer, self.last_auto_shield_height, |w, height| {
+            w.write_u64::<LittleEndian>(height)
         })
     }
 }
@@ -238,11 +419,70 @@ pub struct LightWallet {
     // Local state needed to submit [compact]block-requests to the proxy
     // and interpret responses
     pub transaction_context: TransactionContext,
+
+    /// This wallet's own sends that were broadcast but may not be mined yet
+    pub pending_transactions: Arc<RwLock<Vec<PendingTransaction>>>,
+
+    /// The other ZIP-32 accounts of the seed, each kept as a wallet of its own, with its
+    /// own capability, addresses and transactions
+    pub accounts: Arc<RwLock<Vec<Arc<LightWallet>>>>,
+
+    /// View-only wallets of third parties' unified full viewing keys, each synced from its
+    /// own birthday
+    pub imported_keys: Arc<RwLock<Vec<Arc<LightWallet>>>>,
+
+    /// Transparent addresses handed out to be paid once, such as for an exchange withdrawal
+    pub ephemeral_taddrs: Arc<RwLock<Vec<String>>>,
+
+    /// Ranges of blocks, both ends included, below the last synced block that a
+    /// spend-before-sync has yet to scan
+    pub unscanned_ranges: Arc<RwLock<Vec<(u64, u64)>>>,
+
+    /// The heights that completed each Sapling and each Orchard shard, for the subtree roots
+    /// the witness trees hold from the birthday on. A spend-before-sync fetches the roots
+    /// after these.
+    pub subtree_root_ends: Arc<RwLock<[Vec<u64>; 2]>>,
+
+    /// Whether sync has looked for used transparent addresses past the wallet's own since
+    /// it was loaded
+    pub(crate) transparent_addresses_discovered: AtomicBool,
 }
 
 use crate::wallet::traits::{Diversifiable as _, ReadableWriteable};
-type Receivers = Vec<(address::Address, NonNegativeAmount, Option<MemoBytes>)>;
+pub type Receivers = Vec<(address::Address, NonNegativeAmount, Option<MemoBytes>)>;
 type TxBuilder<'a> = Builder<'a, zingoconfig::ChainType, ()>;
+
+/// The wallet that signs a proposed transaction, which decides the notes it may spend.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Signer {
+    /// This wallet, so only notes it holds the spend key of are selected
+    Wallet,
+    /// Another wallet, handed an [`UnsignedTransaction`], so the notes this wallet only
+    /// views are selected too
+    Offline,
+}
+
+/// The fee rule a proposal is built with, which pays exactly its `fee`, once that is checked
+/// to pay the ZIP-317 fee of `tx_builder`.
+fn proposal_fee_rule(
+    tx_builder: &TxBuilder<'_>,
+    fee: u64,
+    change: Option<u64>,
+) -> Result<FixedFeeRule, String> {
+    let required_fee = tx_builder
+        .get_fee(&Zip317FeeRule::standard())
+        .map(u64::from)
+        .map_err(|e| format!("Error computing ZIP-317 fee: {:?}", e))?;
+    if !fees::pays_required_fee(fee, change, required_fee) {
+        return Err(format!(
+            "The transaction pays a fee of {} zats, but its inputs and outputs need {} zats",
+            fee, required_fee
+        ));
+    }
+    Ok(FixedFeeRule::non_standard(
+        NonNegativeAmount::from_u64(fee).map_err(|_| format!("Invalid fee of {} zats", fee))?,
+    ))
+}
 impl LightWallet {
     fn get_legacy_frontiers(
         trees: TreeState,
@@ -290,44 +530,41 @@ impl LightWallet {
     fn add_notes_to_total<D: DomainWalletExt>(
         candidates: Vec<D::SpendableNoteAT>,
         target_amount: Amount,
+        strategy: NoteSelectionStrategy,
+        free_spends: usize,
     ) -> (Vec<D::SpendableNoteAT>, Amount)
     where
         D::Note: PartialEq + Clone,
         D::Recipient: traits::Recipient,
     {
-        let mut notes = Vec::new();
+        let values = candidates
+            .iter()
+            .map(|note| D::WalletNote::value_from_note(note.note()))
+            .collect::<Vec<_>>();
+        let target = u64::try_from(target_amount).unwrap_or(0);
+        let mut selected = strategy.select(&values, target, free_spends);
+        selected.sort_unstable();
+
         let mut running_total = Amount::zero();
-        for note in candidates {
-            if running_total >= target_amount {
-                break;
+        let mut notes = Vec::new();
+        for (index, note) in candidates.into_iter().enumerate() {
+            if selected.binary_search(&index).is_ok() {
+                running_total +=
+                    Amount::from_u64(values[index]).expect("Note value overflow error");
+                notes.push(note);
             }
-            running_total += Amount::from_u64(D::WalletNote::value_from_note(note.note()))
-                .expect("Note value overflow error");
-            notes.push(note);
         }
 
         (notes, running_total)
     }
 
-    // This function will likely be used if/when we reimplement key import
-    #[allow(dead_code)]
-    fn adjust_wallet_birthday(&self, new_birthday: u64) {
-        let mut wallet_birthday = self.birthday.load(std::sync::atomic::Ordering::SeqCst);
-        if new_birthday < wallet_birthday {
-            wallet_birthday = cmp::max(
-                new_birthday,
-                self.transaction_context.config.sapling_activation_height(),
-            );
-            self.birthday
-                .store(wallet_birthday, std::sync::atomic::Ordering::SeqCst);
-        }
-    }
-
     /// Clears all the downloaded blocks and resets the state back to the initial block.
     /// After this, the wallet's initial state will need to be set
     /// and the wallet will need to be rescanned
     pub async fn clear_all(&self) {
         self.blocks.write().await.clear();
+        self.unscanned_ranges.write().await.clear();
+        *self.subtree_root_ends.write().await = [vec![], vec![]];
         self.transaction_context
             .transaction_metadata_set
             .write()
@@ -348,14 +585,30 @@ impl LightWallet {
     }
 
     async fn get_all_domain_specific_notes<D>(&self) -> Vec<D::SpendableNoteAT>
+    where
+        D: DomainWalletExt,
+        <D as Domain>::Recipient: traits::Recipient,
+        <D as Domain>::Note: PartialEq + Clone,
+    {
+        self.get_domain_specific_candidates::<D>(Signer::Wallet)
+            .await
+    }
+
+    /// The notes `signer` can spend, largest first
+    async fn get_domain_specific_candidates<D>(&self, signer: Signer) -> Vec<D::SpendableNoteAT>
     where
         D: DomainWalletExt,
         <D as Domain>::Recipient: traits::Recipient,
         <D as Domain>::Note: PartialEq + Clone,
     {
         let wc = self.wallet_capability();
+        // While a spend-before-sync is filling in history, notes in shards that are not
+        // fully scanned yet have no witness
+        let check_witnesses = !self.unscanned_ranges.read().await.is_empty();
+        let anchor_depth = self.transaction_context.config.reorg_buffer_offset as usize;
         let tranmds_lth = self.transactions();
         let transaction_metadata_set = tranmds_lth.read().await;
+        let witness_tree = D::transaction_metadata_set_to_shardtree(&transaction_metadata_set);
         let mut candidate_notes = transaction_metadata_set
             .current
             .iter()
@@ -364,11 +617,24 @@ impl LightWallet {
                     .iter()
                     .map(move |note| (*transaction_id, note))
             })
+            .filter(|(_, note)| {
+                match (check_witnesses, witness_tree, note.witnessed_position()) {
+                    (true, Some(tree), Some(position)) => tree
+                        .witness_at_checkpoint_depth(*position, anchor_depth)
+                        .is_ok(),
+                    _ => true,
+                }
+            })
             .filter_map(
                 |(transaction_id, note): (transaction::TxId, &D::WalletNote)| -> Option <D::SpendableNoteAT> {
-                        // Get the spending key for the selected fvk, if we have it
-                        let extsk = D::wc_to_sk(&wc);
-                        SpendableNote::from(transaction_id, note, extsk.ok().as_ref())
+                    match signer {
+                        Signer::Wallet => {
+                            // Get the spending key for the selected fvk, if we have it
+                            let extsk = D::wc_to_sk(&wc);
+                            SpendableNote::from(transaction_id, note, extsk.ok().as_ref())
+                        }
+                        Signer::Offline => SpendableNote::from_viewed(transaction_id, note),
+                    }
                 }
             )
             .collect::<Vec<D::SpendableNoteAT>>();
@@ -625,7 +891,7 @@ impl LightWallet {
                 format!("could not create initial address: {e}"),
             ));
         };
-        let transaction_metadata_set = if wc.can_spend_from_all_pools() {
+        let transaction_metadata_set = if wc.get_trees_witness_trees().is_some() {
             Arc::new(RwLock::new(TransactionMetadataSet::new_with_witness_trees()))
         } else {
             Arc::new(RwLock::new(TransactionMetadataSet::new_treeless()))
@@ -641,7 +907,234 @@ impl LightWallet {
             send_progress: Arc::new(RwLock::new(SendProgress::new(0))),
             price: Arc::new(RwLock::new(WalletZecPriceInfo::default())),
             transaction_context,
+            pending_transactions: Arc::new(RwLock::new(vec![])),
+            accounts: Arc::new(RwLock::new(vec![])),
+            imported_keys: Arc::new(RwLock::new(vec![])),
+            ephemeral_taddrs: Arc::new(RwLock::new(vec![])),
+            unscanned_ranges: Arc::new(RwLock::new(vec![])),
+            subtree_root_ends: Arc::new(RwLock::new([vec![], vec![]])),
+            transparent_addresses_discovered: AtomicBool::new(false),
+        })
+    }
+
+    /// The ZIP-32 account index of this wallet, if it was derived from a seed.
+    pub fn account_index(&self) -> Option<u32> {
+        self.mnemonic.as_ref().map(|(_, index)| *index)
+    }
+
+    pub async fn account(&self, index: u32) -> Option<Arc<LightWallet>> {
+        self.accounts
+            .read()
+            .await
+            .iter()
+            .find(|account| account.account_index() == Some(index))
+            .cloned()
+    }
+
+    /// Derives the seed's next unused account, born at this wallet's birthday. The new
+    /// account has no blocks yet, so it needs its initial block before it syncs.
+    pub async fn add_account(&self) -> Result<Arc<LightWallet>, String> {
+        let (mnemonic, primary_index) = self
+            .mnemonic
+            .clone()
+            .ok_or("Only a wallet with a seed phrase can derive accounts".to_string())?;
+        let mut accounts = self.accounts.write().await;
+        let index = accounts
+            .iter()
+            .filter_map(|account| account.account_index())
+            .fold(primary_index, u32::max)
+            + 1;
+        // ZIP-32 accounts are hardened children
+        if index >= 1 << 31 {
+            return Err("The seed has no more accounts".to_string());
+        }
+        let account = Arc::new(
+            LightWallet::new(
+                self.transaction_context.config.clone(),
+                WalletBase::MnemonicAndIndex(mnemonic, index),
+                self.get_birthday().await,
+            )
+            .map_err(|e| format!("Could not derive account {}: {}", index, e))?,
+        );
+        accounts.push(account.clone());
+        Ok(account)
+    }
+
+    /// The encoded unified full viewing key of this wallet, which tags the transactions of
+    /// an imported key. `None` unless the wallet views all three pools.
+    pub fn encoded_ufvk(&self) -> Option<String> {
+        use zcash_address::unified::Encoding as _;
+        let capability = self.wallet_capability();
+        if !(capability.orchard.can_view()
+            && capability.sapling.can_view()
+            && capability.transparent.can_view())
+        {
+            return None;
+        }
+        capability.ufvk().ok().map(|ufvk| {
+            ufvk.encode(
+                &self
+                    .transaction_context
+                    .config
+                    .chain
+                    .to_zcash_address_network(),
+            )
+        })
+    }
+
+    /// Adds a view-only wallet of a third party's UFVK, born at `birthday`. Like a new
+    /// account, it needs its initial block before it syncs.
+    pub async fn import_ufvk(
+        &self,
+        ufvk: String,
+        birthday: u64,
+    ) -> Result<Arc<LightWallet>, String> {
+        let birthday = cmp::max(
+            birthday,
+            self.transaction_context.config.sapling_activation_height(),
+        );
+        let imported = LightWallet::new(
+            self.transaction_context.config.clone(),
+            WalletBase::Ufvk(ufvk),
+            birthday,
+        )
+        .map_err(|e| e.to_string())?;
+        let imported_capability = imported.wallet_capability();
+        let mut imported_keys = self.imported_keys.write().await;
+        let accounts = self.accounts.read().await;
+        for viewed in std::iter::once(self)
+            .chain(accounts.iter().map(|account| &**account))
+            .chain(imported_keys.iter().map(|other| &**other))
+        {
+            if let Some(pool) = imported_capability.shared_viewing_key(&viewed.wallet_capability())
+            {
+                return Err(format!(
+                    "This wallet already views the {:?} key of that UFVK",
+                    pool
+                ));
+            }
+        }
+        let imported = Arc::new(imported);
+        imported_keys.push(imported.clone());
+        Ok(imported)
+    }
+
+    /// Derives a transparent address from a fresh transparent child key, without a unified
+    /// address around it. An ephemeral one is remembered as single-use.
+    pub async fn new_taddr(&self, ephemeral: bool) -> Result<String, String> {
+        let wc = self.wallet_capability();
+        if !wc.transparent.can_view() {
+            return Err("This wallet has no transparent key".to_string());
+        }
+        let taddr = address_from_pubkeyhash(
+            &self.transaction_context.config,
+            wc.new_transparent_address()?,
+        );
+        if ephemeral {
+            self.ephemeral_taddrs.write().await.push(taddr.clone());
+        }
+        Ok(taddr)
+    }
+
+    /// Each ephemeral address with the number of transactions that paid it. More than one
+    /// links those payers together.
+    pub async fn ephemeral_receipts(&self) -> Vec<(String, usize)> {
+        let transactions = self.transactions();
+        let transactions = transactions.read().await;
+        self.ephemeral_taddrs
+            .read()
+            .await
+            .iter()
+            .map(|taddr| {
+                let receipts = transactions
+                    .current
+                    .values()
+                    .filter(|record| {
+                        !record.status.is_expired()
+                            && record
+                                .transparent_notes
+                                .iter()
+                                .any(|utxo| utxo.address == *taddr)
+                    })
+                    .count();
+                (taddr.clone(), receipts)
+            })
+            .collect()
+    }
+
+    /// Proposes to shield the utxos of `taddr` that have `min_confirmations`, alone, into
+    /// the wallet's first address. `None` when there are none, or they wouldn't cover the
+    /// fee.
+    pub async fn propose_address_shielding(
+        &self,
+        taddr: &str,
+        min_confirmations: u32,
+        submission_height: BlockHeight,
+    ) -> Result<Option<Proposal>, String> {
+        let utxos = self
+            .confirmed_utxos(min_confirmations, |address| address == taddr)
+            .await;
+        let destination = self
+            .wallet_capability()
+            .addresses()
+            .iter()
+            .next()
+            .cloned()
+            .ok_or("The wallet has no address to shield to".to_string())?;
+        if utxos.is_empty() {
+            return Ok(None);
+        }
+        Ok(self
+            .propose_shielding(
+                utxos,
+                false,
+                address::Address::Unified(destination),
+                submission_height,
+            )
+            .await?
+            .ok())
+    }
+
+    /// The utxos an automatic shielding sweeps: the mined ones of every address but the
+    /// single-use ones, which are shielded on their own.
+    pub async fn auto_shield_utxos(&self) -> Vec<notes::TransparentNote> {
+        let ephemeral_taddrs = self.ephemeral_taddrs.read().await.clone();
+        self.confirmed_utxos(1, |address| {
+            !ephemeral_taddrs.iter().any(|taddr| taddr == address)
         })
+        .await
+    }
+
+    /// The unspent utxos with `min_confirmations` paid to the addresses `include` picks.
+    pub(crate) async fn confirmed_utxos(
+        &self,
+        min_confirmations: u32,
+        include: impl Fn(&str) -> bool,
+    ) -> Vec<notes::TransparentNote> {
+        let confirmed_by = BlockHeight::from_u32(
+            (self.last_synced_height().await as u32 + 1)
+                .saturating_sub(cmp::max(min_confirmations, 1)),
+        );
+        self.transactions()
+            .read()
+            .await
+            .current
+            .values()
+            .filter(|record| record.status.is_confirmed_before_or_at(&confirmed_by))
+            .flat_map(|record| record.transparent_notes.iter())
+            .filter(|utxo| {
+                include(&utxo.address) && utxo.spent.is_none() && utxo.unconfirmed_spent.is_none()
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// The seed's other accounts, then the imported keys: every wallet synced along with
+    /// this one.
+    pub async fn other_wallets(&self) -> Vec<Arc<LightWallet>> {
+        let mut wallets = self.accounts.read().await.clone();
+        wallets.extend(self.imported_keys.read().await.iter().cloned());
+        wallets
     }
 
     pub(crate) fn note_address<D: DomainWalletExt>(
@@ -808,6 +1301,69 @@ impl LightWallet {
             None
         };
 
+        let pending_transactions = if external_version >= 29 {
+            Vector::read(&mut reader, |r| PendingTransaction::read(r))?
+        } else {
+            vec![]
+        };
+
+        let mut accounts = vec![];
+        if external_version >= 34 {
+            for (index, account_bytes) in Vector::read(&mut reader, |r| {
+                Ok((
+                    r.read_u32::<LittleEndian>()?,
+                    Vector::read(r, |r| r.read_u8())?,
+                ))
+            })? {
+                let mut account =
+                    Box::pin(Self::read_internal(account_bytes.as_slice(), config)).await?;
+                let (seed, _) = mnemonic.as_ref().ok_or(Error::new(
+                    ErrorKind::InvalidData,
+                    "Accounts of a wallet without a seed",
+                ))?;
+                account.mnemonic = Some((seed.clone(), index));
+                accounts.push(Arc::new(account));
+            }
+        } else if external_version >= 30 {
+            for account_bytes in Vector::read(&mut reader, |r| Vector::read(r, |r| r.read_u8()))? {
+                accounts.push(Arc::new(
+                    Box::pin(Self::read_internal(account_bytes.as_slice(), config)).await?,
+                ));
+            }
+        }
+
+        let mut imported_keys = vec![];
+        if external_version >= 31 {
+            for key_bytes in Vector::read(&mut reader, |r| Vector::read(r, |r| r.read_u8()))? {
+                imported_keys.push(Arc::new(
+                    Box::pin(Self::read_internal(key_bytes.as_slice(), config)).await?,
+                ));
+            }
+        }
+
+        let ephemeral_taddrs = if external_version >= 32 {
+            Vector::read(&mut reader, |r| utils::read_string(r))?
+        } else {
+            vec![]
+        };
+
+        let unscanned_ranges = if external_version >= 33 {
+            Vector::read(&mut reader, |r| {
+                Ok((r.read_u64::<LittleEndian>()?, r.read_u64::<LittleEndian>()?))
+            })?
+        } else {
+            vec![]
+        };
+
+        let subtree_root_ends = if external_version >= 35 {
+            [
+                Vector::read(&mut reader, |r| r.read_u64::<LittleEndian>())?,
+                Vector::read(&mut reader, |r| r.read_u64::<LittleEndian>())?,
+            ]
+        } else {
+            [vec![], vec![]]
+        };
+
         let lw = Self {
             blocks: Arc::new(RwLock::new(blocks)),
             mnemonic,
@@ -817,6 +1373,13 @@ impl LightWallet {
             send_progress: Arc::new(RwLock::new(SendProgress::new(0))),
             price: Arc::new(RwLock::new(price)),
             transaction_context,
+            pending_transactions: Arc::new(RwLock::new(pending_transactions)),
+            accounts: Arc::new(RwLock::new(accounts)),
+            imported_keys: Arc::new(RwLock::new(imported_keys)),
+            ephemeral_taddrs: Arc::new(RwLock::new(ephemeral_taddrs)),
+            unscanned_ranges: Arc::new(RwLock::new(unscanned_ranges)),
+            subtree_root_ends: Arc::new(RwLock::new(subtree_root_ends)),
+            transparent_addresses_discovered: AtomicBool::new(false),
         };
 
         Ok(lw)
@@ -835,6 +1398,9 @@ impl LightWallet {
         &self,
         target_amount: Amount,
         policy: &NoteSelectionPolicy,
+        strategy: NoteSelectionStrategy,
+        outputs: &fees::TransactionShape,
+        signer: Signer,
     ) -> Result<
         (
             Vec<SpendableOrchardNote>,
@@ -871,32 +1437,30 @@ impl LightWallet {
                 }
                 Pool::Sapling => {
                     let sapling_candidates = self
-                        .get_all_domain_specific_notes::<SaplingDomain>()
-                        .await
-                        .into_iter()
-                        .filter(|note| note.spend_key().is_some())
-                        .collect();
+                        .get_domain_specific_candidates::<SaplingDomain>(signer)
+                        .await;
                     (sapling_notes, sapling_value_selected) = Self::add_notes_to_total::<
                         SaplingDomain,
                     >(
                         sapling_candidates,
                         (target_amount - orchard_value_selected - all_transparent_value_in_wallet)
                             .unwrap(),
+                        strategy,
+                        outputs.sapling_outputs.max(2),
                     );
                 }
                 Pool::Orchard => {
                     let orchard_candidates = self
-                        .get_all_domain_specific_notes::<OrchardDomain>()
-                        .await
-                        .into_iter()
-                        .filter(|note| note.spend_key().is_some())
-                        .collect();
+                        .get_domain_specific_candidates::<OrchardDomain>(signer)
+                        .await;
                     (orchard_notes, orchard_value_selected) = Self::add_notes_to_total::<
                         OrchardDomain,
                     >(
                         orchard_candidates,
                         (target_amount - all_transparent_value_in_wallet - sapling_value_selected)
                             .unwrap(),
+                        strategy,
+                        outputs.orchard_outputs.max(2),
                     );
                 }
             }
@@ -928,9 +1492,9 @@ impl LightWallet {
         .expect("u64 representable"))
     }
 
-    pub async fn send_to_addresses<F, Fut, P: SpendProver + OutputProver>(
+    pub async fn send_to_addresses<F, Fut>(
         &self,
-        sapling_prover: P,
+        sapling_prover: SaplingProver,
         policy: NoteSelectionPolicy,
         receivers: Receivers,
         submission_height: BlockHeight,
@@ -940,9 +1504,69 @@ impl LightWallet {
         F: Fn(Box<[u8]>) -> Fut,
         Fut: Future<Output = Result<String, String>>,
     {
-        // Reset the progress to start. Any errors will get recorded here
-        self.reset_send_progress().await;
+        let proposal = self
+            .create_proposal(policy, receivers, submission_height)
+            .await?;
+        self.send_proposal(sapling_prover, &proposal, submission_height, broadcast_fn)
+            .await
+    }
+
+    /// Selects notes and utxos to pay `receivers` and computes the exact fee and change,
+    /// without proving anything.  The result can be handed to [`Self::send_proposal`].
+    pub async fn create_proposal(
+        &self,
+        policy: NoteSelectionPolicy,
+        receivers: Receivers,
+        submission_height: BlockHeight,
+    ) -> Result<Proposal, String> {
+        self.check_spend_capability()?;
+        let txmds_readlock = self
+            .transaction_context
+            .transaction_metadata_set
+            .read()
+            .await;
+        let witness_trees = txmds_readlock
+            .witness_trees
+            .as_ref()
+            .expect("If we have spend capability we have trees");
+        self.propose_transaction(
+            submission_height,
+            witness_trees,
+            now(),
+            receivers,
+            policy,
+            Signer::Wallet,
+        )
+        .await
+    }
 
+    /// Resolves the wallet's [`ChangeTarget`] to one of its own receivers.
+    async fn change_receiver(&self) -> Result<ChangeReceiver, String> {
+        let change_target = self.wallet_options.read().await.change_target;
+        let wc = self.wallet_capability();
+        let (address, prefer_sapling) = match change_target {
+            ChangeTarget::DefaultAddress => (wc.addresses().iter().next(), false),
+            ChangeTarget::AddressIndex(index) => (wc.addresses().iter().nth(index as usize), false),
+            ChangeTarget::SaplingPool => (
+                wc.addresses().iter().find(|ua| ua.sapling().is_some()),
+                true,
+            ),
+        };
+        let address = address.ok_or(format!(
+            "The wallet has no address for change target {change_target}"
+        ))?;
+        match (address.orchard(), address.sapling(), prefer_sapling) {
+            (_, Some(sapling), true) | (None, Some(sapling), false) => {
+                Ok(ChangeReceiver::Sapling(*sapling))
+            }
+            (Some(orchard), _, false) => Ok(ChangeReceiver::Orchard(*orchard)),
+            _ => Err(format!(
+                "The address for change target {change_target} has no shielded receiver to return change to"
+            )),
+        }
+    }
+
+    fn check_spend_capability(&self) -> Result<(), String> {
         // Sanity check that this is a spending wallet.  Why isn't this done earlier?
         if !self.wallet_capability().can_spend_from_all_pools() {
             // Creating transactions in context of all possible combinations
@@ -952,44 +1576,652 @@ impl LightWallet {
             // Thus we forbid spending for wallets without complete spending capability for now
             return Err("Wallet is in watch-only mode and thus it cannot spend.".to_string());
         }
-        // Create the transaction
-        let start_time = now();
-        let build_result = self
-            .create_publication_ready_transaction(
-                submission_height,
-                start_time,
-                receivers,
-                policy,
-                sapling_prover,
-            )
-            .await?;
+        Ok(())
+    }
 
-        // Call the internal function
-        match self
-            .send_to_addresses_inner(build_result.transaction(), submission_height, broadcast_fn)
-            .await
-        {
-            Ok((transaction_id, raw_transaction)) => {
-                self.set_send_success(transaction_id.clone()).await;
-                Ok((transaction_id, raw_transaction))
-            }
-            Err(e) => {
-                self.set_send_error(e.to_string()).await;
-                Err(e)
-            }
-        }
+    /// Splits off the notes that are worth no more than the marginal fee of spending them.
+    fn separate_dust<D: DomainWalletExt>(
+        candidates: Vec<D::SpendableNoteAT>,
+        dust: &mut Dust,
+    ) -> Vec<D::SpendableNoteAT>
+    where
+        <D as Domain>::Note: PartialEq + Clone,
+        <D as Domain>::Recipient: traits::Recipient,
+    {
+        candidates
+            .into_iter()
+            .filter(|note| {
+                let value = D::WalletNote::value_from_note(note.note());
+                if value > u64::from(MARGINAL_FEE) {
+                    true
+                } else {
+                    dust.count += 1;
+                    dust.value += value;
+                    false
+                }
+            })
+            .collect()
     }
 
-    async fn create_tx_builder(
+    /// Proposes sending everything spendable in `pools` to `recipient`, less the fee.
+    /// Notes and utxos that would cost at least their own value to spend are left behind,
+    /// and reported as dust.
+    pub async fn create_send_all_proposal(
         &self,
+        pools: &[Pool],
+        recipient: address::Address,
+        memo: Option<MemoBytes>,
         submission_height: BlockHeight,
-        witness_trees: &WitnessTrees,
-    ) -> Result<TxBuilder, ShardTreeError<Infallible>> {
-        let orchard_anchor = self
-            .get_orchard_anchor(&witness_trees.witness_tree_orchard)
-            .await?;
-        let sapling_anchor = self
-            .get_sapling_anchor(&witness_trees.witness_tree_sapling)
+    ) -> Result<(Proposal, Dust), String> {
+        self.check_spend_capability()?;
+        let mut dust = Dust::default();
+        let orchard_notes = if pools.contains(&Pool::Orchard) {
+            Self::separate_dust::<OrchardDomain>(
+                self.get_all_domain_specific_notes::<OrchardDomain>()
+                    .await
+                    .into_iter()
+                    .filter(|note| note.spend_key().is_some())
+                    .collect(),
+                &mut dust,
+            )
+        } else {
+            vec![]
+        };
+        let sapling_notes = if pools.contains(&Pool::Sapling) {
+            Self::separate_dust::<SaplingDomain>(
+                self.get_all_domain_specific_notes::<SaplingDomain>()
+                    .await
+                    .into_iter()
+                    .filter(|note| note.spend_key().is_some())
+                    .collect(),
+                &mut dust,
+            )
+        } else {
+            vec![]
+        };
+        let utxos = if pools.contains(&Pool::Transparent) {
+            self.get_utxos()
+                .await
+                .into_iter()
+                .filter(|utxo| utxo.unconfirmed_spent.is_none() && utxo.spent.is_none())
+                .filter(|utxo| {
+                    if utxo.value > u64::from(MARGINAL_FEE) {
+                        true
+                    } else {
+                        dust.count += 1;
+                        dust.value += utxo.value;
+                        false
+                    }
+                })
+                .collect()
+        } else {
+            vec![]
+        };
+
+        let change_receiver = self.change_receiver().await?;
+
+        let txmds_readlock = self
+            .transaction_context
+            .transaction_metadata_set
+            .read()
+            .await;
+        let witness_trees = txmds_readlock
+            .witness_trees
+            .as_ref()
+            .expect("If we have spend capability we have trees");
+        let proposal = Proposal {
+            orchard_notes,
+            sapling_notes,
+            utxos,
+            receivers: vec![],
+            change: None,
+            change_receiver,
+            receiver_preference: self.wallet_options.read().await.receiver_preference,
+            fee: u64::from(MINIMUM_FEE),
+        };
+        match self
+            .balance_sweep(proposal, recipient, memo, submission_height, witness_trees)
+            .await?
+        {
+            Ok(proposal) => Ok((proposal, dust)),
+            Err(unaffordable) => Err(format!(
+                "Not enough spendable funds to send. Have {} zats, the fee is {} zats. {} zats in {} notes are dust, worth less than the fee to spend them.",
+                unaffordable.total_selected(), unaffordable.fee, dust.value, dust.count
+            )),
+        }
+    }
+
+    /// Proposes shielding `utxos`, and every spendable sapling note if `with_sapling`, into
+    /// `destination`. The amount shielded is what those inputs are worth less the ZIP-317
+    /// fee of spending exactly them. The inner error is the proposal whose fee would consume
+    /// everything selected.
+    pub async fn propose_shielding(
+        &self,
+        utxos: Vec<notes::TransparentNote>,
+        with_sapling: bool,
+        destination: address::Address,
+        submission_height: BlockHeight,
+    ) -> Result<Result<Proposal, Proposal>, String> {
+        self.check_spend_capability()?;
+        let sapling_notes = if with_sapling {
+            self.get_all_domain_specific_notes::<SaplingDomain>()
+                .await
+                .into_iter()
+                .filter(|note| note.spend_key().is_some())
+                .collect()
+        } else {
+            vec![]
+        };
+        let proposal = Proposal {
+            orchard_notes: vec![],
+            sapling_notes,
+            utxos,
+            receivers: vec![],
+            change: None,
+            change_receiver: self.change_receiver().await?,
+            receiver_preference: ReceiverPreference::OrchardFirst,
+            fee: u64::from(MINIMUM_FEE),
+        };
+
+        let txmds_readlock = self
+            .transaction_context
+            .transaction_metadata_set
+            .read()
+            .await;
+        let witness_trees = txmds_readlock
+            .witness_trees
+            .as_ref()
+            .expect("If we have spend capability we have trees");
+        self.balance_sweep(
+            proposal,
+            destination,
+            None,
+            submission_height,
+            witness_trees,
+        )
+        .await
+    }
+
+    /// Sets the single receiver of a change-less `proposal` to `recipient`, paying it
+    /// everything selected less the fee.
+    /// The inner error is the proposal, when its fee would consume everything selected.
+    async fn balance_sweep(
+        &self,
+        mut proposal: Proposal,
+        recipient: address::Address,
+        memo: Option<MemoBytes>,
+        submission_height: BlockHeight,
+        witness_trees: &WitnessTrees,
+    ) -> Result<Result<Proposal, Proposal>, String> {
+        // The fee only depends on the shape of the transaction, not on the value sent,
+        // so this settles after at most one correction.
+        loop {
+            if proposal.sweep_to(recipient.clone(), memo.clone()).is_err() {
+                return Ok(Err(proposal));
+            }
+            let required_fee = self
+                .required_fee(
+                    submission_height,
+                    witness_trees,
+                    &proposal,
+                    &Zip317FeeRule::standard(),
+                )
+                .await?;
+            if required_fee == proposal.fee {
+                return Ok(Ok(proposal));
+            }
+            proposal.fee = required_fee;
+        }
+    }
+
+    /// Proposes self-sends to the wallet's first address that each merge up to
+    /// `max_inputs` of the smallest spendable notes in `pool`. Sapling notes are merged
+    /// into the address' orchard receiver, when it has one and `privacy_policy` allows value
+    /// to cross pools, and stay in the sapling pool otherwise.
+    /// Batches whose notes are together worth no more than their fee are left out.
+    pub async fn create_consolidation_proposals(
+        &self,
+        pool: Pool,
+        max_inputs: usize,
+        privacy_policy: PrivacyPolicy,
+        submission_height: BlockHeight,
+    ) -> Result<Vec<Proposal>, String> {
+        self.check_spend_capability()?;
+        if max_inputs < 2 {
+            return Err(
+                "Consolidation needs to merge at least 2 notes per transaction".to_string(),
+            );
+        }
+        let mut dust = Dust::default();
+        let (mut orchard_notes, mut sapling_notes) = match pool {
+            Pool::Orchard => (
+                Self::separate_dust::<OrchardDomain>(
+                    self.get_all_domain_specific_notes::<OrchardDomain>()
+                        .await
+                        .into_iter()
+                        .filter(|note| note.spend_key().is_some())
+                        .collect(),
+                    &mut dust,
+                ),
+                vec![],
+            ),
+            Pool::Sapling => (
+                vec![],
+                Self::separate_dust::<SaplingDomain>(
+                    self.get_all_domain_specific_notes::<SaplingDomain>()
+                        .await
+                        .into_iter()
+                        .filter(|note| note.spend_key().is_some())
+                        .collect(),
+                    &mut dust,
+                ),
+            ),
+            Pool::Transparent => {
+                return Err(
+                    "Transparent funds are consolidated by shielding them. Use the shield command instead."
+                        .to_string(),
+                )
+            }
+        };
+        // Candidates arrive largest first
+        orchard_notes.reverse();
+        sapling_notes.reverse();
+
+        let recipient = address::Address::Unified(
+            self.wallet_capability()
+                .addresses()
+                .iter()
+                .next()
+                .ok_or("The wallet has no address to consolidate into".to_string())?
+                .clone(),
+        );
+        let change_receiver = self.change_receiver().await?;
+        let receiver_preference = if privacy_policy >= PrivacyPolicy::AllowRevealedAmounts {
+            // Merging into orchard is the point of consolidating sapling notes
+            ReceiverPreference::OrchardFirst
+        } else {
+            ReceiverPreference::MatchFunding
+        };
+        let txmds_readlock = self
+            .transaction_context
+            .transaction_metadata_set
+            .read()
+            .await;
+        let witness_trees = txmds_readlock
+            .witness_trees
+            .as_ref()
+            .expect("If we have spend capability we have trees");
+
+        let mut proposals = vec![];
+        let mut orchard_batches = orchard_notes.chunks(max_inputs);
+        let mut sapling_batches = sapling_notes.chunks(max_inputs);
+        loop {
+            let (orchard_batch, sapling_batch) = match pool {
+                Pool::Orchard => match orchard_batches.next() {
+                    Some(batch) => (batch.to_vec(), vec![]),
+                    None => break,
+                },
+                _ => match sapling_batches.next() {
+                    Some(batch) => (vec![], batch.to_vec()),
+                    None => break,
+                },
+            };
+            if orchard_batch.len() + sapling_batch.len() < 2 {
+                break;
+            }
+            let proposal = Proposal {
+                orchard_notes: orchard_batch,
+                sapling_notes: sapling_batch,
+                utxos: vec![],
+                receivers: vec![],
+                change: None,
+                change_receiver,
+                receiver_preference,
+                fee: u64::from(MINIMUM_FEE),
+            };
+            if let Ok(proposal) = self
+                .balance_sweep(
+                    proposal,
+                    recipient.clone(),
+                    None,
+                    submission_height,
+                    witness_trees,
+                )
+                .await?
+            {
+                proposals.push(proposal);
+            }
+        }
+        Ok(proposals)
+    }
+
+    /// Proposes sending to `receivers` as [`Self::create_proposal`] does, but without needing
+    /// spend keys. The anchors and witnesses of the spent notes are fixed, so that a wallet
+    /// holding the keys can sign and prove the transaction without any chain state.
+    pub async fn create_unsigned_transaction(
+        &self,
+        policy: NoteSelectionPolicy,
+        privacy_policy: PrivacyPolicy,
+        receivers: Receivers,
+        submission_height: BlockHeight,
+    ) -> Result<UnsignedTransaction, String> {
+        let txmds_readlock = self
+            .transaction_context
+            .transaction_metadata_set
+            .read()
+            .await;
+        let witness_trees = txmds_readlock
+            .witness_trees
+            .as_ref()
+            .ok_or("This wallet keeps no witness trees. Rescan it to build them.".to_string())?;
+        let proposal = self
+            .propose_transaction(
+                submission_height,
+                witness_trees,
+                now(),
+                receivers,
+                policy,
+                Signer::Offline,
+            )
+            .await?;
+        proposal.check_privacy(privacy_policy)?;
+
+        let sapling_anchor = self
+            .get_sapling_anchor(&witness_trees.witness_tree_sapling)
+            .await
+            .map_err(|e| format!("could not create sapling anchor: {e:?}"))?;
+        let orchard_anchor = self
+            .get_orchard_anchor(&witness_trees.witness_tree_orchard)
+            .await
+            .map_err(|e| format!("could not create orchard anchor: {e:?}"))?;
+        let checkpoint_depth = self.transaction_context.config.reorg_buffer_offset as usize;
+        let sapling_spends = proposal
+            .sapling_notes
+            .iter()
+            .map(|selected| {
+                Ok(UnsignedSaplingSpend {
+                    diversifier: selected.diversifier,
+                    note: selected.note.clone(),
+                    merkle_path: witness_trees
+                        .witness_tree_sapling
+                        .witness_at_checkpoint_depth(selected.witnessed_position, checkpoint_depth)
+                        .map_err(|e| format!("failed to compute sapling witness: {e}"))?,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let orchard_spends = proposal
+            .orchard_notes
+            .iter()
+            .map(|selected| {
+                Ok(UnsignedOrchardSpend {
+                    diversifier: selected.diversifier,
+                    note: selected.note,
+                    merkle_path: witness_trees
+                        .witness_tree_orchard
+                        .witness_at_checkpoint_depth(selected.witnessed_position, checkpoint_depth)
+                        .map_err(|e| format!("failed to compute orchard witness: {e}"))?,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let outputs = proposal
+            .receivers
+            .iter()
+            .map(|(recipient, value, memo)| {
+                (
+                    recipient.encode(&self.transaction_context.config.chain),
+                    u64::from(*value),
+                    memo.clone(),
+                )
+            })
+            .collect();
+
+        Ok(UnsignedTransaction {
+            submission_height,
+            sapling_anchor,
+            orchard_anchor,
+            sapling_spends,
+            orchard_spends,
+            utxos: proposal.utxos,
+            outputs,
+            change: proposal.change,
+            change_receiver: proposal.change_receiver,
+            receiver_preference: proposal.receiver_preference,
+            fee: proposal.fee,
+        })
+    }
+
+    /// Signs and proves `unsigned` with this wallet's spend keys. Needs no chain state, so
+    /// it works on a wallet that has never synced.
+    /// Returns the transaction id and the raw transaction.
+    pub async fn sign_unsigned_transaction(
+        &self,
+        unsigned: &UnsignedTransaction,
+        sapling_prover: SaplingProver,
+    ) -> Result<(transaction::TxId, Vec<u8>), String> {
+        self.check_spend_capability()?;
+        // The file comes from another wallet, which must not get to take the change
+        if unsigned.change.is_some()
+            && !unsigned
+                .change_receiver
+                .is_receiver_of(self.wallet_capability().addresses().iter())
+        {
+            return Err(format!(
+                "The unsigned transaction returns its change to {}, which is not an address of this wallet. Refusing to sign it.",
+                unsigned
+                    .change_receiver
+                    .encode(&self.transaction_context.config.chain)
+            ));
+        }
+        let chain = self.transaction_context.config.chain;
+        let receivers = unsigned
+            .outputs
+            .iter()
+            .map(|(recipient, value, memo)| {
+                Ok((
+                    address::Address::decode(&chain, recipient)
+                        .ok_or(format!("Invalid recipient address: '{}'", recipient))?,
+                    NonNegativeAmount::from_u64(*value)
+                        .map_err(|_| format!("Invalid amount {}", value))?,
+                    memo.clone(),
+                ))
+            })
+            .collect::<Result<Receivers, String>>()?;
+
+        let tx_builder = Builder::new(
+            chain,
+            unsigned.submission_height,
+            transaction::builder::BuildConfig::Standard {
+                sapling_anchor: Some(unsigned.sapling_anchor),
+                orchard_anchor: Some(unsigned.orchard_anchor),
+            },
+        );
+        let (mut total_shielded_receivers, tx_builder) = self
+            .add_consumer_specified_outputs_to_builder(
+                tx_builder,
+                receivers.clone(),
+                unsigned.receiver_preference,
+                &unsigned.pools_spent(),
+            )?;
+        let tx_builder = self.add_change_output_to_builder(
+            tx_builder,
+            unsigned.change,
+            unsigned.change_receiver,
+            &receivers,
+            &mut total_shielded_receivers,
+        )?;
+        let mut tx_builder = self.add_transparent_inputs_to_builder(tx_builder, &unsigned.utxos)?;
+        let wc = self.wallet_capability();
+        if !unsigned.sapling_spends.is_empty() {
+            let extsk = SaplingDomain::wc_to_sk(&wc)?;
+            for spend in unsigned.sapling_spends.iter() {
+                tx_builder
+                    .add_sapling_spend::<Zip317FeeRule>(
+                        &extsk,
+                        spend.note.clone(),
+                        spend.merkle_path.clone(),
+                    )
+                    .map_err(|e| format!("Error adding note: {:?}", e))?;
+            }
+        }
+        if !unsigned.orchard_spends.is_empty() {
+            let spend_key = OrchardDomain::wc_to_sk(&wc)?;
+            for spend in unsigned.orchard_spends.iter() {
+                tx_builder
+                    .add_orchard_spend::<Zip317FeeRule>(
+                        &spend_key,
+                        spend.note,
+                        orchard::tree::MerklePath::from(spend.merkle_path.clone()),
+                    )
+                    .map_err(|e| format!("Error adding note: {:?}", e))?;
+            }
+        }
+
+        let fee_rule = proposal_fee_rule(&tx_builder, unsigned.fee, unsigned.change)?;
+        let build_result = sapling_prover
+            .build(tx_builder, &fee_rule)
+            .map_err(|e| format!("Error creating transaction: {:?}", e))?;
+        let transaction = build_result.transaction();
+        let mut raw_transaction = vec![];
+        transaction
+            .write(&mut raw_transaction)
+            .map_err(|e| format!("Error serializing transaction: {}", e))?;
+        Ok((transaction.txid(), raw_transaction))
+    }
+
+    /// Broadcasts a transaction that was built elsewhere, or one that fell out of the
+    /// mempool, and records it in this wallet as pending, as it does its own sends.
+    /// A transaction the wallet already holds as confirmed is refused, as recording it
+    /// again would mark it pending.
+    pub async fn broadcast_raw_transaction<F, Fut>(
+        &self,
+        raw_transaction: &[u8],
+        submission_height: BlockHeight,
+        broadcast_fn: F,
+    ) -> Result<String, String>
+    where
+        F: Fn(Box<[u8]>) -> Fut,
+        Fut: Future<Output = Result<String, String>>,
+    {
+        let transaction = Transaction::read(
+            raw_transaction,
+            BranchId::for_height(&self.transaction_context.config.chain, submission_height),
+        )
+        .map_err(|e| format!("Could not read transaction: {}", e))?;
+        if self
+            .transaction_context
+            .transaction_metadata_set
+            .read()
+            .await
+            .current
+            .get(&transaction.txid())
+            .is_some_and(|record| record.status.is_confirmed())
+        {
+            return Err(format!(
+                "Transaction {} is already confirmed",
+                transaction.txid()
+            ));
+        }
+        self.send_to_addresses_inner(&transaction, submission_height, broadcast_fn)
+            .await
+            .map(|(transaction_id, _)| transaction_id)
+    }
+
+    /// Proves and broadcasts exactly the inputs, outputs and fee of `proposal`.
+    pub async fn send_proposal<F, Fut>(
+        &self,
+        sapling_prover: SaplingProver,
+        proposal: &Proposal,
+        submission_height: BlockHeight,
+        broadcast_fn: F,
+    ) -> Result<(String, Vec<u8>), String>
+    where
+        F: Fn(Box<[u8]>) -> Fut,
+        Fut: Future<Output = Result<String, String>>,
+    {
+        // Reset the progress to start. Any errors will get recorded here
+        self.reset_send_progress().await;
+
+        self.check_proposal_inputs_unspent(proposal).await?;
+        // Create the transaction
+        let start_time = now();
+        let build_result = self
+            .create_publication_ready_transaction(
+                submission_height,
+                start_time,
+                proposal,
+                sapling_prover,
+            )
+            .await?;
+
+        // Call the internal function
+        match self
+            .send_to_addresses_inner(build_result.transaction(), submission_height, broadcast_fn)
+            .await
+        {
+            Ok((transaction_id, raw_transaction)) => {
+                self.set_send_success(transaction_id.clone()).await;
+                Ok((transaction_id, raw_transaction))
+            }
+            Err(e) => {
+                self.set_send_error(e.to_string()).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// A proposal may be confirmed some time after it was made.  Refuse it if any of its
+    /// inputs has been spent since.
+    async fn check_proposal_inputs_unspent(&self, proposal: &Proposal) -> Result<(), String> {
+        let orchard_nullifiers = self
+            .get_all_domain_specific_notes::<OrchardDomain>()
+            .await
+            .into_iter()
+            .map(|note| note.nullifier)
+            .collect::<Vec<_>>();
+        let sapling_nullifiers = self
+            .get_all_domain_specific_notes::<SaplingDomain>()
+            .await
+            .into_iter()
+            .map(|note| note.nullifier)
+            .collect::<Vec<_>>();
+        let utxos = self.get_utxos().await;
+        let all_inputs_unspent = proposal
+            .orchard_notes
+            .iter()
+            .all(|note| orchard_nullifiers.contains(&note.nullifier))
+            && proposal
+                .sapling_notes
+                .iter()
+                .all(|note| sapling_nullifiers.contains(&note.nullifier))
+            && proposal.utxos.iter().all(|proposed| {
+                utxos.iter().any(|utxo| {
+                    utxo.txid == proposed.txid
+                        && utxo.output_index == proposed.output_index
+                        && utxo.spent.is_none()
+                        && utxo.unconfirmed_spent.is_none()
+                })
+            });
+        if all_inputs_unspent {
+            Ok(())
+        } else {
+            Err(
+                "Proposal is stale: some of its inputs have been spent since it was created. \
+                Please create a new proposal."
+                    .to_string(),
+            )
+        }
+    }
+
+    async fn create_tx_builder(
+        &self,
+        submission_height: BlockHeight,
+        witness_trees: &WitnessTrees,
+    ) -> Result<TxBuilder, ShardTreeError<Infallible>> {
+        let orchard_anchor = self
+            .get_orchard_anchor(&witness_trees.witness_tree_orchard)
+            .await?;
+        let sapling_anchor = self
+            .get_sapling_anchor(&witness_trees.witness_tree_sapling)
             .await?;
         Ok(Builder::new(
             self.transaction_context.config.chain,
@@ -1002,14 +2234,11 @@ impl LightWallet {
         ))
     }
 
-    async fn add_spends_to_builder<'a>(
-        &'a self,
+    fn add_transparent_inputs_to_builder<'a>(
+        &self,
         mut tx_builder: TxBuilder<'a>,
-        witness_trees: &WitnessTrees,
-        orchard_notes: &[SpendableOrchardNote],
-        sapling_notes: &[SpendableSaplingNote],
         utxos: &[notes::TransparentNote],
-    ) -> Result<TxBuilder<'_>, String> {
+    ) -> Result<TxBuilder<'a>, String> {
         // Add all tinputs
         // Create a map from address -> sk for all taddrs, so we can spend from the
         // right address
@@ -1047,11 +2276,23 @@ impl LightWallet {
             })
             .collect::<Result<Vec<_>, _>>()
             .map_err(|e| format!("{:?}", e))?;
+        Ok(tx_builder)
+    }
+
+    async fn add_spends_to_builder<'a>(
+        &'a self,
+        tx_builder: TxBuilder<'a>,
+        witness_trees: &WitnessTrees,
+        orchard_notes: &[SpendableOrchardNote],
+        sapling_notes: &[SpendableSaplingNote],
+        utxos: &[notes::TransparentNote],
+    ) -> Result<TxBuilder<'_>, String> {
+        let mut tx_builder = self.add_transparent_inputs_to_builder(tx_builder, utxos)?;
 
         for selected in sapling_notes.iter() {
             info!("Adding sapling spend");
             // Turbofish only needed for error type
-            if let Err(e) = tx_builder.add_sapling_spend::<FixedFeeRule>(
+            if let Err(e) = tx_builder.add_sapling_spend::<Zip317FeeRule>(
                 &selected.extsk.clone().unwrap(),
                 selected.note.clone(),
                 witness_trees
@@ -1070,7 +2311,7 @@ impl LightWallet {
 
         for selected in orchard_notes.iter() {
             info!("Adding orchard spend");
-            if let Err(e) = tx_builder.add_orchard_spend::<transaction::fees::fixed::FeeRule>(
+            if let Err(e) = tx_builder.add_orchard_spend::<Zip317FeeRule>(
                 &selected.spend_key.unwrap(),
                 selected.note,
                 orchard::tree::MerklePath::from(
@@ -1090,10 +2331,14 @@ impl LightWallet {
         }
         Ok(tx_builder)
     }
+    /// Each unified address is paid in the receiver `receiver_preference` chooses for a
+    /// transaction spending from `funding_pools`.
     fn add_consumer_specified_outputs_to_builder<'a>(
         &'a self,
         mut tx_builder: TxBuilder<'a>,
         receivers: Receivers,
+        receiver_preference: ReceiverPreference,
+        funding_pools: &[Pool],
     ) -> Result<(u32, TxBuilder<'_>), String> {
         // Convert address (str) to RecipientAddress and value to Amount
 
@@ -1105,6 +2350,17 @@ impl LightWallet {
 
         let mut total_shielded_receivers = 0u32;
         for (recipient_address, value, memo) in receivers {
+            let receiving_pool =
+                receiver_preference.receiving_pool(&recipient_address, funding_pools);
+            if matches!(recipient_address, address::Address::Unified(_))
+                && receiving_pool == Some(Pool::Transparent)
+                && memo.is_some()
+            {
+                return Err(format!(
+                    "Can't send a memo to the transparent receiver of {}",
+                    recipient_address.encode(&self.transaction_context.config.chain)
+                ));
+            }
             // Compute memo if it exists
             let validated_memo = match memo {
                 None => MemoBytes::from(Memo::Empty),
@@ -1119,27 +2375,33 @@ impl LightWallet {
                     total_shielded_receivers += 1;
                     tx_builder.add_sapling_output(Some(sapling_ovk), to, value, validated_memo)
                 }
-                address::Address::Unified(ua) => {
-                    if let Some(orchard_addr) = ua.orchard() {
+                address::Address::Unified(ua) => match receiving_pool {
+                    Some(Pool::Orchard) => {
                         total_shielded_receivers += 1;
-                        tx_builder.add_orchard_output::<FixedFeeRule>(
+                        tx_builder.add_orchard_output::<Zip317FeeRule>(
                             Some(orchard_ovk.clone()),
-                            *orchard_addr,
+                            *ua.orchard().expect("the chosen receiver"),
                             u64::from(value),
                             validated_memo,
                         )
-                    } else if let Some(sapling_addr) = ua.sapling() {
+                    }
+                    Some(Pool::Sapling) => {
                         total_shielded_receivers += 1;
                         tx_builder.add_sapling_output(
                             Some(sapling_ovk),
-                            *sapling_addr,
+                            *ua.sapling().expect("the chosen receiver"),
                             value,
                             validated_memo,
                         )
-                    } else {
-                        return Err("Received UA with no Orchard or Sapling receiver".to_string());
                     }
-                }
+                    Some(Pool::Transparent) => tx_builder
+                        .add_transparent_output(
+                            ua.transparent().expect("the chosen receiver"),
+                            value,
+                        )
+                        .map_err(transaction::builder::Error::TransparentBuild),
+                    None => return Err("Received UA with no receiver".to_string()),
+                },
             } {
                 let e = format!("Error adding output: {:?}", e);
                 error!("{}", e);
@@ -1152,11 +2414,16 @@ impl LightWallet {
     fn add_change_output_to_builder<'a>(
         &self,
         mut tx_builder: TxBuilder<'a>,
-        target_amount: Amount,
-        selected_value: Amount,
-        total_shielded_receivers: &mut u32,
+        change: Option<u64>,
+        change_receiver: ChangeReceiver,
         receivers: &Receivers,
+        total_shielded_receivers: &mut u32,
     ) -> Result<TxBuilder<'a>, String> {
+        // Without a change output there is nowhere to record the uas we sent to,
+        // which only affects how the sent-to address is displayed.
+        let Some(change) = change else {
+            return Ok(tx_builder);
+        };
         let destination_uas = receivers
             .iter()
             .filter_map(|receiver| match receiver.0 {
@@ -1176,18 +2443,33 @@ impl LightWallet {
                 [0; 511]
             }
         };
-        let orchard_ovk =
-            orchard::keys::OutgoingViewingKey::try_from(&*self.wallet_capability()).unwrap();
+        // Here we store the uas we sent to in the memo field.
+        // These are used to recover the full UA we sent to.
+        let memo = MemoBytes::from(Memo::Arbitrary(Box::new(uas_bytes)));
         *total_shielded_receivers += 1;
-        if let Err(e) = tx_builder.add_orchard_output::<FixedFeeRule>(
-            Some(orchard_ovk.clone()),
-            *self.wallet_capability().addresses()[0].orchard().unwrap(),
-            u64::try_from(selected_value).expect("u64 representable")
-                - u64::try_from(target_amount).expect("u64 representable"),
-            // Here we store the uas we sent to in the memo field.
-            // These are used to recover the full UA we sent to.
-            MemoBytes::from(Memo::Arbitrary(Box::new(uas_bytes))),
-        ) {
+        if let Err(e) = match change_receiver {
+            ChangeReceiver::Orchard(change_address) => {
+                let orchard_ovk =
+                    orchard::keys::OutgoingViewingKey::try_from(&*self.wallet_capability())?;
+                tx_builder.add_orchard_output::<Zip317FeeRule>(
+                    Some(orchard_ovk),
+                    change_address,
+                    change,
+                    memo,
+                )
+            }
+            ChangeReceiver::Sapling(change_address) => {
+                let sapling_ovk =
+                    sapling_crypto::keys::OutgoingViewingKey::try_from(&*self.wallet_capability())?;
+                tx_builder.add_sapling_output::<Zip317FeeRule>(
+                    Some(sapling_ovk),
+                    change_address,
+                    NonNegativeAmount::from_u64(change)
+                        .map_err(|_| "Invalid change value".to_string())?,
+                    memo,
+                )
+            }
+        } {
             let e = format!("Error adding change output: {:?}", e);
             error!("{}", e);
             return Err(e);
@@ -1195,23 +2477,143 @@ impl LightWallet {
         Ok(tx_builder)
     }
 
-    async fn create_and_populate_tx_builder(
+    /// Creates a builder at `submission_height` and adds exactly the inputs and outputs of
+    /// `proposal` to it.
+    async fn create_and_populate_tx_builder(
+        &self,
+        submission_height: BlockHeight,
+        witness_trees: &WitnessTrees,
+        proposal: &Proposal,
+    ) -> Result<(TxBuilder<'_>, u32), String> {
+        let tx_builder = match self
+            .create_tx_builder(submission_height, witness_trees)
+            .await
+        {
+            Err(ShardTreeError::Query(QueryError::NotContained(addr))) => Err(format!(
+                "could not create anchor, missing address {addr:?}. \
+                If you are fully synced, you may need to rescan to proceed"
+            )),
+            Err(ShardTreeError::Query(QueryError::CheckpointPruned)) => {
+                let blocks = self.blocks.read().await.len();
+                let offset = self.transaction_context.config.reorg_buffer_offset;
+                Err(format!(
+                    "The reorg buffer offset has been set to {} \
+                    but there are only {} blocks in the wallet. \
+                    Please sync at least {} more blocks before trying again",
+                    offset,
+                    blocks,
+                    offset + 1 - blocks as u32
+                ))
+            }
+            Err(ShardTreeError::Query(QueryError::TreeIncomplete(addrs))) => Err(format!(
+                "could not create anchor, missing addresses {addrs:?}. \
+                If you are fully synced, you may need to rescan to proceed"
+            )),
+            Err(ShardTreeError::Insert(_)) => unreachable!(),
+            Err(ShardTreeError::Storage(_infallible)) => unreachable!(),
+            Ok(v) => Ok(v),
+        }?;
+
+        let (mut total_shielded_receivers, tx_builder) = self
+            .add_consumer_specified_outputs_to_builder(
+                tx_builder,
+                proposal.receivers.clone(),
+                proposal.receiver_preference,
+                &proposal.pools_spent(),
+            )?;
+        let tx_builder = self.add_change_output_to_builder(
+            tx_builder,
+            proposal.change,
+            proposal.change_receiver,
+            &proposal.receivers,
+            &mut total_shielded_receivers,
+        )?;
+        let tx_builder = self
+            .add_spends_to_builder(
+                tx_builder,
+                witness_trees,
+                &proposal.orchard_notes,
+                &proposal.sapling_notes,
+                &proposal.utxos,
+            )
+            .await?;
+        Ok((tx_builder, total_shielded_receivers))
+    }
+
+    /// Selects notes to cover `receivers` plus the ZIP-317 fee.
+    /// Under [`ReceiverPreference::MatchFunding`], each shielded pool of `policy` that every
+    /// recipient can be paid in is first tried alone, so that no value crosses pools.
+    async fn propose_transaction(
+        &self,
+        submission_height: BlockHeight,
+        witness_trees: &WitnessTrees,
+        start_time: u64,
+        receivers: Receivers,
+        policy: NoteSelectionPolicy,
+        signer: Signer,
+    ) -> Result<Proposal, String> {
+        let receiver_preference = self.wallet_options.read().await.receiver_preference;
+        if receiver_preference == ReceiverPreference::MatchFunding && policy.len() > 1 {
+            for pool in policy.iter().filter(|pool| **pool != Pool::Transparent) {
+                let paid_without_crossing = receivers.iter().all(|(recipient, _, _)| {
+                    matches!(
+                        receiver_preference.receiving_pool(recipient, &[*pool]),
+                        Some(paid) if paid == *pool || paid == Pool::Transparent
+                    )
+                });
+                if !paid_without_crossing {
+                    continue;
+                }
+                if let Ok(proposal) = self
+                    .propose_transaction_from_pools(
+                        submission_height,
+                        witness_trees,
+                        start_time,
+                        receivers.clone(),
+                        vec![*pool],
+                        receiver_preference,
+                        signer,
+                    )
+                    .await
+                {
+                    return Ok(proposal);
+                }
+            }
+        }
+        self.propose_transaction_from_pools(
+            submission_height,
+            witness_trees,
+            start_time,
+            receivers,
+            policy,
+            receiver_preference,
+            signer,
+        )
+        .await
+    }
+
+    /// Adding inputs can change the fee, so selection repeats until the fee of the
+    /// populated builder agrees with the fee the notes were selected for.
+    async fn propose_transaction_from_pools(
         &self,
         submission_height: BlockHeight,
         witness_trees: &WitnessTrees,
         start_time: u64,
         receivers: Receivers,
         policy: NoteSelectionPolicy,
-    ) -> Result<(TxBuilder<'_>, u32), String> {
-        let fee_rule =
-            &zcash_primitives::transaction::fees::fixed::FeeRule::non_standard(MINIMUM_FEE); // Start building tx
-        let mut total_shielded_receivers;
-        let mut orchard_notes;
-        let mut sapling_notes;
-        let mut utxos;
-        let mut tx_builder;
-        let mut proposed_fee = MINIMUM_FEE;
-        let mut total_value_covered_by_selected;
+        receiver_preference: ReceiverPreference,
+        signer: Signer,
+    ) -> Result<Proposal, String> {
+        let fee_rule = &Zip317FeeRule::standard();
+        let change_receiver = self.change_receiver().await?;
+        let mut strategy = self.wallet_options.read().await.note_selection;
+        // Until notes are selected, the recipients are counted in the pools they would be
+        // paid in if every pool of the policy were spent. Each later round counts them as
+        // the builder pays them, from the pools the round before spent.
+        let mut outputs =
+            receiver_preference.output_shape(&receivers, &policy, Some(change_receiver.pool()));
+        let mut proposed_fees = vec![];
+        let mut proposed_fee = u64::from(MINIMUM_FEE);
         let total_earmarked_for_recipients: u64 = receivers.iter().map(|to| u64::from(to.1)).sum();
         info!(
             "0: Creating transaction sending {} zatoshis to {} addresses",
@@ -1219,54 +2621,16 @@ impl LightWallet {
             receivers.len()
         );
         loop {
-            tx_builder = match self
-                .create_tx_builder(submission_height, witness_trees)
-                .await
-            {
-                Err(ShardTreeError::Query(QueryError::NotContained(addr))) => Err(format!(
-                    "could not create anchor, missing address {addr:?}. \
-                    If you are fully synced, you may need to rescan to proceed"
-                )),
-                Err(ShardTreeError::Query(QueryError::CheckpointPruned)) => {
-                    let blocks = self.blocks.read().await.len();
-                    let offset = self.transaction_context.config.reorg_buffer_offset;
-                    Err(format!(
-                        "The reorg buffer offset has been set to {} \
-                        but there are only {} blocks in the wallet. \
-                        Please sync at least {} more blocks before trying again",
-                        offset,
-                        blocks,
-                        offset + 1 - blocks as u32
-                    ))
-                }
-                Err(ShardTreeError::Query(QueryError::TreeIncomplete(addrs))) => Err(format!(
-                    "could not create anchor, missing addresses {addrs:?}. \
-                    If you are fully synced, you may need to rescan to proceed"
-                )),
-                Err(ShardTreeError::Insert(_)) => unreachable!(),
-                Err(ShardTreeError::Storage(_infallible)) => unreachable!(),
-                Ok(v) => Ok(v),
-            }?;
-
-            // Select notes to cover the target value
-            info!("{}: Adding outputs", now() - start_time);
-            (total_shielded_receivers, tx_builder) = self
-                .add_consumer_specified_outputs_to_builder(tx_builder, receivers.clone())
-                .expect("To add outputs");
-
-            let earmark_total_plus_default_fee =
-                total_earmarked_for_recipients + u64::from(proposed_fee);
+            let earmark_total_plus_default_fee = total_earmarked_for_recipients + proposed_fee;
             // Select notes as a fn of target amount
-            (
-                orchard_notes,
-                sapling_notes,
-                utxos,
-                total_value_covered_by_selected,
-            ) = match self
+            let (orchard_notes, sapling_notes, utxos, total_value_covered_by_selected) = match self
                 .select_notes_and_utxos(
                     Amount::from_u64(earmark_total_plus_default_fee)
                         .expect("Valid amount, from u64."),
                     &policy,
+                    strategy,
+                    &outputs,
+                    signer,
                 )
                 .await
             {
@@ -1292,59 +2656,72 @@ impl LightWallet {
                 &utxos.len()
             );
 
-            let temp_tx_builder = match self.add_change_output_to_builder(
-                tx_builder,
-                Amount::from_u64(earmark_total_plus_default_fee).expect("valid value of u64"),
-                Amount::from_u64(total_value_covered_by_selected).unwrap(),
-                &mut total_shielded_receivers,
-                &receivers,
-            ) {
-                Ok(txb) => txb,
-                Err(r) => {
-                    return Err(r);
-                }
-            };
-            info!("{}: selecting notes", now() - start_time);
-            tx_builder = match self
-                .add_spends_to_builder(
-                    temp_tx_builder,
-                    witness_trees,
-                    &orchard_notes,
-                    &sapling_notes,
-                    &utxos,
-                )
-                .await
-            {
-                Ok(tx_builder) => tx_builder,
-
-                Err(s) => {
-                    return Err(s);
-                }
+            let change = total_value_covered_by_selected - earmark_total_plus_default_fee;
+            let proposal = Proposal {
+                orchard_notes,
+                sapling_notes,
+                utxos,
+                receivers: receivers.clone(),
+                change: if change > 0 { Some(change) } else { None },
+                change_receiver,
+                receiver_preference,
+                fee: proposed_fee,
             };
-            proposed_fee = tx_builder.get_fee(fee_rule).unwrap();
-            if u64::from(proposed_fee) + total_earmarked_for_recipients
-                <= total_value_covered_by_selected
-            {
-                break;
+            // The change output was sized with the proposed fee, so the transaction
+            // only balances once the fee computed over its final shape agrees. Without
+            // change, the fee may keep what dropping the change output saved.
+            let required_fee = self
+                .required_fee(submission_height, witness_trees, &proposal, fee_rule)
+                .await?;
+            if fees::pays_required_fee(proposed_fee, proposal.change, required_fee) {
+                return Ok(proposal);
+            }
+            outputs = receiver_preference.output_shape(
+                &receivers,
+                &proposal.pools_spent(),
+                Some(change_receiver.pool()),
+            );
+            proposed_fees.push(proposed_fee);
+            if proposed_fees.contains(&required_fee) {
+                // The selection moves with the fee and has come back to a fee it already
+                // tried. Largest first only ever adds notes as the fee grows.
+                strategy = NoteSelectionStrategy::LargestFirst;
             }
+            proposed_fee = required_fee;
         }
-        Ok((tx_builder, total_shielded_receivers))
     }
 
-    async fn create_publication_ready_transaction<P: SpendProver + OutputProver>(
+    async fn required_fee(
+        &self,
+        submission_height: BlockHeight,
+        witness_trees: &WitnessTrees,
+        proposal: &Proposal,
+        fee_rule: &Zip317FeeRule,
+    ) -> Result<u64, String> {
+        if !self.wallet_capability().can_spend_from_all_pools() {
+            // Without spend keys there is no builder to ask, so a view-only wallet counts
+            // the actions itself.
+            return Ok(proposal.shape().conventional_fee());
+        }
+        let (tx_builder, _) = self
+            .create_and_populate_tx_builder(submission_height, witness_trees, proposal)
+            .await?;
+        tx_builder
+            .get_fee(fee_rule)
+            .map(u64::from)
+            .map_err(|e| format!("Error computing ZIP-317 fee: {:?}", e))
+    }
+
+    async fn create_publication_ready_transaction(
         &self,
         submission_height: BlockHeight,
         start_time: u64,
-        receivers: Receivers,
-        policy: NoteSelectionPolicy,
-        sapling_prover: P,
+        proposal: &Proposal,
+        sapling_prover: SaplingProver,
         // We only care about the transaction...but it can now only be aquired by reference
         // from the build result, so we need to return the whole thing
     ) -> Result<BuildResult, String> {
-        // Start building transaction with spends and outputs set by:
-        //  * target amount
-        //  * selection policy
-        //  * recipient list
+        // Start building transaction with the spends and outputs of the proposal
         let txmds_readlock = self
             .transaction_context
             .transaction_metadata_set
@@ -1354,21 +2731,9 @@ impl LightWallet {
             .witness_trees
             .as_ref()
             .expect("If we have spend capability we have trees");
-        let (tx_builder, total_shielded_receivers) = match self
-            .create_and_populate_tx_builder(
-                submission_height,
-                witness_trees,
-                start_time,
-                receivers,
-                policy,
-            )
-            .await
-        {
-            Ok(tx_builder) => tx_builder,
-            Err(s) => {
-                return Err(s);
-            }
-        };
+        let (tx_builder, total_shielded_receivers) = self
+            .create_and_populate_tx_builder(submission_height, witness_trees, proposal)
+            .await?;
 
         drop(txmds_readlock);
         // The builder now has the correct set of inputs and outputs
@@ -1405,13 +2770,9 @@ impl LightWallet {
 
         info!("{}: Building transaction", now() - start_time);
 
+        let fee_rule = proposal_fee_rule(&tx_builder, proposal.fee, proposal.change)?;
         let tx_builder = tx_builder.with_progress_notifier(transmitter);
-        let build_result = match tx_builder.build(
-            OsRng,
-            &sapling_prover,
-            &sapling_prover,
-            &transaction::fees::fixed::FeeRule::non_standard(MINIMUM_FEE),
-        ) {
+        let build_result = match sapling_prover.build(tx_builder, &fee_rule) {
             Ok(res) => res,
             Err(e) => {
                 let e = format!("Error creating transaction: {:?}", e);
@@ -1443,21 +2804,129 @@ impl LightWallet {
 
         let transaction_id = broadcast_fn(raw_transaction.clone().into_boxed_slice()).await?;
 
-        // Add this transaction to the mempool structure
+        self.record_broadcast(transaction, submission_height).await;
+        // Keep the raw bytes, so that the send can be rebroadcast if it is dropped
         {
-            let price = self.price.read().await.clone();
-
-            let status = ConfirmationStatus::Broadcast(submission_height);
-            self.transaction_context
-                .scan_full_tx(transaction, status, now() as u32, get_price(now(), &price))
-                .await;
+            let mut pending_transactions = self.pending_transactions.write().await;
+            pending_transactions.retain(|pending| pending.txid != transaction.txid());
+            pending_transactions.push(PendingTransaction {
+                txid: transaction.txid(),
+                raw_transaction: raw_transaction.clone(),
+                expiry_height: transaction.expiry_height(),
+                last_broadcast_height: submission_height,
+                broadcasts: 1,
+                last_error: None,
+            });
         }
 
         Ok((transaction_id, raw_transaction))
     }
 
+    /// Adds a broadcast transaction to the mempool structure
+    async fn record_broadcast(&self, transaction: &Transaction, submission_height: BlockHeight) {
+        let price = self.price.read().await.clone();
+
+        let status = ConfirmationStatus::Broadcast(submission_height);
+        self.transaction_context
+            .scan_full_tx(transaction, status, now() as u32, get_price(now(), &price))
+            .await;
+    }
+
+    /// Marks the pending sends whose expiry height the chain has reached as expired, and
+    /// releases the notes and utxos they spent, so that they are spendable again.
+    /// Returns the sends that newly expired.
+    pub async fn expire_pending_transactions(
+        &self,
+        latest_height: BlockHeight,
+    ) -> Vec<transaction::TxId> {
+        let expired = self
+            .pending_transactions
+            .read()
+            .await
+            .iter()
+            .filter(|pending| pending.is_expired(latest_height))
+            .map(|pending| (pending.txid, pending.expiry_height))
+            .collect::<Vec<_>>();
+        if expired.is_empty() {
+            return vec![];
+        }
+        self.transaction_context
+            .transaction_metadata_set
+            .write()
+            .await
+            .mark_expired(&expired)
+    }
+
+    /// Broadcasts again the pending sends that are due under the wallet's
+    /// [`RebroadcastPolicy`]. Sends that were mined stop being tracked, as do expired
+    /// sends once their record is cleared.
+    /// Returns the result of each broadcast.
+    /// The sends are copied out before broadcasting, so that no lock is held over the server
+    /// calls.
+    pub async fn rebroadcast_pending<F, Fut>(
+        &self,
+        latest_height: BlockHeight,
+        broadcast_fn: F,
+    ) -> Vec<(transaction::TxId, Result<String, String>)>
+    where
+        F: Fn(Box<[u8]>) -> Fut,
+        Fut: Future<Output = Result<String, String>>,
+    {
+        let policy = self.wallet_options.read().await.rebroadcast;
+        let due = {
+            let mut pending_transactions = self.pending_transactions.write().await;
+            let txmds_readlock = self
+                .transaction_context
+                .transaction_metadata_set
+                .read()
+                .await;
+            // Expired sends are kept while their record is, so that they are listed as expired
+            pending_transactions.retain(|pending| {
+                match txmds_readlock.current.get(&pending.txid) {
+                    Some(record) => !record.status.is_confirmed(),
+                    None => !pending.is_expired(latest_height),
+                }
+            });
+            pending_transactions
+                .iter()
+                .filter(|pending| {
+                    !pending.is_expired(latest_height) && pending.is_due(policy, latest_height)
+                })
+                .map(|pending| (pending.txid, pending.raw_transaction.clone()))
+                .collect::<Vec<_>>()
+        };
+
+        let mut results = vec![];
+        for (txid, raw_transaction) in due {
+            let result = broadcast_fn(raw_transaction.clone().into_boxed_slice()).await;
+            if let Some(pending) = self
+                .pending_transactions
+                .write()
+                .await
+                .iter_mut()
+                .find(|pending| pending.txid == txid)
+            {
+                pending.last_broadcast_height = latest_height;
+                pending.broadcasts += 1;
+                pending.last_error = result.as_ref().err().cloned();
+            }
+            if result.is_ok() {
+                // The send may have been dropped from the mempool structure
+                // as expired, so record it again
+                if let Ok(transaction) = Transaction::read(
+                    &raw_transaction[..],
+                    BranchId::for_height(&self.transaction_context.config.chain, latest_height),
+                ) {
+                    self.record_broadcast(&transaction, latest_height).await;
+                }
+            }
+            results.push((txid, result));
+        }
+        results
+    }
+
     pub const fn serialized_version() -> u64 {
-        28
+        35
     }
 
     pub async fn set_blocks(&self, new_blocks: Vec<BlockData>) {
@@ -1660,7 +3129,12 @@ impl LightWallet {
         self.transaction_context.key.clone()
     }
 
-    pub async fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+    pub async fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+        self.write_internal(writer, true).await
+    }
+
+    /// An account is written without the seed, which the wallet holding it writes once.
+    async fn write_internal<W: Write>(&self, mut writer: W, with_seed: bool) -> io::Result<()> {
         // Write the version
         writer.write_u64::<LittleEndian>(Self::serialized_version())?;
 
@@ -1703,14 +3177,65 @@ impl LightWallet {
         self.price.read().await.write(&mut writer)?;
 
         let seed_bytes = match &self.mnemonic {
-            Some(m) => m.0.clone().into_entropy(),
-            None => vec![],
+            Some(m) if with_seed => m.0.clone().into_entropy(),
+            _ => vec![],
         };
         Vector::write(&mut writer, &seed_bytes, |w, byte| w.write_u8(*byte))?;
 
         match &self.mnemonic {
-            Some(m) => writer.write_u32::<LittleEndian>(m.1)?,
-            None => (),
+            Some(m) if with_seed => writer.write_u32::<LittleEndian>(m.1)?,
+            _ => (),
+        }
+
+        Vector::write(
+            &mut writer,
+            &self.pending_transactions.read().await,
+            |w, pending| pending.write(w),
+        )?;
+
+        // Each account is a whole wallet but for the seed, written length-prefixed after
+        // its index
+        let mut accounts_bytes = vec![];
+        for account in self.accounts.read().await.iter() {
+            let mut account_bytes = vec![];
+            Box::pin(account.write_internal(&mut account_bytes, false)).await?;
+            accounts_bytes.push((account.account_index().unwrap_or_default(), account_bytes));
+        }
+        Vector::write(&mut writer, &accounts_bytes, |w, (index, account_bytes)| {
+            w.write_u32::<LittleEndian>(*index)?;
+            Vector::write(w, account_bytes, |w, byte| w.write_u8(*byte))
+        })?;
+
+        // And so is each imported key
+        let mut keys_bytes = vec![];
+        for imported in self.imported_keys.read().await.iter() {
+            let mut key_bytes = vec![];
+            Box::pin(imported.write(&mut key_bytes)).await?;
+            keys_bytes.push(key_bytes);
+        }
+        Vector::write(&mut writer, &keys_bytes, |w, key_bytes| {
+            Vector::write(w, key_bytes, |w, byte| w.write_u8(*byte))
+        })?;
+
+        Vector::write(
+            &mut writer,
+            &self.ephemeral_taddrs.read().await,
+            |w, taddr| utils::write_string(w, taddr),
+        )?;
+
+        Vector::write(
+            &mut writer,
+            &self.unscanned_ranges.read().await,
+            |w, (start, end)| {
+                w.write_u64::<LittleEndian>(*start)?;
+                w.write_u64::<LittleEndian>(*end)
+            },
+        )?;
+
+        for ends in self.subtree_root_ends.read().await.iter() {
+            Vector::write(&mut writer, ends, |w, end| {
+                w.write_u64::<LittleEndian>(*end)
+            })?;
         }
 
         Ok(())
@@ -1786,8 +3311,666 @@ fn decode_orchard_spending_key(
 
 #[cfg(test)]
 mod test {
-    use incrementalmerkletree::frontier::CommitmentTree;
+    use incrementalmerkletree::{frontier::CommitmentTree, Hashable, Level, MerklePath, Position};
     use orchard::tree::MerkleHashOrchard;
+    use sapling_crypto::{value::NoteValue, zip32::ExtendedSpendingKey, Rseed};
+    use zcash_client_backend::address;
+    use zcash_primitives::{
+        consensus::{BlockHeight, BranchId},
+        merkle_tree::HashSer,
+        transaction::{
+            components::{amount::NonNegativeAmount, TxOut},
+            Transaction, TxId,
+        },
+    };
+    use zingo_status::confirmation_status::ConfirmationStatus;
+    use zingoconfig::{ChainType, ZingoConfig};
+
+    use super::{
+        data::COMMITMENT_TREE_LEVELS,
+        proposal::ChangeReceiver,
+        prover::SaplingProver,
+        unsigned::{UnsignedSaplingSpend, UnsignedTransaction},
+        ChangeTarget, LightWallet, NoteSelectionStrategy, Pool, RebroadcastPolicy,
+        ReceiverPreference, WalletBase, WalletOptions,
+    };
+
+    #[test]
+    fn wallet_options_round_trip() {
+        for change_target in [
+            ChangeTarget::DefaultAddress,
+            ChangeTarget::AddressIndex(7),
+            ChangeTarget::SaplingPool,
+        ] {
+            let options = WalletOptions {
+                change_target,
+                note_selection: NoteSelectionStrategy::ExactMatch,
+                rebroadcast: RebroadcastPolicy::Never,
+                receiver_preference: ReceiverPreference::MatchFunding,
+                ephemeral_shield_depth: Some(3),
+                last_auto_shield_height: Some(1_234),
+                ..WalletOptions::default()
+            };
+            let mut buffer = vec![];
+            options.write(&mut buffer).unwrap();
+            let read = WalletOptions::read(buffer.as_slice()).unwrap();
+            assert_eq!(read.change_target, change_target);
+            assert_eq!(read.note_selection, NoteSelectionStrategy::ExactMatch);
+            assert_eq!(read.rebroadcast, RebroadcastPolicy::Never);
+            assert_eq!(read.receiver_preference, ReceiverPreference::MatchFunding);
+            assert_eq!(read.ephemeral_shield_depth, Some(3));
+            assert_eq!(read.last_auto_shield_height, Some(1_234));
+            assert_eq!(read.download_memos, options.download_memos);
+            assert_eq!(
+                read.transaction_size_filter,
+                options.transaction_size_filter
+            );
+        }
+    }
+
+    #[test]
+    fn wallet_options_version_2_defaults_change_target() {
+        // version 2: download_memos, then an optional transaction_size_filter
+        let version_2 = [2, 0, 0, 0, 0, 0, 0, 0, 1, 1, 0xf4, 1, 0, 0];
+        let read = WalletOptions::read(version_2.as_slice()).unwrap();
+        assert_eq!(read.transaction_size_filter, Some(500));
+        assert_eq!(read.change_target, ChangeTarget::DefaultAddress);
+        assert_eq!(read.note_selection, NoteSelectionStrategy::LargestFirst);
+        assert_eq!(read.rebroadcast, RebroadcastPolicy::default());
+        assert_eq!(read.receiver_preference, ReceiverPreference::OrchardFirst);
+        assert_eq!(read.ephemeral_shield_depth, None);
+        assert_eq!(read.auto_shield, None);
+        assert_eq!(read.last_auto_shield_height, None);
+    }
+
+    #[tokio::test]
+    async fn unsigned_transactions_round_trip_and_sign() {
+        let config = ZingoConfig::build(ChainType::FakeMainnet).create();
+        let wallet = LightWallet::new(config.clone(), WalletBase::SeedBytes([7; 32]), 0).unwrap();
+        let wc = wallet.wallet_capability();
+        let own_sapling = *wc.addresses().iter().next().unwrap().sapling().unwrap();
+        let elsewhere = ExtendedSpendingKey::master(&[2; 32]).default_address().1;
+
+        // A note of the wallet's, alone in the tree
+        let note = sapling_crypto::Note::from_parts(
+            own_sapling,
+            NoteValue::from_raw(40_000),
+            Rseed::AfterZip212([7; 32]),
+        );
+        let merkle_path = MerklePath::from_parts(
+            (0..COMMITMENT_TREE_LEVELS)
+                .map(|level| sapling_crypto::Node::empty_root(Level::from(level)))
+                .collect(),
+            Position::from(0u64),
+        )
+        .unwrap();
+        let mut root = vec![];
+        merkle_path
+            .root(sapling_crypto::Node::from_cmu(&note.cmu()))
+            .write(&mut root)
+            .unwrap();
+        let unsigned = UnsignedTransaction {
+            submission_height: BlockHeight::from_u32(2_000_000),
+            sapling_anchor: Option::from(sapling_crypto::Anchor::from_bytes(
+                root.try_into().unwrap(),
+            ))
+            .unwrap(),
+            orchard_anchor: orchard::Anchor::empty_tree(),
+            sapling_spends: vec![UnsignedSaplingSpend {
+                diversifier: *own_sapling.diversifier(),
+                note,
+                merkle_path,
+            }],
+            orchard_spends: vec![],
+            utxos: vec![],
+            outputs: vec![(
+                address::Address::Sapling(elsewhere).encode(&config.chain),
+                20_000,
+                None,
+            )],
+            change: Some(10_000),
+            change_receiver: ChangeReceiver::Sapling(own_sapling),
+            receiver_preference: ReceiverPreference::default(),
+            fee: 10_000,
+        };
+
+        let mut bytes = vec![];
+        unsigned.write(&mut bytes).unwrap();
+        let read = UnsignedTransaction::read(bytes.as_slice(), &wc).unwrap();
+        let json = read.to_json(&config.chain);
+        assert_eq!(json, unsigned.to_json(&config.chain));
+        assert_eq!(json["change"], 10_000);
+        assert_eq!(
+            json["change_address"],
+            address::Address::Sapling(own_sapling).encode(&config.chain)
+        );
+
+        let (txid, raw_transaction) = wallet
+            .sign_unsigned_transaction(&read, SaplingProver::Mock)
+            .await
+            .unwrap();
+        let transaction = Transaction::read(
+            raw_transaction.as_slice(),
+            BranchId::for_height(&config.chain, read.submission_height),
+        )
+        .unwrap();
+        assert_eq!(transaction.txid(), txid);
+        let sapling_bundle = transaction.sapling_bundle().unwrap();
+        assert_eq!(sapling_bundle.shielded_spends().len(), 1);
+        assert_eq!(sapling_bundle.shielded_outputs().len(), 2);
+
+        // Change returned to anyone else is refused
+        let redirected = UnsignedTransaction {
+            change_receiver: ChangeReceiver::Sapling(elsewhere),
+            ..read
+        };
+        assert!(wallet
+            .sign_unsigned_transaction(&redirected, SaplingProver::Mock)
+            .await
+            .is_err());
+    }
+
+    const SUBMISSION_HEIGHT: u32 = 2_000_000;
+
+    /// A wallet holding a 100_000 zat utxo, with anchors to build at [`SUBMISSION_HEIGHT`]
+    async fn wallet_with_utxo() -> LightWallet {
+        let config = ZingoConfig::build(ChainType::FakeMainnet).create();
+        let wallet = LightWallet::new(config, WalletBase::SeedBytes([7; 32]), 0).unwrap();
+        prepare_to_build(&wallet, true).await;
+        wallet
+    }
+
+    /// Adds the anchors to build at [`SUBMISSION_HEIGHT`], and, if `with_utxo`, a 100_000
+    /// zat utxo to the wallet's first address
+    async fn prepare_to_build(wallet: &LightWallet, with_utxo: bool) {
+        let taddr = *wallet.wallet_capability().addresses()[0]
+            .transparent()
+            .unwrap();
+        let submission_height = BlockHeight::from_u32(SUBMISSION_HEIGHT);
+        let mut txmds = wallet
+            .transaction_context
+            .transaction_metadata_set
+            .write()
+            .await;
+        if with_utxo {
+            txmds.add_new_taddr_output(
+                TxId::from_bytes([9; 32]),
+                address::Address::Transparent(taddr)
+                    .encode(&wallet.transaction_context.config.chain),
+                ConfirmationStatus::Confirmed(submission_height - 10),
+                0,
+                &TxOut {
+                    value: NonNegativeAmount::const_from_u64(100_000),
+                    script_pubkey: taddr.script(),
+                },
+                0,
+            );
+        }
+        // The anchors are taken at the latest checkpoint
+        txmds
+            .witness_trees
+            .as_mut()
+            .unwrap()
+            .add_checkpoint(submission_height - 1);
+    }
+
+    #[tokio::test]
+    async fn accounts_are_saved_without_repeating_the_seed() {
+        let config = ZingoConfig::build(ChainType::FakeMainnet).create();
+        let wallet = LightWallet::new(config.clone(), WalletBase::SeedBytes([7; 32]), 0).unwrap();
+        let first = wallet.add_account().await.unwrap();
+        let second = wallet.add_account().await.unwrap();
+        assert_eq!(first.account_index(), Some(1));
+        assert_eq!(second.account_index(), Some(2));
+
+        let mut bytes = vec![];
+        wallet.write(&mut bytes).await.unwrap();
+        let seed = [7; 32];
+        assert_eq!(
+            bytes
+                .windows(seed.len())
+                .filter(|window| *window == seed)
+                .count(),
+            1
+        );
+
+        let read = LightWallet::read_internal(bytes.as_slice(), &config)
+            .await
+            .unwrap();
+        let account = read.account(2).await.unwrap();
+        assert_eq!(account.account_index(), Some(2));
+        assert_eq!(
+            account.mnemonic.as_ref().unwrap().0.entropy(),
+            wallet.mnemonic.as_ref().unwrap().0.entropy()
+        );
+        assert_eq!(
+            account.wallet_capability().addresses()[0],
+            second.wallet_capability().addresses()[0]
+        );
+        assert!(read.account(3).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn transparent_addresses_are_derived_ahead_as_they_are_added() {
+        let config = ZingoConfig::build(ChainType::FakeMainnet).create();
+        let spending = LightWallet::new(config.clone(), WalletBase::SeedBytes([7; 32]), 0).unwrap();
+        let viewing = LightWallet::new(
+            config,
+            WalletBase::Ufvk(spending.encoded_ufvk().unwrap()),
+            0,
+        )
+        .unwrap();
+        for wallet in [spending, viewing] {
+            let capability = wallet.wallet_capability();
+            let ahead = (0..3)
+                .map(|index| capability.transparent_address_at(index).unwrap().unwrap())
+                .collect::<Vec<_>>();
+            while capability.addresses().len() < ahead.len() {
+                capability.new_address(capability.can_view()).unwrap();
+            }
+            let added = capability
+                .addresses()
+                .iter()
+                .map(|address| *address.transparent().unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(added, ahead);
+        }
+    }
+
+    #[tokio::test]
+    async fn transparent_only_addresses_take_child_indexes_in_turn() {
+        use super::{keys::unified::WalletCapability, traits::ReadableWriteable as _};
+
+        let config = ZingoConfig::build(ChainType::FakeMainnet).create();
+        let wallet = LightWallet::new(config.clone(), WalletBase::SeedBytes([7; 32]), 0).unwrap();
+        let capability = wallet.wallet_capability();
+        let at = |index| {
+            super::keys::address_from_pubkeyhash(
+                &config,
+                capability.transparent_address_at(index).unwrap().unwrap(),
+            )
+        };
+        let ephemeral = wallet.new_taddr(true).await.unwrap();
+        let ua = capability.new_address(capability.can_view()).unwrap();
+        let plain = wallet.new_taddr(false).await.unwrap();
+        assert_eq!(capability.addresses().len(), 2);
+        assert_eq!(ephemeral, at(1));
+        assert_eq!(
+            super::keys::address_from_pubkeyhash(&config, *ua.transparent().unwrap()),
+            at(2)
+        );
+        assert_eq!(plain, at(3));
+        assert_eq!(
+            *wallet.ephemeral_taddrs.read().await,
+            vec![ephemeral.clone()]
+        );
+
+        let secret_keys = capability.get_taddr_to_secretkey_map(&config).unwrap();
+        assert!(secret_keys.contains_key(&ephemeral) && secret_keys.contains_key(&plain));
+
+        let mut bytes = vec![];
+        capability.write(&mut bytes).unwrap();
+        let read = WalletCapability::read(bytes.as_slice(), ()).unwrap();
+        assert_eq!(
+            read.transparent_receivers(),
+            capability.transparent_receivers()
+        );
+        assert_eq!(read.next_transparent_index(), 4);
+    }
+
+    #[tokio::test]
+    async fn ephemeral_addresses_count_their_payments() {
+        let config = ZingoConfig::build(ChainType::FakeMainnet).create();
+        let wallet = LightWallet::new(config, WalletBase::SeedBytes([7; 32]), 0).unwrap();
+        let ephemeral = wallet.new_taddr(true).await.unwrap();
+        let taddr = *wallet
+            .wallet_capability()
+            .transparent_receivers()
+            .last()
+            .unwrap();
+        for (payment, txid) in [[1; 32], [2; 32]].into_iter().enumerate() {
+            wallet.transactions().write().await.add_new_taddr_output(
+                TxId::from_bytes(txid),
+                ephemeral.clone(),
+                ConfirmationStatus::Confirmed(BlockHeight::from_u32(1_000 + payment as u32)),
+                0,
+                &TxOut {
+                    value: NonNegativeAmount::const_from_u64(10_000),
+                    script_pubkey: taddr.script(),
+                },
+                0,
+            );
+            assert_eq!(
+                wallet.ephemeral_receipts().await,
+                vec![(ephemeral.clone(), payment + 1)]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn auto_shield_leaves_single_use_addresses_out() {
+        let wallet = wallet_with_utxo().await;
+        wallet
+            .blocks
+            .write()
+            .await
+            .push(super::data::BlockData::new_with(
+                SUBMISSION_HEIGHT as u64 - 1,
+                &[0; 32],
+            ));
+        let ephemeral = wallet.new_taddr(true).await.unwrap();
+        let ephemeral_receiver = *wallet
+            .wallet_capability()
+            .transparent_receivers()
+            .last()
+            .unwrap();
+        wallet.transactions().write().await.add_new_taddr_output(
+            TxId::from_bytes([8; 32]),
+            ephemeral,
+            ConfirmationStatus::Confirmed(BlockHeight::from_u32(SUBMISSION_HEIGHT - 10)),
+            0,
+            &TxOut {
+                value: NonNegativeAmount::const_from_u64(50_000),
+                script_pubkey: ephemeral_receiver.script(),
+            },
+            0,
+        );
+
+        let utxos = wallet.auto_shield_utxos().await;
+        assert_eq!(
+            utxos.iter().map(|utxo| utxo.value).collect::<Vec<_>>(),
+            vec![100_000]
+        );
+        let destination =
+            address::Address::Unified(wallet.wallet_capability().addresses()[0].clone());
+        let proposal = wallet
+            .propose_shielding(
+                utxos,
+                false,
+                destination,
+                BlockHeight::from_u32(SUBMISSION_HEIGHT),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(u64::from(proposal.receivers[0].1) + proposal.fee, 100_000);
+    }
+
+    #[tokio::test]
+    async fn shielding_pays_the_fee_of_the_inputs_it_sweeps() {
+        let wallet = wallet_with_utxo().await;
+        wallet
+            .blocks
+            .write()
+            .await
+            .push(super::data::BlockData::new_with(
+                SUBMISSION_HEIGHT as u64 - 1,
+                &[0; 32],
+            ));
+        let submission_height = BlockHeight::from_u32(SUBMISSION_HEIGHT);
+        let destination =
+            address::Address::Unified(wallet.wallet_capability().addresses()[0].clone());
+
+        let utxos = wallet.confirmed_utxos(1, |_| true).await;
+        let proposal = wallet
+            .propose_shielding(utxos, true, destination.clone(), submission_height)
+            .await
+            .unwrap()
+            .unwrap();
+        // One transparent input to the orchard receiver, padded to two actions
+        assert_eq!(proposal.fee, 15_000);
+        assert_eq!(proposal.total_to_receivers(), 85_000);
+        assert_eq!(proposal.change, None);
+        let txmds = wallet
+            .transaction_context
+            .transaction_metadata_set
+            .read()
+            .await;
+        let builder_fee = wallet
+            .required_fee(
+                submission_height,
+                txmds.witness_trees.as_ref().unwrap(),
+                &proposal,
+                &zcash_primitives::transaction::fees::zip317::FeeRule::standard(),
+            )
+            .await
+            .unwrap();
+        drop(txmds);
+        assert_eq!(proposal.fee, builder_fee);
+
+        let unaffordable = wallet
+            .propose_shielding(vec![], true, destination, submission_height)
+            .await
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(unaffordable.total_selected(), 0);
+    }
+
+    #[tokio::test]
+    async fn ufvks_are_imported_once_per_viewing_key() {
+        use zcash_address::unified::{Encoding as _, Fvk, Ufvk};
+
+        let config = ZingoConfig::build(ChainType::FakeMainnet).create();
+        let wallet = LightWallet::new(config.clone(), WalletBase::SeedBytes([7; 32]), 0).unwrap();
+        let other = LightWallet::new(config.clone(), WalletBase::SeedBytes([8; 32]), 0).unwrap();
+        let network = config.chain.to_zcash_address_network();
+
+        assert!(wallet
+            .import_ufvk(wallet.encoded_ufvk().unwrap(), 0)
+            .await
+            .is_err());
+        let other_ufvk = other.encoded_ufvk().unwrap();
+        wallet.import_ufvk(other_ufvk.clone(), 0).await.unwrap();
+        assert!(wallet.import_ufvk(other_ufvk, 0).await.is_err());
+
+        // A UFVK holding only the orchard key encodes differently, but views the same funds
+        let orchard_fvk =
+            orchard::keys::FullViewingKey::try_from(&*wallet.wallet_capability()).unwrap();
+        let orchard_only = Ufvk::try_from_items(vec![Fvk::Orchard(orchard_fvk.to_bytes())])
+            .unwrap()
+            .encode(&network);
+        assert_eq!(
+            wallet.import_ufvk(orchard_only, 0).await.unwrap_err(),
+            "This wallet already views the Orchard key of that UFVK"
+        );
+        assert_eq!(wallet.imported_keys.read().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn accounts_spend_only_their_own_funds() {
+        let config = ZingoConfig::build(ChainType::FakeMainnet).create();
+        let wallet = LightWallet::new(config, WalletBase::SeedBytes([7; 32]), 0).unwrap();
+        let account = wallet.add_account().await.unwrap();
+        prepare_to_build(&wallet, false).await;
+        prepare_to_build(&account, true).await;
+
+        let recipient = ExtendedSpendingKey::master(&[2; 32]).default_address().1;
+        let receivers = vec![(
+            address::Address::Sapling(recipient),
+            NonNegativeAmount::const_from_u64(40_000),
+            None,
+        )];
+        let submission_height = BlockHeight::from_u32(SUBMISSION_HEIGHT);
+        assert!(wallet
+            .create_proposal(
+                vec![Pool::Transparent],
+                receivers.clone(),
+                submission_height
+            )
+            .await
+            .is_err());
+        let proposal = account
+            .create_proposal(vec![Pool::Transparent], receivers, submission_height)
+            .await
+            .unwrap();
+        assert_eq!(proposal.utxos.len(), 1);
+        assert_eq!(
+            proposal.utxos[0].address,
+            address::Address::Transparent(
+                *account.wallet_capability().addresses()[0]
+                    .transparent()
+                    .unwrap()
+            )
+            .encode(&account.transaction_context.config.chain)
+        );
+    }
+
+    #[tokio::test]
+    async fn sends_with_the_mock_prover() {
+        let wallet = wallet_with_utxo().await;
+        let config = wallet.transaction_context.config.clone();
+        let submission_height = BlockHeight::from_u32(SUBMISSION_HEIGHT);
+        let recipient = ExtendedSpendingKey::master(&[2; 32]).default_address().1;
+        let receivers = vec![(
+            address::Address::Sapling(recipient),
+            NonNegativeAmount::const_from_u64(40_000),
+            None,
+        )];
+        let proposal = wallet
+            .create_proposal(vec![Pool::Transparent], receivers, submission_height)
+            .await
+            .unwrap();
+        let (txid, raw_transaction) = wallet
+            .send_proposal(
+                SaplingProver::Mock,
+                &proposal,
+                submission_height,
+                |_| async { Ok("broadcast".to_string()) },
+            )
+            .await
+            .unwrap();
+        assert_eq!(txid, "broadcast");
+
+        let transaction = Transaction::read(
+            raw_transaction.as_slice(),
+            BranchId::for_height(&config.chain, submission_height),
+        )
+        .unwrap();
+        assert_eq!(transaction.transparent_bundle().unwrap().vin.len(), 1);
+        assert!(!transaction
+            .sapling_bundle()
+            .unwrap()
+            .shielded_outputs()
+            .is_empty());
+        // The utxo is spent by the pending send, which is kept to rebroadcast
+        assert!(wallet.get_utxos().await[0].unconfirmed_spent.is_some());
+        assert_eq!(
+            wallet.pending_transactions.read().await[0].txid,
+            transaction.txid()
+        );
+    }
+
+    #[tokio::test]
+    async fn proposal_returns_what_the_fee_leaves_as_change() {
+        let wallet = wallet_with_utxo().await;
+        let recipient = address::Address::Transparent(
+            zcash_primitives::legacy::TransparentAddress::PublicKey([7; 20]),
+        );
+        let proposal = wallet
+            .create_proposal(
+                vec![Pool::Transparent],
+                vec![(recipient, NonNegativeAmount::const_from_u64(50_000), None)],
+                BlockHeight::from_u32(SUBMISSION_HEIGHT),
+            )
+            .await
+            .unwrap();
+        // One transparent input and output, and the orchard change padded to two actions
+        assert_eq!(proposal.fee, 15_000);
+        assert_eq!(proposal.change, Some(35_000));
+        assert_eq!(proposal.pools_spent(), vec![Pool::Transparent]);
+        assert_eq!(proposal.pools_received(), vec![Pool::Transparent]);
+
+        let json = proposal.to_json(&ChainType::FakeMainnet);
+        assert_eq!(json["fee"], 15_000);
+        assert_eq!(json["change"], 35_000);
+        assert_eq!(json["total_selected"], 100_000);
+        assert_eq!(json["total_to_receivers"], 50_000);
+        assert_eq!(json["outputs"][0]["pool"], "Transparent");
+    }
+
+    #[tokio::test]
+    async fn send_all_pays_everything_but_dust_less_the_fee() {
+        let wallet = wallet_with_utxo().await;
+        let taddr = *wallet.wallet_capability().addresses()[0]
+            .transparent()
+            .unwrap();
+        for (txid, value) in [([10; 32], 30_000), ([11; 32], 20_000), ([12; 32], 4_000)] {
+            wallet.transactions().write().await.add_new_taddr_output(
+                TxId::from_bytes(txid),
+                address::Address::Transparent(taddr)
+                    .encode(&wallet.transaction_context.config.chain),
+                ConfirmationStatus::Confirmed(BlockHeight::from_u32(SUBMISSION_HEIGHT - 10)),
+                0,
+                &TxOut {
+                    value: NonNegativeAmount::const_from_u64(value),
+                    script_pubkey: taddr.script(),
+                },
+                0,
+            );
+        }
+        let recipient = address::Address::Transparent(
+            zcash_primitives::legacy::TransparentAddress::PublicKey([7; 20]),
+        );
+        let (proposal, dust) = wallet
+            .create_send_all_proposal(
+                &[Pool::Transparent],
+                recipient,
+                None,
+                BlockHeight::from_u32(SUBMISSION_HEIGHT),
+            )
+            .await
+            .unwrap();
+        // The 4_000 zat utxo is worth less than its marginal fee. The other three go to the
+        // one transparent output, with no change.
+        assert_eq!((dust.count, dust.value), (1, 4_000));
+        assert_eq!(proposal.utxos.len(), 3);
+        assert_eq!(proposal.fee, 15_000);
+        assert_eq!(proposal.total_to_receivers(), 135_000);
+        assert_eq!(proposal.change_pool(), None);
+        assert_eq!(proposal.receivers.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn proposal_fee_is_the_fee_of_the_build() {
+        let wallet = wallet_with_utxo().await;
+        wallet.wallet_options.write().await.receiver_preference = ReceiverPreference::MatchFunding;
+        let submission_height = BlockHeight::from_u32(SUBMISSION_HEIGHT);
+        // The policy would pay the sapling receiver, but only transparent funds are spent,
+        // so the orchard receiver is paid
+        let recipient =
+            address::Address::Unified(wallet.wallet_capability().addresses()[0].clone());
+        let proposal = wallet
+            .create_proposal(
+                vec![Pool::Sapling, Pool::Transparent],
+                vec![(
+                    recipient.clone(),
+                    NonNegativeAmount::const_from_u64(40_000),
+                    None,
+                )],
+                submission_height,
+            )
+            .await
+            .unwrap();
+        assert_eq!(proposal.pools_spent(), [Pool::Transparent]);
+        assert_eq!(proposal.receiving_pool(&recipient), Some(Pool::Orchard));
+
+        let txmds = wallet
+            .transaction_context
+            .transaction_metadata_set
+            .read()
+            .await;
+        let builder_fee = wallet
+            .required_fee(
+                submission_height,
+                txmds.witness_trees.as_ref().unwrap(),
+                &proposal,
+                &zcash_primitives::transaction::fees::zip317::FeeRule::standard(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(proposal.fee, builder_fee);
+        assert_eq!(proposal.shape().conventional_fee(), builder_fee);
+    }
 
     #[test]
     fn anchor_from_tree_works() {
@@ -0,0 +1,163 @@
+//! Shielding the wallet's transparent funds automatically, after each sync.
+use std::io::{self, Read, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use json::{object, JsonValue};
+
+/// A pool that transparent funds can be shielded into.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShieldedPool {
+    Orchard,
+    Sapling,
+}
+
+/// When and where transparent funds are shielded after a sync. Only the primary account's
+/// funds are shielded; those of the seed's other accounts are left for `shield
+/// --from-account`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AutoShieldPolicy {
+    /// The verified transparent balance, in zatoshis, above which funds are shielded.
+    /// Single-use addresses are left out, as they are shielded on their own.
+    pub min_balance: u64,
+    /// The shielded pool the funds are sent to
+    pub pool: ShieldedPool,
+    /// The fewest blocks between two automatic shieldings
+    pub min_blocks_between: u32,
+}
+
+impl std::str::FromStr for AutoShieldPolicy {
+    type Err = String;
+
+    /// Parses `<min balance>:<orchard|sapling>:<min blocks between>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let usage = format!(
+            "Unknown auto-shield policy '{s}'. Expected <min balance>:<orchard|sapling>:<min blocks between>"
+        );
+        match s.split(':').collect::<Vec<_>>()[..] {
+            [min_balance, pool, min_blocks_between] => Ok(AutoShieldPolicy {
+                min_balance: min_balance.parse().map_err(|_| usage.clone())?,
+                pool: match pool {
+                    "orchard" => ShieldedPool::Orchard,
+                    "sapling" => ShieldedPool::Sapling,
+                    _ => return Err(usage),
+                },
+                min_blocks_between: min_blocks_between.parse().map_err(|_| usage.clone())?,
+            }),
+            _ => Err(usage),
+        }
+    }
+}
+
+impl std::fmt::Display for AutoShieldPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let pool = match self.pool {
+            ShieldedPool::Orchard => "orchard",
+            ShieldedPool::Sapling => "sapling",
+        };
+        write!(
+            f,
+            "{}:{}:{}",
+            self.min_balance, pool, self.min_blocks_between
+        )
+    }
+}
+
+impl AutoShieldPolicy {
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let min_balance = reader.read_u64::<LittleEndian>()?;
+        let pool = match reader.read_u8()? {
+            0 => ShieldedPool::Orchard,
+            1 => ShieldedPool::Sapling,
+            v => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Bad auto-shield pool {}", v),
+                ))
+            }
+        };
+        let min_blocks_between = reader.read_u32::<LittleEndian>()?;
+        Ok(AutoShieldPolicy {
+            min_balance,
+            pool,
+            min_blocks_between,
+        })
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_u64::<LittleEndian>(self.min_balance)?;
+        writer.write_u8(match self.pool {
+            ShieldedPool::Orchard => 0,
+            ShieldedPool::Sapling => 1,
+        })?;
+        writer.write_u32::<LittleEndian>(self.min_blocks_between)
+    }
+}
+
+/// What became of the automatic shielding after a sync.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AutoShieldResult {
+    Shielded {
+        txid: String,
+        value: u64,
+    },
+    /// The verified transparent balance was not above the policy's minimum
+    BelowThreshold {
+        balance: u64,
+    },
+    /// The last automatic shielding was too few blocks ago
+    TooSoon {
+        last_height: u64,
+    },
+    /// Another sync or send held the sync lock
+    LockContended,
+    Failed(String),
+}
+
+impl From<&AutoShieldResult> for JsonValue {
+    fn from(result: &AutoShieldResult) -> Self {
+        match result {
+            AutoShieldResult::Shielded { txid, value } => object! {
+                "result" => "shielded",
+                "txid" => txid.clone(),
+                "value" => *value,
+            },
+            AutoShieldResult::BelowThreshold { balance } => object! {
+                "result" => "below_threshold",
+                "balance" => *balance,
+            },
+            AutoShieldResult::TooSoon { last_height } => object! {
+                "result" => "too_soon",
+                "last_height" => *last_height,
+            },
+            AutoShieldResult::LockContended => object! { "result" => "lock_contended" },
+            AutoShieldResult::Failed(e) => object! {
+                "result" => "failed",
+                "error" => e.clone(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AutoShieldPolicy, ShieldedPool};
+
+    #[test]
+    fn policy_round_trip() {
+        let policy: AutoShieldPolicy = "100000:sapling:10".parse().unwrap();
+        assert_eq!(
+            policy,
+            AutoShieldPolicy {
+                min_balance: 100_000,
+                pool: ShieldedPool::Sapling,
+                min_blocks_between: 10,
+            }
+        );
+        assert_eq!(policy.to_string().parse(), Ok(policy));
+        let mut buffer = vec![];
+        policy.write(&mut buffer).unwrap();
+        assert_eq!(AutoShieldPolicy::read(buffer.as_slice()).unwrap(), policy);
+        assert!("100000:transparent:10".parse::<AutoShieldPolicy>().is_err());
+        assert!("100000:orchard".parse::<AutoShieldPolicy>().is_err());
+    }
+}
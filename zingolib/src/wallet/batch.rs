@@ -0,0 +1,306 @@
+//! Batches of payments read from a CSV file, paid in as many transactions as needed.
+use json::{object, JsonValue};
+use zcash_client_backend::address;
+use zcash_primitives::memo::MemoBytes;
+use zingoconfig::ChainType;
+
+use super::{
+    fees::TransactionShape,
+    receivers::{decode_tex_address, ReceiverPreference},
+    utils, Pool,
+};
+
+/// Bounds the ZIP-317 logical actions of the outputs of each transaction, and so its size
+/// and proving time.
+pub const DEFAULT_MAX_ACTIONS_PER_TRANSACTION: usize = 50;
+
+pub struct BatchPayment {
+    /// The line of the CSV file the payment was read from, counting from 1
+    pub line: usize,
+    pub address: String,
+    /// The decoded `address`, a TEX address as its transparent address
+    pub recipient: address::Address,
+    pub amount: u64,
+    pub memo: Option<MemoBytes>,
+}
+
+pub struct PaymentBatch {
+    pub payments: Vec<BatchPayment>,
+}
+
+impl PaymentBatch {
+    /// Reads rows of `address,amount[,memo]`, with amounts in zatoshis. Fields may be
+    /// quoted, so that memos can hold commas. A header row, blank lines and lines starting
+    /// with `#` are skipped.
+    /// Every row is checked, and the error lists every invalid row.
+    pub fn parse_csv(contents: &str, chain: &ChainType) -> Result<Self, String> {
+        let mut payments = vec![];
+        let mut errors = vec![];
+        for (index, row) in contents.lines().enumerate() {
+            let line = index + 1;
+            let row = row.trim();
+            if row.is_empty() || row.starts_with('#') {
+                continue;
+            }
+            match parse_row(row, chain) {
+                Ok(Some((address, recipient, amount, memo))) => payments.push(BatchPayment {
+                    line,
+                    address,
+                    recipient,
+                    amount,
+                    memo,
+                }),
+                // The header
+                Ok(None) if payments.is_empty() && errors.is_empty() => (),
+                Ok(None) => errors.push(format!("line {line}: unexpected header")),
+                Err(e) => errors.push(format!("line {line}: {e}")),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(format!("Invalid batch:\n{}", errors.join("\n")));
+        }
+        if payments.is_empty() {
+            return Err("The batch has no payments".to_string());
+        }
+        Ok(Self { payments })
+    }
+
+    pub fn total(&self) -> u64 {
+        self.payments.iter().map(|payment| payment.amount).sum()
+    }
+
+    /// Splits the batch into the payments of each transaction, in order, so that the outputs
+    /// of each, with a change output, come to at most `max_actions` logical actions.
+    /// Unified addresses are counted in the pool `receiver_preference` pays them in.
+    /// The spends are not known before notes are selected, so are not counted.
+    pub fn transactions(
+        &self,
+        max_actions: usize,
+        receiver_preference: ReceiverPreference,
+    ) -> Vec<&[BatchPayment]> {
+        let add_output =
+            |shape: &mut TransactionShape, payment: &BatchPayment| match receiver_preference
+                .receiving_pool(&payment.recipient, &[])
+            {
+                Some(Pool::Orchard) => shape.orchard_outputs += 1,
+                Some(Pool::Sapling) => shape.sapling_outputs += 1,
+                Some(Pool::Transparent) | None => shape.transparent_outputs += 1,
+            };
+        // The change output, wherever it goes, costs at most an orchard action
+        let change_only = TransactionShape {
+            orchard_outputs: 1,
+            ..Default::default()
+        };
+
+        let mut transactions = vec![];
+        let mut start = 0;
+        let mut shape = change_only;
+        for (index, payment) in self.payments.iter().enumerate() {
+            let mut with_payment = shape;
+            add_output(&mut with_payment, payment);
+            // A payment that doesn't fit starts the next transaction, which holds at least it
+            if index > start && with_payment.logical_actions() > max_actions {
+                transactions.push(&self.payments[start..index]);
+                start = index;
+                with_payment = change_only;
+                add_output(&mut with_payment, payment);
+            }
+            shape = with_payment;
+        }
+        if start < self.payments.len() {
+            transactions.push(&self.payments[start..]);
+        }
+        transactions
+    }
+}
+
+/// One payment of a batch.
+pub struct BatchPaymentResult {
+    pub line: usize,
+    pub address: String,
+    pub amount: u64,
+}
+
+/// What became of one transaction of a batch.
+pub struct BatchTransactionResult {
+    pub payments: Vec<BatchPaymentResult>,
+    /// The transaction the payments were sent in
+    pub txid: Result<String, String>,
+    /// Zero unless the transaction was sent
+    pub fee: u64,
+}
+
+impl BatchTransactionResult {
+    pub fn new(payments: &[BatchPayment], txid: Result<String, String>, fee: u64) -> Self {
+        Self {
+            payments: payments
+                .iter()
+                .map(|payment| BatchPaymentResult {
+                    line: payment.line,
+                    address: payment.address.clone(),
+                    amount: payment.amount,
+                })
+                .collect(),
+            txid,
+            fee,
+        }
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        let mut result = object! {
+            "payments" => self.payments.iter().map(|payment| object! {
+                "line" => payment.line,
+                "address" => payment.address.clone(),
+                "amount" => payment.amount,
+            }).collect::<Vec<_>>(),
+            "fee" => self.fee,
+        };
+        match &self.txid {
+            Ok(txid) => result["txid"] = txid.clone().into(),
+            Err(e) => result["error"] = e.clone().into(),
+        }
+        result
+    }
+}
+
+/// Returns `None` for the header row.
+#[allow(clippy::type_complexity)]
+fn parse_row(
+    row: &str,
+    chain: &ChainType,
+) -> Result<Option<(String, address::Address, u64, Option<MemoBytes>)>, String> {
+    let fields = split_fields(row)?;
+    if fields.len() < 2 || fields.len() > 3 {
+        return Err(format!(
+            "expected address,amount[,memo] but found {} fields",
+            fields.len()
+        ));
+    }
+    if fields[0].eq_ignore_ascii_case("address") {
+        return Ok(None);
+    }
+    let address = fields[0].trim().to_string();
+    let decoded = address::Address::decode(chain, &address)
+        .or_else(|| decode_tex_address(chain, &address).map(address::Address::Transparent))
+        .ok_or_else(|| format!("invalid address '{address}' for {chain}"))?;
+    let amount = match fields[1].trim().parse::<u64>() {
+        Ok(0) => return Err("amount must be more than 0 zatoshis".to_string()),
+        Ok(amount) => amount,
+        Err(e) => return Err(format!("couldn't parse amount '{}': {}", fields[1], e)),
+    };
+    let memo = match fields.get(2).filter(|memo| !memo.is_empty()) {
+        Some(memo) => {
+            if matches!(decoded, address::Address::Transparent(_)) {
+                return Err(format!(
+                    "can't send a memo to the non-shielded address {address}"
+                ));
+            }
+            Some(utils::interpret_memo_string(memo.clone())?)
+        }
+        None => None,
+    };
+    Ok(Some((address, decoded, amount, memo)))
+}
+
+/// Splits a CSV row on commas. A quoted field may hold commas, and `""` for a quote.
+fn split_fields(row: &str) -> Result<Vec<String>, String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut chars = row.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', false) if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+            }
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', true) => quoted = false,
+            (',', false) => fields.push(std::mem::take(&mut field)),
+            (c, _) => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("unterminated quote".to_string());
+    }
+    fields.push(field);
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use zingoconfig::ChainType;
+
+    use super::{PaymentBatch, ReceiverPreference};
+
+    const SAPLING: &str =
+        "ztestsapling1x65nq4dgp0qfywgxcwk9n0fvm4fysmapgr2q00p85ju252h6l7mmxu2jg9cqqhtvzd69jwhgv8d";
+    const TRANSPARENT: &str = "tmS9nbexug7uT8x1cMTLP1ABEyKXpMjR5F1";
+
+    #[test]
+    fn parses_rows() {
+        let csv = format!(
+            "address,amount,memo\n\
+            # March payroll\n\
+            {SAPLING},100000,\"Thanks, and \"\"welcome\"\"\"\n\
+            \n\
+            {TRANSPARENT},25000\n"
+        );
+        let batch = PaymentBatch::parse_csv(&csv, &ChainType::Testnet).unwrap();
+        assert_eq!(batch.payments.len(), 2);
+        assert_eq!(batch.payments[0].line, 3);
+        assert_eq!(
+            batch.payments[0].memo.as_ref().unwrap().as_slice()[..21],
+            *b"Thanks, and \"welcome\""
+        );
+        assert_eq!(batch.payments[1].address, TRANSPARENT);
+        assert!(batch.payments[1].memo.is_none());
+        assert_eq!(batch.total(), 125_000);
+        assert_eq!(
+            batch
+                .transactions(1, ReceiverPreference::OrchardFirst)
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn reports_every_invalid_row() {
+        let csv = format!(
+            "{SAPLING},100000\n\
+            {SAPLING},lots\n\
+            notanaddress,5\n\
+            {TRANSPARENT},5,\"memo\"\n"
+        );
+        let e = PaymentBatch::parse_csv(&csv, &ChainType::Testnet)
+            .err()
+            .unwrap();
+        assert!(e.contains("line 2: couldn't parse amount"));
+        assert!(e.contains("line 3: invalid address"));
+        assert!(e.contains("line 4: can't send a memo"));
+        assert!(!e.contains("line 1"));
+    }
+
+    #[test]
+    fn transactions_are_split_by_actions() {
+        // Transparent outputs share logical actions with no inputs, sapling outputs are
+        // padded to two, and the change counts as an orchard output padded to two.
+        let csv = format!("{TRANSPARENT},1\n").repeat(6) + &format!("{SAPLING},1\n").repeat(4);
+        let batch = PaymentBatch::parse_csv(&csv, &ChainType::Testnet).unwrap();
+        let sizes = |max_actions| {
+            batch
+                .transactions(max_actions, ReceiverPreference::OrchardFirst)
+                .iter()
+                .map(|payments| payments.len())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(sizes(50), [10]);
+        // 4 transparent and 2 orchard, then 2 transparent, 2 sapling and 2 orchard
+        assert_eq!(sizes(6), [4, 4, 2]);
+        // A payment that exceeds the limit alone is still paid
+        assert_eq!(sizes(1), [1; 10]);
+    }
+}
@@ -17,6 +17,7 @@ use std::convert::TryFrom;
 use std::io::{self, Read, Write};
 use std::usize;
 use zcash_client_backend::proto::compact_formats::CompactBlock;
+use zcash_client_backend::proto::service::SubtreeRoot;
 use zcash_client_backend::serialization::shardtree::{read_shard, write_shard};
 use zcash_encoding::{Optional, Vector};
 use zcash_note_encryption::Domain;
@@ -198,6 +199,32 @@ impl WitnessTrees {
         }
     }
 
+    /// Inserts the roots of complete shards, numbered from `start_index`, so that notes in a
+    /// scanned shard can be witnessed without scanning the shards around it.
+    pub(crate) fn insert_subtree_roots<D: DomainWalletExt>(
+        &mut self,
+        start_index: u64,
+        roots: &[SubtreeRoot],
+    ) -> Result<(), String>
+    where
+        <D as Domain>::Note: PartialEq + Clone,
+        <D as Domain>::Recipient: traits::Recipient,
+    {
+        for (index, root) in (start_index..).zip(roots) {
+            let node = <D::WalletNote as super::notes::ShieldedNoteInterface>::Node::read(
+                &root.root_hash[..],
+            )
+            .map_err(|e| format!("invalid {} subtree root {index}: {e}", D::NAME))?;
+            D::get_shardtree_mut(self)
+                .insert(
+                    Address::from_parts(Level::from(MAX_SHARD_LEVEL), index),
+                    node,
+                )
+                .map_err(|e| format!("failed to insert {} subtree root {index}: {e}", D::NAME))?;
+        }
+        Ok(())
+    }
+
     pub fn clear(&mut self) {
         *self = Self::default()
     }
@@ -474,6 +501,8 @@ pub(crate) fn write_sapling_rseed<W: Write>(
 
 #[derive(Debug)]
 pub struct OutgoingTxData {
+    /// The receiver that was paid. For a unified address, this is the one receiver of
+    /// it that the transaction pays.
     pub to_address: String,
     pub value: u64,
     pub memo: Memo,
@@ -489,7 +518,9 @@ impl PartialEq for OutgoingTxData {
 }
 
 impl OutgoingTxData {
-    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+    /// Before version 24 of the transaction record, only the unified address was kept
+    /// when there was one, in place of the receiver.
+    pub fn read<R: Read>(mut reader: R, transaction_record_version: u64) -> io::Result<Self> {
         let address_len = reader.read_u64::<LittleEndian>()?;
         let mut address_bytes = vec![0; address_len as usize];
         reader.read_exact(&mut address_bytes)?;
@@ -510,28 +541,36 @@ impl OutgoingTxData {
             )),
         }?;
 
+        let recipient_ua = if transaction_record_version >= 24 {
+            Optional::read(&mut reader, |r| {
+                let ua_len = r.read_u64::<LittleEndian>()?;
+                let mut ua_bytes = vec![0; ua_len as usize];
+                r.read_exact(&mut ua_bytes)?;
+                String::from_utf8(ua_bytes)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            })?
+        } else {
+            None
+        };
+
         Ok(OutgoingTxData {
             to_address: address,
             value,
             memo,
-            recipient_ua: None,
+            recipient_ua,
         })
     }
 
     pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
         // Strings are written as len + utf8
-        match &self.recipient_ua {
-            None => {
-                writer.write_u64::<LittleEndian>(self.to_address.as_bytes().len() as u64)?;
-                writer.write_all(self.to_address.as_bytes())?;
-            }
-            Some(ua) => {
-                writer.write_u64::<LittleEndian>(ua.as_bytes().len() as u64)?;
-                writer.write_all(ua.as_bytes())?;
-            }
-        }
+        writer.write_u64::<LittleEndian>(self.to_address.as_bytes().len() as u64)?;
+        writer.write_all(self.to_address.as_bytes())?;
         writer.write_u64::<LittleEndian>(self.value)?;
-        writer.write_all(self.memo.encode().as_array())
+        writer.write_all(self.memo.encode().as_array())?;
+        Optional::write(&mut writer, self.recipient_ua.as_ref(), |w, ua| {
+            w.write_u64::<LittleEndian>(ua.as_bytes().len() as u64)?;
+            w.write_all(ua.as_bytes())
+        })
     }
 }
 
@@ -720,7 +759,7 @@ fn single_transparent_note_makes_is_incoming_true() {
     transaction_record.transparent_notes.push(transparent_note);
     assert!(transaction_record.is_incoming_transaction());
 }
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct SpendableSaplingNote {
     pub transaction_id: TxId,
     pub nullifier: sapling_crypto::Nullifier,
@@ -730,7 +769,7 @@ pub struct SpendableSaplingNote {
     pub extsk: Option<sapling_crypto::zip32::ExtendedSpendingKey>,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct SpendableOrchardNote {
     pub transaction_id: TxId,
     pub nullifier: orchard::note::Nullifier,
@@ -0,0 +1,120 @@
+//! ZIP-317 conventional fee computation.
+//! <https://zips.z.cash/zip-0317>
+use zcash_primitives::transaction::fees::zip317::{GRACE_ACTIONS, MARGINAL_FEE};
+
+/// The minimum number of outputs in a non-empty Sapling bundle, and the minimum number of
+/// actions in a non-empty Orchard bundle. The transaction builder pads bundles up to these.
+const MIN_SHIELDED_OUTPUTS: usize = 2;
+
+/// The shape of a transaction, as counted by ZIP-317.
+/// Counts are of the inputs and outputs that are requested of the builder, before padding.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TransactionShape {
+    pub transparent_inputs: usize,
+    pub transparent_outputs: usize,
+    pub sapling_spends: usize,
+    pub sapling_outputs: usize,
+    pub orchard_spends: usize,
+    pub orchard_outputs: usize,
+}
+
+impl TransactionShape {
+    /// The Sapling bundle's contribution to the logical action count, including the
+    /// dummy outputs the builder pads a non-empty bundle with.
+    pub fn sapling_logical_actions(&self) -> usize {
+        if self.sapling_spends == 0 && self.sapling_outputs == 0 {
+            0
+        } else {
+            self.sapling_spends
+                .max(self.sapling_outputs.max(MIN_SHIELDED_OUTPUTS))
+        }
+    }
+
+    /// The number of Orchard actions, including padding.
+    pub fn orchard_actions(&self) -> usize {
+        if self.orchard_spends == 0 && self.orchard_outputs == 0 {
+            0
+        } else {
+            self.orchard_spends
+                .max(self.orchard_outputs)
+                .max(MIN_SHIELDED_OUTPUTS)
+        }
+    }
+
+    /// max(transparent inputs, transparent outputs) + max(sapling spends, sapling outputs)
+    /// + orchard actions
+    pub fn logical_actions(&self) -> usize {
+        self.transparent_inputs.max(self.transparent_outputs)
+            + self.sapling_logical_actions()
+            + self.orchard_actions()
+    }
+
+    /// The ZIP-317 conventional fee, in zatoshis.
+    pub fn conventional_fee(&self) -> u64 {
+        u64::from(MARGINAL_FEE) * self.logical_actions().max(GRACE_ACTIONS) as u64
+    }
+}
+
+/// Whether a transaction paying `fee`, and returning `change`, pays its ZIP-317
+/// `required_fee`. One without change may pay up to [`MARGINAL_FEE`] more: the leftover
+/// would need a change output that costs about as much as it is worth, so it goes to the fee.
+pub fn pays_required_fee(fee: u64, change: Option<u64>, required_fee: u64) -> bool {
+    match change {
+        Some(_) => fee == required_fee,
+        None => (required_fee..=required_fee + u64::from(MARGINAL_FEE)).contains(&fee),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{pays_required_fee, TransactionShape};
+
+    #[test]
+    fn grace_actions_floor_the_fee() {
+        let one_orchard_output = TransactionShape {
+            orchard_outputs: 1,
+            ..Default::default()
+        };
+        assert_eq!(one_orchard_output.orchard_actions(), 2);
+        assert_eq!(one_orchard_output.conventional_fee(), 10_000);
+        assert_eq!(TransactionShape::default().conventional_fee(), 10_000);
+    }
+
+    #[test]
+    fn multi_recipient_payout() {
+        // One orchard note spent to five orchard recipients plus change
+        let payout = TransactionShape {
+            orchard_spends: 1,
+            orchard_outputs: 6,
+            ..Default::default()
+        };
+        assert_eq!(payout.logical_actions(), 6);
+        assert_eq!(payout.conventional_fee(), 30_000);
+    }
+
+    #[test]
+    fn shielding_counts_every_pool() {
+        // Three utxos and one sapling note shielded to orchard, with orchard change
+        let shield = TransactionShape {
+            transparent_inputs: 3,
+            sapling_spends: 1,
+            orchard_outputs: 2,
+            ..Default::default()
+        };
+        assert_eq!(shield.sapling_logical_actions(), 2);
+        assert_eq!(shield.logical_actions(), 7);
+        assert_eq!(shield.conventional_fee(), 35_000);
+    }
+
+    #[test]
+    fn leftover_below_the_marginal_fee_goes_to_the_fee() {
+        // Dropping the change output of a 3 action transaction lowers its fee to 10_000,
+        // leaving 5_000 that a change output would cost as much as
+        assert!(pays_required_fee(15_000, None, 10_000));
+        assert!(!pays_required_fee(15_001, None, 10_000));
+        assert!(!pays_required_fee(9_999, None, 10_000));
+        // With change, any leftover is returned
+        assert!(pays_required_fee(10_000, Some(5_000), 10_000));
+        assert!(!pays_required_fee(15_000, Some(0), 10_000));
+    }
+}
@@ -80,10 +80,11 @@ pub fn get_zaddr_from_bip39seed(
 }
 
 pub fn is_shielded_address(addr: &str, config: &ZingoConfig) -> bool {
-    matches!(
-        address::Address::decode(&config.chain, addr),
-        Some(address::Address::Sapling(_)) | Some(address::Address::Unified(_))
-    )
+    match address::Address::decode(&config.chain, addr) {
+        Some(address::Address::Sapling(_)) => true,
+        Some(address::Address::Unified(ua)) => ua.orchard().is_some() || ua.sapling().is_some(),
+        _ => false,
+    }
 }
 
 /// STATIC METHODS
@@ -19,7 +19,7 @@ use zcash_primitives::zip32::AccountId;
 use zcash_primitives::{legacy::TransparentAddress, zip32::DiversifierIndex};
 use zingoconfig::ZingoConfig;
 
-use crate::wallet::traits::ReadableWriteable;
+use crate::wallet::{traits::ReadableWriteable, Pool};
 
 use super::{
     extended_transparent::{ExtendedPrivKey, ExtendedPubKey, KeyIndex},
@@ -70,6 +70,9 @@ pub struct WalletCapability {
 
     transparent_child_keys: append_only_vec::AppendOnlyVec<(usize, secp256k1::SecretKey)>,
     addresses: append_only_vec::AppendOnlyVec<UnifiedAddress>,
+    // Transparent addresses handed out on their own, each with the number of unified
+    // addresses made before it. They take transparent child indexes in turn with those.
+    transparent_only_addresses: append_only_vec::AppendOnlyVec<(usize, TransparentAddress)>,
     // Not all diversifier indexes produce valid sapling addresses.
     // Because of this, the index isn't necessarily equal to addresses.len()
     addresses_write_lock: AtomicBool,
@@ -82,6 +85,7 @@ impl Default for WalletCapability {
             transparent: Capability::None,
             transparent_child_keys: AppendOnlyVec::new(),
             addresses: AppendOnlyVec::new(),
+            transparent_only_addresses: AppendOnlyVec::new(),
             addresses_write_lock: AtomicBool::new(false),
         }
     }
@@ -152,6 +156,25 @@ impl WalletCapability {
         &self.addresses
     }
 
+    /// The transparent receivers of the unified addresses, then the transparent addresses
+    /// handed out on their own.
+    pub fn transparent_receivers(&self) -> Vec<TransparentAddress> {
+        self.addresses
+            .iter()
+            .filter_map(|ua| ua.transparent().copied())
+            .chain(
+                self.transparent_only_addresses
+                    .iter()
+                    .map(|(_, taddr)| *taddr),
+            )
+            .collect()
+    }
+
+    /// The transparent child index the next address takes.
+    pub(crate) fn next_transparent_index(&self) -> u32 {
+        (self.addresses.len() + self.transparent_only_addresses.len()) as u32
+    }
+
     pub fn transparent_child_keys(
         &self,
     ) -> Result<&AppendOnlyVec<(usize, secp256k1::SecretKey)>, String> {
@@ -182,6 +205,73 @@ impl WalletCapability {
         Ufvk::try_from_items(vec![o_fvk, s_fvk, t_fvk])
     }
 
+    /// A pool that both `self` and `other` view with the same full viewing key, so that
+    /// both would find the same funds in it. Keys are compared pool by pool, as two UFVKs
+    /// holding a different set of the same keys encode differently.
+    pub(crate) fn shared_viewing_key(&self, other: &WalletCapability) -> Option<Pool> {
+        let orchard = |wc: &WalletCapability| {
+            orchard::keys::FullViewingKey::try_from(wc)
+                .ok()
+                .map(|fvk| fvk.to_bytes())
+        };
+        let sapling = |wc: &WalletCapability| {
+            sapling_crypto::zip32::DiversifiableFullViewingKey::try_from(wc)
+                .ok()
+                .map(|dfvk| dfvk.to_bytes())
+        };
+        let transparent = |wc: &WalletCapability| ExtendedPubKey::try_from(wc).ok();
+        if orchard(self).is_some() && orchard(self) == orchard(other) {
+            Some(Pool::Orchard)
+        } else if sapling(self).is_some() && sapling(self) == sapling(other) {
+            Some(Pool::Sapling)
+        } else if transparent(self).is_some() && transparent(self) == transparent(other) {
+            Some(Pool::Transparent)
+        } else {
+            None
+        }
+    }
+
+    /// The transparent child key at `index`: its secret key, if the wallet can spend, and its
+    /// public key. `None` without a transparent key.
+    fn transparent_child_key(
+        &self,
+        index: u32,
+    ) -> Result<Option<(Option<secp256k1::SecretKey>, secp256k1::PublicKey)>, String> {
+        let child_index = KeyIndex::from_index(index)
+            .map_err(|e| format!("Invalid transparent child index {index}: {e}"))?;
+        match &self.transparent {
+            Capability::Spend(ext_sk) => {
+                let child_sk = ext_sk
+                    .derive_private_key(child_index)
+                    .map_err(|e| format!("Transparent private key derivation failed: {e}"))?
+                    .private_key;
+                let secp = secp256k1::Secp256k1::new();
+                let child_pk = secp256k1::PublicKey::from_secret_key(&secp, &child_sk);
+                Ok(Some((Some(child_sk), child_pk)))
+            }
+            Capability::View(ext_pk) => {
+                let child_pk = ext_pk
+                    .derive_public_key(child_index)
+                    .map_err(|e| format!("Transparent public key derivation failed: {e}"))?
+                    .public_key;
+                Ok(Some((None, child_pk)))
+            }
+            Capability::None => Ok(None),
+        }
+    }
+
+    /// The transparent address at `index`, whether or not the wallet has an address with it
+    /// yet. `None` without a transparent key.
+    pub(crate) fn transparent_address_at(
+        &self,
+        index: u32,
+    ) -> Result<Option<TransparentAddress>, String> {
+        Ok(self.transparent_child_key(index)?.map(|(_, child_pk)| {
+            #[allow(deprecated)]
+            zcash_primitives::legacy::keys::pubkey_to_address(&child_pk)
+        }))
+    }
+
     pub fn new_address(
         &self,
         desired_receivers: ReceiverSelection,
@@ -238,35 +328,20 @@ impl WalletCapability {
         };
 
         let transparent_receiver = if desired_receivers.transparent {
-            let child_index = KeyIndex::from_index(self.addresses.len() as u32).unwrap();
-            match &self.transparent {
-                Capability::Spend(ext_sk) => {
-                    let child_sk = match ext_sk.derive_private_key(child_index) {
-                        Err(e) => {
-                            self.addresses_write_lock
-                                .swap(false, atomic::Ordering::Release);
-                            return Err(format!("Transparent private key derivation failed: {e}"));
-                        }
-                        Ok(res) => res.private_key,
-                    };
-                    let secp = secp256k1::Secp256k1::new();
-                    let child_pk = secp256k1::PublicKey::from_secret_key(&secp, &child_sk);
-                    self.transparent_child_keys
-                        .push((self.addresses.len(), child_sk));
-                    Some(child_pk)
-                }
-                Capability::View(ext_pk) => {
-                    let child_pk = match ext_pk.derive_public_key(child_index) {
-                        Err(e) => {
-                            self.addresses_write_lock
-                                .swap(false, atomic::Ordering::Release);
-                            return Err(format!("Transparent public key derivation failed: {e}"));
-                        }
-                        Ok(res) => res.public_key,
-                    };
-                    Some(child_pk)
+            let child_index = self.next_transparent_index();
+            match self.transparent_child_key(child_index) {
+                Ok(child_key) => child_key.map(|(child_sk, child_pk)| {
+                    if let Some(child_sk) = child_sk {
+                        self.transparent_child_keys
+                            .push((child_index as usize, child_sk));
+                    }
+                    child_pk
+                }),
+                Err(e) => {
+                    self.addresses_write_lock
+                        .swap(false, atomic::Ordering::Release);
+                    return Err(e);
                 }
-                Capability::None => None,
             }
         } else {
             None
@@ -300,30 +375,63 @@ impl WalletCapability {
         Ok(ua)
     }
 
+    /// Derives a transparent address of its own, from the next transparent child key,
+    /// without the shielded receivers of a unified address.
+    pub fn new_transparent_address(&self) -> Result<TransparentAddress, String> {
+        if !self.transparent.can_view() {
+            return Err("The wallet is not capable of producing desired receivers.".to_string());
+        }
+        if self
+            .addresses_write_lock
+            .swap(true, atomic::Ordering::Acquire)
+        {
+            return Err("addresses_write_lock collision!".to_string());
+        }
+        let child_index = self.next_transparent_index();
+        let child_key = self.transparent_child_key(child_index);
+        let taddr = match child_key {
+            Ok(Some((child_sk, child_pk))) => {
+                if let Some(child_sk) = child_sk {
+                    self.transparent_child_keys
+                        .push((child_index as usize, child_sk));
+                }
+                #[allow(deprecated)]
+                zcash_primitives::legacy::keys::pubkey_to_address(&child_pk)
+            }
+            Ok(None) => unreachable!("the transparent key was checked"),
+            Err(e) => {
+                self.addresses_write_lock
+                    .swap(false, atomic::Ordering::Release);
+                return Err(e);
+            }
+        };
+        self.transparent_only_addresses
+            .push((self.addresses.len(), taddr));
+        self.addresses_write_lock
+            .swap(false, atomic::Ordering::Release);
+        Ok(taddr)
+    }
+
     pub fn get_taddr_to_secretkey_map(
         &self,
         config: &ZingoConfig,
     ) -> Result<HashMap<String, secp256k1::SecretKey>, String> {
         if self.transparent.can_spend() {
+            let secp = secp256k1::Secp256k1::new();
             Ok(self
-                .addresses
+                .transparent_child_keys
                 .iter()
-                .enumerate()
-                .filter_map(|(i, ua)| {
-                    ua.transparent().zip(
-                        self.transparent_child_keys
-                            .iter()
-                            .find(|(index, _key)| i == *index),
-                    )
-                })
-                .map(|(taddr, key)| {
+                .map(|(_index, key)| {
+                    let pubkey = secp256k1::PublicKey::from_secret_key(&secp, key);
+                    #[allow(deprecated)]
+                    let taddr = zcash_primitives::legacy::keys::pubkey_to_address(&pubkey);
                     let hash = match taddr {
                         TransparentAddress::PublicKey(hash) => hash,
                         TransparentAddress::Script(hash) => hash,
                     };
                     (
                         hash.to_base58check(&config.base58_pubkey_address(), &[]),
-                        key.1,
+                        *key,
                     )
                 })
                 .collect())
@@ -432,22 +540,20 @@ impl WalletCapability {
     }
 
     pub(crate) fn get_all_taddrs(&self, config: &ZingoConfig) -> HashSet<String> {
-        self.addresses
-            .iter()
-            .filter_map(|address| {
-                address.transparent().and_then(|transparent_receiver| {
-                    if let zcash_primitives::legacy::TransparentAddress::PublicKey(hash) =
-                        transparent_receiver
-                    {
-                        Some(super::ToBase58Check::to_base58check(
-                            hash.as_slice(),
-                            &config.base58_pubkey_address(),
-                            &[],
-                        ))
-                    } else {
-                        None
-                    }
-                })
+        self.transparent_receivers()
+            .into_iter()
+            .filter_map(|transparent_receiver| {
+                if let zcash_primitives::legacy::TransparentAddress::PublicKey(hash) =
+                    transparent_receiver
+                {
+                    Some(super::ToBase58Check::to_base58check(
+                        hash.as_slice(),
+                        &config.base58_pubkey_address(),
+                        &[],
+                    ))
+                } else {
+                    None
+                }
             })
             .collect()
     }
@@ -464,8 +570,10 @@ impl WalletCapability {
     }
 
     //TODO: NAME?????!!
+    /// View-only wallets that see both shielded pools keep witness trees too, so that they
+    /// can create unsigned transactions for a spending wallet to sign.
     pub fn get_trees_witness_trees(&self) -> Option<crate::wallet::data::WitnessTrees> {
-        if self.can_spend_from_all_pools() {
+        if self.can_spend_from_all_pools() || (self.orchard.can_view() && self.sapling.can_view()) {
             Some(crate::wallet::data::WitnessTrees::default())
         } else {
             None
@@ -533,8 +641,15 @@ where
     }
 }
 
+/// The receivers a transparent address handed out on its own is written as
+const TRANSPARENT_ONLY: ReceiverSelection = ReceiverSelection {
+    orchard: false,
+    sapling: false,
+    transparent: true,
+};
+
 impl ReadableWriteable<()> for WalletCapability {
-    const VERSION: u8 = 2;
+    const VERSION: u8 = 3;
 
     fn read<R: Read>(mut reader: R, _input: ()) -> io::Result<Self> {
         let version = Self::get_version(&mut reader)?;
@@ -552,7 +667,8 @@ impl ReadableWriteable<()> for WalletCapability {
                     ..Default::default()
                 }
             }
-            2 => Self {
+            // version 3 adds transparent-only addresses to the receiver selections
+            2 | 3 => Self {
                 orchard: Capability::read(&mut reader, ())?,
                 sapling: Capability::read(&mut reader, ())?,
                 transparent: Capability::read(&mut reader, ())?,
@@ -567,8 +683,12 @@ impl ReadableWriteable<()> for WalletCapability {
         };
         let receiver_selections = Vector::read(reader, |r| ReceiverSelection::read(r, ()))?;
         for rs in receiver_selections {
-            wc.new_address(rs)
-                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            if rs == TRANSPARENT_ONLY {
+                wc.new_transparent_address().map(|_| ())
+            } else {
+                wc.new_address(rs).map(|_| ())
+            }
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
         }
         Ok(wc)
     }
@@ -578,18 +698,26 @@ impl ReadableWriteable<()> for WalletCapability {
         self.orchard.write(&mut writer)?;
         self.sapling.write(&mut writer)?;
         self.transparent.write(&mut writer)?;
-        Vector::write(
-            &mut writer,
-            &self.addresses.iter().collect::<Vec<_>>(),
-            |w, address| {
-                ReceiverSelection {
+        // In the order the addresses were made, so that reading derives the same ones
+        let mut receiver_selections = vec![];
+        for (position, address) in self.addresses.iter().map(Some).chain([None]).enumerate() {
+            receiver_selections.extend(
+                self.transparent_only_addresses
+                    .iter()
+                    .filter(|(before, _)| *before == position)
+                    .map(|_| TRANSPARENT_ONLY),
+            );
+            if let Some(address) = address {
+                receiver_selections.push(ReceiverSelection {
                     orchard: address.orchard().is_some(),
                     sapling: address.sapling().is_some(),
                     transparent: address.transparent().is_some(),
-                }
-                .write(w)
-            },
-        )
+                });
+            }
+        }
+        Vector::write(&mut writer, &receiver_selections, |w, receivers| {
+            receivers.write(w)
+        })
     }
 }
 
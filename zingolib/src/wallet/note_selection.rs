@@ -0,0 +1,243 @@
+//! Strategies for choosing which notes of a pool cover a target value.
+//! Strategies work on note values alone, and return indices into the candidate list.
+
+/// Branch and bound gives up after visiting this many subsets.
+const EXACT_MATCH_MAX_TRIES: usize = 100_000;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NoteSelectionStrategy {
+    /// Spend the largest notes first. Fewest spends for the target.
+    #[default]
+    LargestFirst,
+    /// Spend the smallest notes first. Defragments the wallet at the cost of more spends.
+    SmallestFirst,
+    /// Search for notes that sum to exactly the target, so that no change is needed.
+    /// Falls back to largest-first when there is no exact match.
+    ExactMatch,
+    /// Spend as few notes as largest-first, then use any spends that ZIP-317 does not
+    /// charge for (because the bundle is padded up to its output count) on the smallest
+    /// remaining notes.
+    FeeMinimizing,
+}
+
+impl std::str::FromStr for NoteSelectionStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "largest_first" => Ok(NoteSelectionStrategy::LargestFirst),
+            "smallest_first" => Ok(NoteSelectionStrategy::SmallestFirst),
+            "exact_match" => Ok(NoteSelectionStrategy::ExactMatch),
+            "fee_minimizing" => Ok(NoteSelectionStrategy::FeeMinimizing),
+            other => Err(format!(
+                "Unknown note selection strategy '{other}'. Expected one of largest_first, \
+                smallest_first, exact_match, fee_minimizing"
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for NoteSelectionStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NoteSelectionStrategy::LargestFirst => write!(f, "largest_first"),
+            NoteSelectionStrategy::SmallestFirst => write!(f, "smallest_first"),
+            NoteSelectionStrategy::ExactMatch => write!(f, "exact_match"),
+            NoteSelectionStrategy::FeeMinimizing => write!(f, "fee_minimizing"),
+        }
+    }
+}
+
+impl NoteSelectionStrategy {
+    pub(crate) fn as_u8(&self) -> u8 {
+        match self {
+            NoteSelectionStrategy::LargestFirst => 0,
+            NoteSelectionStrategy::SmallestFirst => 1,
+            NoteSelectionStrategy::ExactMatch => 2,
+            NoteSelectionStrategy::FeeMinimizing => 3,
+        }
+    }
+
+    pub(crate) fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(NoteSelectionStrategy::LargestFirst),
+            1 => Some(NoteSelectionStrategy::SmallestFirst),
+            2 => Some(NoteSelectionStrategy::ExactMatch),
+            3 => Some(NoteSelectionStrategy::FeeMinimizing),
+            _ => None,
+        }
+    }
+
+    /// Chooses notes from `values` to cover `target`. `free_spends` is how many spends the
+    /// pool's bundle can hold without adding actions.
+    /// If the candidates can't cover the target, every candidate is returned.
+    pub fn select(&self, values: &[u64], target: u64, free_spends: usize) -> Vec<usize> {
+        let mut descending = (0..values.len()).collect::<Vec<_>>();
+        descending.sort_by(|a, b| values[*b].cmp(&values[*a]));
+        match self {
+            NoteSelectionStrategy::LargestFirst => take_until(values, descending, target),
+            NoteSelectionStrategy::SmallestFirst => {
+                descending.reverse();
+                take_until(values, descending, target)
+            }
+            NoteSelectionStrategy::ExactMatch => exact_match(values, &descending, target)
+                .unwrap_or_else(|| take_until(values, descending, target)),
+            NoteSelectionStrategy::FeeMinimizing => {
+                let mut selected = take_until(values, descending.clone(), target);
+                let extra = free_spends.saturating_sub(selected.len());
+                selected.extend(
+                    descending
+                        .iter()
+                        .rev()
+                        .filter(|index| !selected.contains(index))
+                        .take(extra)
+                        .copied()
+                        .collect::<Vec<_>>(),
+                );
+                selected
+            }
+        }
+    }
+}
+
+fn take_until(values: &[u64], order: Vec<usize>, target: u64) -> Vec<usize> {
+    let mut running_total = 0;
+    order
+        .into_iter()
+        .take_while(|index| {
+            let needed = running_total < target;
+            running_total += values[*index];
+            needed
+        })
+        .collect()
+}
+
+/// Depth first search over include/exclude decisions, largest notes first, pruning
+/// branches that overshoot or can no longer reach the target.
+fn exact_match(values: &[u64], descending: &[usize], target: u64) -> Option<Vec<usize>> {
+    if target == 0 {
+        return None;
+    }
+    let mut remaining = vec![0; descending.len() + 1];
+    for depth in (0..descending.len()).rev() {
+        remaining[depth] = remaining[depth + 1] + values[descending[depth]];
+    }
+    let mut tries = 0;
+    let mut selected = vec![];
+    if search(
+        values,
+        descending,
+        &remaining,
+        0,
+        target,
+        &mut selected,
+        &mut tries,
+    ) {
+        Some(selected)
+    } else {
+        None
+    }
+}
+
+fn search(
+    values: &[u64],
+    descending: &[usize],
+    remaining: &[u64],
+    depth: usize,
+    still_needed: u64,
+    selected: &mut Vec<usize>,
+    tries: &mut usize,
+) -> bool {
+    if still_needed == 0 {
+        return true;
+    }
+    *tries += 1;
+    if depth == descending.len()
+        || remaining[depth] < still_needed
+        || *tries > EXACT_MATCH_MAX_TRIES
+    {
+        return false;
+    }
+    let index = descending[depth];
+    if values[index] <= still_needed {
+        selected.push(index);
+        if search(
+            values,
+            descending,
+            remaining,
+            depth + 1,
+            still_needed - values[index],
+            selected,
+            tries,
+        ) {
+            return true;
+        }
+        selected.pop();
+    }
+    search(
+        values,
+        descending,
+        remaining,
+        depth + 1,
+        still_needed,
+        selected,
+        tries,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NoteSelectionStrategy;
+
+    const VALUES: [u64; 5] = [40_000, 10_000, 70_000, 20_000, 5_000];
+
+    fn sum(indices: &[usize]) -> u64 {
+        indices.iter().map(|index| VALUES[*index]).sum()
+    }
+
+    #[test]
+    fn largest_first_spends_fewest_notes() {
+        let selected = NoteSelectionStrategy::LargestFirst.select(&VALUES, 80_000, 0);
+        assert_eq!(selected, vec![2, 0]);
+    }
+
+    #[test]
+    fn smallest_first_defragments() {
+        let selected = NoteSelectionStrategy::SmallestFirst.select(&VALUES, 30_000, 0);
+        assert_eq!(selected, vec![4, 1, 3]);
+    }
+
+    #[test]
+    fn exact_match_avoids_change() {
+        let selected = NoteSelectionStrategy::ExactMatch.select(&VALUES, 75_000, 0);
+        assert_eq!(sum(&selected), 75_000);
+        // No exact match, so fall back to largest first
+        let selected = NoteSelectionStrategy::ExactMatch.select(&VALUES, 71_000, 0);
+        assert_eq!(selected, vec![2, 0]);
+    }
+
+    #[test]
+    fn fee_minimizing_fills_free_spends_with_small_notes() {
+        let selected = NoteSelectionStrategy::FeeMinimizing.select(&VALUES, 60_000, 2);
+        assert_eq!(selected, vec![2, 4]);
+        let selected = NoteSelectionStrategy::FeeMinimizing.select(&VALUES, 60_000, 0);
+        assert_eq!(selected, vec![2]);
+    }
+
+    #[test]
+    fn insufficient_candidates_are_all_returned() {
+        for strategy in [
+            NoteSelectionStrategy::LargestFirst,
+            NoteSelectionStrategy::SmallestFirst,
+            NoteSelectionStrategy::ExactMatch,
+            NoteSelectionStrategy::FeeMinimizing,
+        ] {
+            let selected = strategy.select(&VALUES, 1_000_000, 2);
+            assert_eq!(selected.len(), VALUES.len());
+            assert_eq!(
+                NoteSelectionStrategy::from_u8(strategy.as_u8()),
+                Some(strategy)
+            );
+        }
+    }
+}
@@ -0,0 +1,387 @@
+//! ZIP-321 payment request URIs.
+//! <https://zips.z.cash/zip-0321>
+use std::collections::BTreeMap;
+
+use json::{object, JsonValue};
+use zcash_client_backend::address;
+use zcash_primitives::memo::{Memo, MemoBytes};
+use zingoconfig::ChainType;
+
+use crate::error::{ZingoLibError, ZingoLibResult};
+
+const SCHEME: &str = "zcash:";
+const COIN: u64 = 100_000_000;
+const MAX_MONEY: u64 = 21_000_000 * COIN;
+
+/// One payment of a request. Amount and memo are optional in a URI, so the paying
+/// wallet can fill them in.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Payment {
+    pub address: String,
+    /// In zatoshis
+    pub amount: Option<u64>,
+    pub memo: Option<MemoBytes>,
+    pub label: Option<String>,
+    pub message: Option<String>,
+}
+
+impl Payment {
+    pub fn to_json(&self) -> JsonValue {
+        object! {
+            "address" => self.address.clone(),
+            "amount" => self.amount,
+            "memo" => self.memo.clone().and_then(|memo| match Memo::try_from(memo) {
+                Ok(Memo::Text(text)) => Some(text.to_string()),
+                _ => None,
+            }),
+            "label" => self.label.clone(),
+            "message" => self.message.clone(),
+        }
+    }
+}
+
+/// The payments of a `zcash:` URI, in payment index order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PaymentRequest {
+    pub payments: Vec<Payment>,
+}
+
+#[derive(Default)]
+struct ParsedParams {
+    address: Option<String>,
+    amount: Option<String>,
+    memo: Option<String>,
+    label: Option<String>,
+    message: Option<String>,
+}
+
+impl PaymentRequest {
+    pub fn parse(uri: &str, chain: &ChainType) -> ZingoLibResult<Self> {
+        let invalid = |reason: &str| ZingoLibError::InvalidPaymentUri(reason.to_string());
+        if !uri
+            .get(..SCHEME.len())
+            .is_some_and(|scheme| scheme.eq_ignore_ascii_case(SCHEME))
+        {
+            return Err(invalid("it does not start with zcash:"));
+        }
+        let (path, query) = match uri[SCHEME.len()..].split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (&uri[SCHEME.len()..], None),
+        };
+
+        let mut params: BTreeMap<usize, ParsedParams> = BTreeMap::new();
+        if !path.is_empty() {
+            params.entry(0).or_default().address = Some(percent_decode(path)?);
+        }
+        for param in query.into_iter().flat_map(|query| query.split('&')) {
+            let (name, value) = param
+                .split_once('=')
+                .ok_or_else(|| invalid(&format!("parameter '{param}' has no value")))?;
+            let (name, index) = match name.split_once('.') {
+                Some((name, index)) => (name, parse_index(index)?),
+                None => (name, 0),
+            };
+            let value = percent_decode(value)?;
+            let payment = params.entry(index).or_default();
+            let field = match name {
+                "address" => &mut payment.address,
+                "amount" => &mut payment.amount,
+                "memo" => &mut payment.memo,
+                "label" => &mut payment.label,
+                "message" => &mut payment.message,
+                required if required.starts_with("req-") => {
+                    return Err(ZingoLibError::PaymentUriUnsupportedRequirement(
+                        required.to_string(),
+                    ))
+                }
+                // Unknown parameters that are not required may be ignored
+                _ => continue,
+            };
+            if field.replace(value).is_some() {
+                return Err(ZingoLibError::PaymentUriDuplicateParameter(
+                    param.to_string(),
+                ));
+            }
+        }
+        if params.is_empty() {
+            return Err(invalid("it requests no payments"));
+        }
+
+        let payments = params
+            .into_iter()
+            .map(|(index, params)| {
+                let address = params
+                    .address
+                    .ok_or(ZingoLibError::PaymentUriMissingAddress(index))?;
+                let decoded = address::Address::decode(chain, &address)
+                    .ok_or_else(|| ZingoLibError::PaymentUriAddress(index, address.clone()))?;
+                let amount = params
+                    .amount
+                    .map(|amount| {
+                        parse_amount(&amount).ok_or(ZingoLibError::PaymentUriAmount(index, amount))
+                    })
+                    .transpose()?;
+                let memo = params
+                    .memo
+                    .map(|memo| {
+                        if let address::Address::Transparent(_) = decoded {
+                            return Err(ZingoLibError::PaymentUriMemo(
+                                index,
+                                "transparent addresses can't receive memos".to_string(),
+                            ));
+                        }
+                        base64::decode_config(&memo, base64::URL_SAFE_NO_PAD)
+                            .map_err(|e| e.to_string())
+                            .and_then(|bytes| {
+                                MemoBytes::from_bytes(&bytes)
+                                    .map_err(|_| "it is longer than 512 bytes".to_string())
+                            })
+                            .map_err(|e| ZingoLibError::PaymentUriMemo(index, e))
+                    })
+                    .transpose()?;
+                Ok(Payment {
+                    address,
+                    amount,
+                    memo,
+                    label: params.label,
+                    message: params.message,
+                })
+            })
+            .collect::<ZingoLibResult<Vec<_>>>()?;
+        Ok(PaymentRequest { payments })
+    }
+
+    /// Encodes the request, with the first payment's address as the URI path.
+    pub fn to_uri(&self) -> String {
+        let mut uri = SCHEME.to_string();
+        let mut params = vec![];
+        for (index, payment) in self.payments.iter().enumerate() {
+            let suffix = if index == 0 {
+                uri.push_str(&percent_encode(&payment.address));
+                String::new()
+            } else {
+                params.push(format!(
+                    "address.{index}={}",
+                    percent_encode(&payment.address)
+                ));
+                format!(".{index}")
+            };
+            if let Some(amount) = payment.amount {
+                params.push(format!("amount{suffix}={}", format_amount(amount)));
+            }
+            if let Some(memo) = &payment.memo {
+                let bytes = memo.as_slice();
+                let length = bytes
+                    .iter()
+                    .rposition(|byte| *byte != 0)
+                    .map_or(0, |i| i + 1);
+                params.push(format!(
+                    "memo{suffix}={}",
+                    base64::encode_config(&bytes[..length], base64::URL_SAFE_NO_PAD)
+                ));
+            }
+            if let Some(label) = &payment.label {
+                params.push(format!("label{suffix}={}", percent_encode(label)));
+            }
+            if let Some(message) = &payment.message {
+                params.push(format!("message{suffix}={}", percent_encode(message)));
+            }
+        }
+        if !params.is_empty() {
+            uri.push('?');
+            uri.push_str(&params.join("&"));
+        }
+        uri
+    }
+
+    /// The payments as the `(address, value, memo)` tuples that sends take.
+    /// Every payment must have an amount.
+    pub fn send_tuples(&self) -> ZingoLibResult<Vec<(&str, u64, Option<MemoBytes>)>> {
+        self.payments
+            .iter()
+            .enumerate()
+            .map(|(index, payment)| {
+                payment
+                    .amount
+                    .map(|amount| (payment.address.as_str(), amount, payment.memo.clone()))
+                    .ok_or(ZingoLibError::PaymentUriMissingAmount(index))
+            })
+            .collect()
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        object! {
+            "payments" => self.payments.iter().map(Payment::to_json).collect::<Vec<_>>(),
+            "total" => self.payments.iter().map(|payment| payment.amount).sum::<Option<u64>>(),
+        }
+    }
+}
+
+/// A payment index is 1 to 4 digits, without leading zeros.
+fn parse_index(index: &str) -> ZingoLibResult<usize> {
+    if index.is_empty()
+        || index.len() > 4
+        || index.starts_with('0')
+        || !index.bytes().all(|byte| byte.is_ascii_digit())
+    {
+        return Err(ZingoLibError::InvalidPaymentUri(format!(
+            "'{index}' is not a valid payment index"
+        )));
+    }
+    Ok(index.parse().expect("at most 4 digits"))
+}
+
+/// Parses a ZEC amount with up to 8 decimal places into zatoshis.
+fn parse_amount(amount: &str) -> Option<u64> {
+    let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+    if whole.is_empty()
+        || fraction.len() > 8
+        || !whole
+            .bytes()
+            .chain(fraction.bytes())
+            .all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+    let zats = whole
+        .parse::<u64>()
+        .ok()?
+        .checked_mul(COIN)?
+        .checked_add(format!("{fraction:0<8}").parse::<u64>().ok()?)?;
+    (zats <= MAX_MONEY).then_some(zats)
+}
+
+fn format_amount(zats: u64) -> String {
+    let fraction = zats % COIN;
+    if fraction == 0 {
+        (zats / COIN).to_string()
+    } else {
+        format!("{}.{:08}", zats / COIN, fraction)
+            .trim_end_matches('0')
+            .to_string()
+    }
+}
+
+/// Characters that may appear unescaped in a ZIP-321 parameter value
+fn is_qchar(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"-._~!$'()*+,;:@".contains(&byte)
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| {
+            if is_qchar(byte) {
+                (byte as char).to_string()
+            } else {
+                format!("%{byte:02X}")
+            }
+        })
+        .collect()
+}
+
+fn percent_decode(value: &str) -> ZingoLibResult<String> {
+    let invalid = || ZingoLibError::InvalidPaymentUri(format!("'{value}' is badly escaped"));
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.bytes();
+    while let Some(byte) = rest.next() {
+        if byte == b'%' {
+            let hex = [
+                rest.next().ok_or_else(invalid)?,
+                rest.next().ok_or_else(invalid)?,
+            ];
+            let hex = std::str::from_utf8(&hex).map_err(|_| invalid())?;
+            bytes.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())?);
+        } else if is_qchar(byte) {
+            bytes.push(byte);
+        } else {
+            return Err(invalid());
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| invalid())
+}
+
+#[cfg(test)]
+mod tests {
+    use zingoconfig::ChainType;
+
+    use super::{format_amount, parse_amount, PaymentRequest};
+    use crate::error::ZingoLibError;
+
+    const SAPLING: &str =
+        "ztestsapling1x65nq4dgp0qfywgxcwk9n0fvm4fysmapgr2q00p85ju252h6l7mmxu2jg9cqqhtvzd69jwhgv8d";
+    const TRANSPARENT: &str = "tmS9nbexug7uT8x1cMTLP1ABEyKXpMjR5F1";
+
+    #[test]
+    fn amounts() {
+        assert_eq!(parse_amount("1"), Some(100_000_000));
+        assert_eq!(parse_amount("0.0001"), Some(10_000));
+        assert_eq!(parse_amount("21000000"), Some(2_100_000_000_000_000));
+        assert_eq!(parse_amount("21000000.00000001"), None);
+        assert_eq!(parse_amount("0.000000001"), None);
+        assert_eq!(parse_amount(".5"), None);
+        assert_eq!(parse_amount("-1"), None);
+        assert_eq!(format_amount(123_450_000), "1.2345");
+        assert_eq!(format_amount(200_000_000), "2");
+    }
+
+    #[test]
+    fn multiple_payments() {
+        let uri = format!(
+            "zcash:?address={SAPLING}&amount=1.5&memo=VGhpcyBpcyBhIHNpbXBsZSBtZW1vLg&message=Thank%20you\
+            &address.1={TRANSPARENT}&amount.1=0.001&label.1=Shop"
+        );
+        let request = PaymentRequest::parse(&uri, &ChainType::Testnet).unwrap();
+        assert_eq!(request.payments.len(), 2);
+        assert_eq!(request.payments[0].amount, Some(150_000_000));
+        assert_eq!(request.payments[0].message.as_deref(), Some("Thank you"));
+        assert_eq!(
+            request.payments[0].to_json()["memo"].as_str(),
+            Some("This is a simple memo.")
+        );
+        assert_eq!(request.payments[1].address, TRANSPARENT);
+        assert_eq!(request.payments[1].label.as_deref(), Some("Shop"));
+        assert_eq!(
+            request.send_tuples().unwrap()[1],
+            (TRANSPARENT, 100_000, None)
+        );
+
+        let reparsed = PaymentRequest::parse(&request.to_uri(), &ChainType::Testnet).unwrap();
+        assert_eq!(reparsed, request);
+    }
+
+    #[test]
+    fn invalid_uris() {
+        let parse = |uri: &str| PaymentRequest::parse(uri, &ChainType::Testnet).unwrap_err();
+        assert!(matches!(
+            parse(&format!("zcash:{TRANSPARENT}?memo=VGhpcw")),
+            ZingoLibError::PaymentUriMemo(0, _)
+        ));
+        assert!(matches!(
+            parse(&format!("zcash:{SAPLING}?address={SAPLING}")),
+            ZingoLibError::PaymentUriDuplicateParameter(_)
+        ));
+        assert!(matches!(
+            parse(&format!("zcash:{SAPLING}?amount.1=1")),
+            ZingoLibError::PaymentUriMissingAddress(1)
+        ));
+        assert!(matches!(
+            parse(&format!("zcash:{SAPLING}?req-future=1")),
+            ZingoLibError::PaymentUriUnsupportedRequirement(_)
+        ));
+        assert!(matches!(
+            parse(&format!("zcash:{SAPLING}?amount.01=1")),
+            ZingoLibError::InvalidPaymentUri(_)
+        ));
+        assert!(matches!(
+            parse("zcash:notanaddress"),
+            ZingoLibError::PaymentUriAddress(0, _)
+        ));
+        assert!(matches!(
+            PaymentRequest::parse(&format!("zcash:{SAPLING}"), &ChainType::Testnet)
+                .unwrap()
+                .send_tuples(),
+            Err(ZingoLibError::PaymentUriMissingAmount(0))
+        ));
+    }
+}
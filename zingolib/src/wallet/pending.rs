@@ -0,0 +1,167 @@
+//! The wallet's own sends that have been broadcast but not yet mined.
+//! Their raw bytes are kept, so that they can be rebroadcast if the server drops them
+//! from its mempool.
+use std::io::{self, Read, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use zcash_encoding::{Optional, Vector};
+use zcash_primitives::{consensus::BlockHeight, transaction::TxId};
+
+use super::utils;
+
+pub const DEFAULT_REBROADCAST_INTERVAL: u32 = 5;
+
+/// When pending sends are broadcast again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RebroadcastPolicy {
+    Never,
+    /// Rebroadcast once this many blocks have passed since the last broadcast
+    EveryBlocks(u32),
+}
+
+impl Default for RebroadcastPolicy {
+    fn default() -> Self {
+        RebroadcastPolicy::EveryBlocks(DEFAULT_REBROADCAST_INTERVAL)
+    }
+}
+
+impl std::str::FromStr for RebroadcastPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "never" => Ok(RebroadcastPolicy::Never),
+            blocks => match blocks.parse::<u32>() {
+                Ok(0) | Err(_) => Err(format!(
+                    "Unknown rebroadcast policy '{blocks}'. Expected 'never' or a number of blocks"
+                )),
+                Ok(blocks) => Ok(RebroadcastPolicy::EveryBlocks(blocks)),
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for RebroadcastPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RebroadcastPolicy::Never => write!(f, "never"),
+            RebroadcastPolicy::EveryBlocks(blocks) => write!(f, "{}", blocks),
+        }
+    }
+}
+
+pub struct PendingTransaction {
+    pub txid: TxId,
+    pub raw_transaction: Vec<u8>,
+    /// Zero if the transaction never expires
+    pub expiry_height: BlockHeight,
+    pub last_broadcast_height: BlockHeight,
+    pub broadcasts: u32,
+    /// The error of the latest rebroadcast, cleared when one succeeds
+    pub last_error: Option<String>,
+}
+
+impl PendingTransaction {
+    pub const fn serialized_version() -> u64 {
+        1
+    }
+
+    /// A transaction expires once the chain reaches its expiry height, as it can't be
+    /// mined in any later block.
+    pub fn is_expired(&self, latest_height: BlockHeight) -> bool {
+        u32::from(self.expiry_height) != 0 && latest_height >= self.expiry_height
+    }
+
+    pub fn is_due(&self, policy: RebroadcastPolicy, latest_height: BlockHeight) -> bool {
+        match policy {
+            RebroadcastPolicy::Never => false,
+            RebroadcastPolicy::EveryBlocks(blocks) => {
+                latest_height >= self.last_broadcast_height + blocks
+            }
+        }
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let version = reader.read_u64::<LittleEndian>()?;
+        if version > Self::serialized_version() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Don't know how to read pending transaction version {version}"),
+            ));
+        }
+        let mut txid_bytes = [0u8; 32];
+        reader.read_exact(&mut txid_bytes)?;
+        let raw_transaction = Vector::read(&mut reader, |r| r.read_u8())?;
+        let expiry_height = BlockHeight::from_u32(reader.read_u32::<LittleEndian>()?);
+        let last_broadcast_height = BlockHeight::from_u32(reader.read_u32::<LittleEndian>()?);
+        let broadcasts = reader.read_u32::<LittleEndian>()?;
+        let last_error = Optional::read(&mut reader, utils::read_string)?;
+
+        Ok(Self {
+            txid: TxId::from_bytes(txid_bytes),
+            raw_transaction,
+            expiry_height,
+            last_broadcast_height,
+            broadcasts,
+            last_error,
+        })
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_u64::<LittleEndian>(Self::serialized_version())?;
+        writer.write_all(self.txid.as_ref())?;
+        Vector::write(&mut writer, &self.raw_transaction, |w, byte| {
+            w.write_u8(*byte)
+        })?;
+        writer.write_u32::<LittleEndian>(u32::from(self.expiry_height))?;
+        writer.write_u32::<LittleEndian>(u32::from(self.last_broadcast_height))?;
+        writer.write_u32::<LittleEndian>(self.broadcasts)?;
+        Optional::write(&mut writer, self.last_error.as_ref(), |w, error| {
+            utils::write_string(w, error)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zcash_primitives::{consensus::BlockHeight, transaction::TxId};
+
+    use super::{PendingTransaction, RebroadcastPolicy};
+
+    fn pending() -> PendingTransaction {
+        PendingTransaction {
+            txid: TxId::from_bytes([7; 32]),
+            raw_transaction: vec![1, 2, 3],
+            expiry_height: BlockHeight::from_u32(140),
+            last_broadcast_height: BlockHeight::from_u32(100),
+            broadcasts: 2,
+            last_error: Some("transaction already in mempool".to_string()),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut buffer = vec![];
+        pending().write(&mut buffer).unwrap();
+        let read = PendingTransaction::read(buffer.as_slice()).unwrap();
+        assert_eq!(read.txid, pending().txid);
+        assert_eq!(read.raw_transaction, vec![1, 2, 3]);
+        assert_eq!(read.expiry_height, BlockHeight::from_u32(140));
+        assert_eq!(read.last_broadcast_height, BlockHeight::from_u32(100));
+        assert_eq!(read.broadcasts, 2);
+        assert_eq!(read.last_error, pending().last_error);
+    }
+
+    #[test]
+    fn due_and_expired() {
+        let policy = RebroadcastPolicy::EveryBlocks(5);
+        assert!(!pending().is_due(policy, BlockHeight::from_u32(104)));
+        assert!(pending().is_due(policy, BlockHeight::from_u32(105)));
+        assert!(!pending().is_due(RebroadcastPolicy::Never, BlockHeight::from_u32(139)));
+        assert!(!pending().is_expired(BlockHeight::from_u32(139)));
+        assert!(pending().is_expired(BlockHeight::from_u32(140)));
+        assert_eq!("never".parse(), Ok(RebroadcastPolicy::Never));
+        assert_eq!("3".parse(), Ok(RebroadcastPolicy::EveryBlocks(3)));
+        assert!("0".parse::<RebroadcastPolicy>().is_err());
+    }
+}
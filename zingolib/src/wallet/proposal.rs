@@ -0,0 +1,667 @@
+//! A transaction whose inputs, outputs and fee are fully determined, but which has not
+//! been proven or broadcast.  Consumers inspect a [`Proposal`] before committing to it.
+use json::{object, JsonValue};
+use zcash_client_backend::address;
+use zcash_primitives::{
+    memo::{Memo, MemoBytes},
+    transaction::components::amount::NonNegativeAmount,
+};
+use zingoconfig::ChainType;
+
+use crate::error::{ZingoLibError, ZingoLibResult};
+
+use super::{
+    data::{SpendableOrchardNote, SpendableSaplingNote},
+    fees::TransactionShape,
+    notes::TransparentNote,
+    receivers::ReceiverPreference,
+    Pool, Receivers,
+};
+
+#[derive(Clone, Debug)]
+pub struct Proposal {
+    pub orchard_notes: Vec<SpendableOrchardNote>,
+    pub sapling_notes: Vec<SpendableSaplingNote>,
+    pub utxos: Vec<TransparentNote>,
+    pub receivers: Receivers,
+    /// Value returned to the wallet's own change output. A transaction with no change has no
+    /// change output.
+    pub change: Option<u64>,
+    pub change_receiver: ChangeReceiver,
+    /// Chooses the receiver of each unified address among `receivers`
+    pub receiver_preference: ReceiverPreference,
+    /// The fee of the transaction this proposal builds: its ZIP-317 fee, plus any leftover
+    /// too small to be worth a change output
+    pub fee: u64,
+}
+
+/// The wallet's own receiver that change is returned to.
+#[derive(Clone, Copy, Debug)]
+pub enum ChangeReceiver {
+    Orchard(orchard::Address),
+    Sapling(sapling_crypto::PaymentAddress),
+}
+
+impl ChangeReceiver {
+    pub fn pool(&self) -> Pool {
+        match self {
+            ChangeReceiver::Orchard(_) => Pool::Orchard,
+            ChangeReceiver::Sapling(_) => Pool::Sapling,
+        }
+    }
+
+    /// Whether this is a receiver of one of `addresses`
+    pub fn is_receiver_of<'a>(
+        &self,
+        mut addresses: impl Iterator<Item = &'a address::UnifiedAddress>,
+    ) -> bool {
+        addresses.any(|ua| match self {
+            ChangeReceiver::Orchard(receiver) => ua.orchard() == Some(receiver),
+            ChangeReceiver::Sapling(receiver) => ua.sapling() == Some(receiver),
+        })
+    }
+
+    /// An orchard receiver is encoded as a unified address holding only it.
+    pub fn encode(&self, chain: &ChainType) -> String {
+        match self {
+            ChangeReceiver::Orchard(receiver) => {
+                address::UnifiedAddress::from_receivers(Some(*receiver), None, None)
+                    .expect("an orchard receiver to make a unified address")
+                    .encode(chain)
+            }
+            ChangeReceiver::Sapling(receiver) => address::Address::Sapling(*receiver).encode(chain),
+        }
+    }
+}
+
+/// Notes and utxos left out of a proposal because each is worth no more than the marginal
+/// fee of spending it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Dust {
+    pub count: usize,
+    pub value: u64,
+}
+
+impl Proposal {
+    /// The pool a recipient address is paid in, mirroring the receiver choice made when the
+    /// transaction is built.
+    pub fn receiving_pool(&self, recipient: &address::Address) -> Option<Pool> {
+        self.receiver_preference
+            .receiving_pool(recipient, &self.pools_spent())
+    }
+
+    pub fn total_selected(&self) -> u64 {
+        self.orchard_notes
+            .iter()
+            .map(|note| note.note.value().inner())
+            .sum::<u64>()
+            + self
+                .sapling_notes
+                .iter()
+                .map(|note| note.note.value().inner())
+                .sum::<u64>()
+            + self.utxos.iter().map(|utxo| utxo.value).sum::<u64>()
+    }
+
+    /// Makes `recipient` the only receiver, paid everything selected less the fee.
+    /// The error is the fee, when it would consume everything selected.
+    pub fn sweep_to(
+        &mut self,
+        recipient: address::Address,
+        memo: Option<MemoBytes>,
+    ) -> Result<(), u64> {
+        let total = self.total_selected();
+        if total <= self.fee {
+            return Err(self.fee);
+        }
+        self.receivers = vec![(
+            recipient,
+            NonNegativeAmount::from_u64(total - self.fee)
+                .expect("the selected notes to be worth a valid amount"),
+            memo,
+        )];
+        Ok(())
+    }
+
+    pub fn total_to_receivers(&self) -> u64 {
+        self.receivers
+            .iter()
+            .map(|(_, value, _)| u64::from(*value))
+            .sum()
+    }
+
+    /// The inputs and outputs the builder is asked for, to compute the fee without a builder.
+    pub fn shape(&self) -> TransactionShape {
+        TransactionShape {
+            transparent_inputs: self.utxos.len(),
+            sapling_spends: self.sapling_notes.len(),
+            orchard_spends: self.orchard_notes.len(),
+            ..self.receiver_preference.output_shape(
+                &self.receivers,
+                &self.pools_spent(),
+                self.change.map(|_| self.change_receiver.pool()),
+            )
+        }
+    }
+
+    /// Pools the selected inputs are spent from.
+    pub fn pools_spent(&self) -> Vec<Pool> {
+        let mut pools = vec![];
+        if !self.orchard_notes.is_empty() {
+            pools.push(Pool::Orchard);
+        }
+        if !self.sapling_notes.is_empty() {
+            pools.push(Pool::Sapling);
+        }
+        if !self.utxos.is_empty() {
+            pools.push(Pool::Transparent);
+        }
+        pools
+    }
+
+    /// Pools the recipients are paid in.
+    pub fn pools_received(&self) -> Vec<Pool> {
+        let mut pools = vec![];
+        for pool in self
+            .receivers
+            .iter()
+            .filter_map(|(recipient, _, _)| self.receiving_pool(recipient))
+        {
+            if !pools.contains(&pool) {
+                pools.push(pool);
+            }
+        }
+        pools
+    }
+
+    pub fn to_json(&self, chain: &ChainType) -> JsonValue {
+        object! {
+            "fee" => self.fee,
+            "change" => self.change.unwrap_or(0),
+            "change_pool" => JsonValue::from(self.change_receiver.pool()),
+            "total_to_receivers" => self.total_to_receivers(),
+            "total_selected" => self.total_selected(),
+            "orchard_notes" => self.orchard_notes.iter().map(|note| object! {
+                "created_in_txid" => note.transaction_id.to_string(),
+                "value" => note.note.value().inner(),
+            }).collect::<Vec<JsonValue>>(),
+            "sapling_notes" => self.sapling_notes.iter().map(|note| object! {
+                "created_in_txid" => note.transaction_id.to_string(),
+                "value" => note.note.value().inner(),
+            }).collect::<Vec<JsonValue>>(),
+            "utxos" => self.utxos.iter().map(|utxo| object! {
+                "created_in_txid" => utxo.txid.to_string(),
+                "output_index" => utxo.output_index,
+                "address" => utxo.address.clone(),
+                "value" => utxo.value,
+            }).collect::<Vec<JsonValue>>(),
+            "outputs" => self.receivers.iter().map(|(recipient, value, memo)| object! {
+                "address" => recipient.encode(chain),
+                "pool" => self.receiving_pool(recipient).map(JsonValue::from),
+                "value" => u64::from(*value),
+                "memo" => memo.clone().and_then(memo_text),
+            }).collect::<Vec<JsonValue>>(),
+            "pools_spent" => self.pools_spent().into_iter().map(JsonValue::from).collect::<Vec<JsonValue>>(),
+            "pools_received" => self.pools_received().into_iter().map(JsonValue::from).collect::<Vec<JsonValue>>(),
+        }
+    }
+}
+
+fn memo_text(memo: MemoBytes) -> Option<String> {
+    match Memo::try_from(memo) {
+        Ok(Memo::Text(text)) => Some(text.to_string()),
+        _ => None,
+    }
+}
+
+/// How much a transaction may reveal on chain, from most to least private.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PrivacyPolicy {
+    /// Only spend and pay within a single shielded pool.
+    FullPrivacy,
+    /// Allow value to move between shielded pools, which reveals the amount moved.
+    AllowRevealedAmounts,
+    /// Additionally allow transparent recipients.
+    AllowRevealedRecipients,
+    /// Additionally allow spending transparent funds.
+    AllowFullyTransparent,
+}
+
+impl std::str::FromStr for PrivacyPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "full_privacy" => Ok(PrivacyPolicy::FullPrivacy),
+            "allow_revealed_amounts" => Ok(PrivacyPolicy::AllowRevealedAmounts),
+            "allow_revealed_recipients" => Ok(PrivacyPolicy::AllowRevealedRecipients),
+            "allow_fully_transparent" => Ok(PrivacyPolicy::AllowFullyTransparent),
+            other => Err(format!(
+                "Unknown privacy policy '{other}'. Expected one of full_privacy, \
+                allow_revealed_amounts, allow_revealed_recipients, allow_fully_transparent"
+            )),
+        }
+    }
+}
+
+impl PrivacyPolicy {
+    /// The pools notes are selected from, in order, to pay `receivers` under this policy.
+    /// Under [`PrivacyPolicy::FullPrivacy`], notes are only selected from the pools the
+    /// recipients can be paid in, so that no value has to cross pools. Transparent funds are
+    /// only spent under [`PrivacyPolicy::AllowFullyTransparent`], after the shielded ones.
+    /// A transaction paying a TEX address only spends transparent funds.
+    pub(crate) fn note_selection_pools(
+        self,
+        receiver_preference: ReceiverPreference,
+        receivers: &Receivers,
+        transparent_source_only: bool,
+    ) -> ZingoLibResult<Vec<Pool>> {
+        if transparent_source_only {
+            if self < PrivacyPolicy::AllowFullyTransparent {
+                return ZingoLibError::InsufficientPrivacy(
+                    self,
+                    PrivacyBoundary::TransparentSource,
+                )
+                .handle();
+            }
+            return Ok(vec![Pool::Transparent]);
+        }
+        let default_pools = vec![Pool::Orchard, Pool::Sapling];
+        let mut pools = if self >= PrivacyPolicy::AllowRevealedAmounts {
+            default_pools
+        } else if receiver_preference == ReceiverPreference::MatchFunding {
+            // Any pool that pays every recipient in that same pool
+            let pools = [Pool::Orchard, Pool::Sapling]
+                .into_iter()
+                .filter(|pool| {
+                    receivers.iter().all(|(recipient, _, _)| {
+                        receiver_preference.receiving_pool(recipient, &[*pool]) == Some(*pool)
+                    })
+                })
+                .collect::<Vec<_>>();
+            if pools.is_empty() {
+                default_pools
+            } else {
+                pools
+            }
+        } else {
+            let mut receiving_pools = receivers
+                .iter()
+                .map(|(recipient, _, _)| receiver_preference.receiving_pool(recipient, &[]));
+            match receiving_pools.next() {
+                Some(Some(pool))
+                    if pool != Pool::Transparent
+                        && receiving_pools.all(|other| other == Some(pool)) =>
+                {
+                    vec![pool]
+                }
+                _ => default_pools,
+            }
+        };
+        if self == PrivacyPolicy::AllowFullyTransparent {
+            pools.push(Pool::Transparent);
+        }
+        Ok(pools)
+    }
+}
+
+impl std::fmt::Display for PrivacyPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PrivacyPolicy::FullPrivacy => write!(f, "full_privacy"),
+            PrivacyPolicy::AllowRevealedAmounts => write!(f, "allow_revealed_amounts"),
+            PrivacyPolicy::AllowRevealedRecipients => write!(f, "allow_revealed_recipients"),
+            PrivacyPolicy::AllowFullyTransparent => write!(f, "allow_fully_transparent"),
+        }
+    }
+}
+
+/// A privacy boundary a proposed transaction would cross.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PrivacyBoundary {
+    /// Shielded value would move from one pool to another.
+    PoolCrossing { from: Pool, to: Pool },
+    /// A recipient is paid in the transparent pool.
+    TransparentRecipient,
+    /// Transparent funds would be spent.
+    TransparentSource,
+}
+
+impl PrivacyBoundary {
+    /// The least permissive policy that allows crossing this boundary.
+    pub fn required_policy(&self) -> PrivacyPolicy {
+        match self {
+            PrivacyBoundary::PoolCrossing { .. } => PrivacyPolicy::AllowRevealedAmounts,
+            PrivacyBoundary::TransparentRecipient => PrivacyPolicy::AllowRevealedRecipients,
+            PrivacyBoundary::TransparentSource => PrivacyPolicy::AllowFullyTransparent,
+        }
+    }
+}
+
+impl std::fmt::Display for PrivacyBoundary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PrivacyBoundary::PoolCrossing { from, to } => write!(
+                f,
+                "value would cross from the {:?} pool to the {:?} pool, revealing the amount",
+                from, to
+            ),
+            PrivacyBoundary::TransparentRecipient => {
+                write!(f, "a recipient would be revealed in the transparent pool")
+            }
+            PrivacyBoundary::TransparentSource => {
+                write!(
+                    f,
+                    "transparent funds would be spent, revealing their source"
+                )
+            }
+        }
+    }
+}
+
+impl Proposal {
+    /// The pool change is returned to, if there is any change.
+    pub fn change_pool(&self) -> Option<Pool> {
+        match self.change {
+            Some(change) if change > 0 => Some(self.change_receiver.pool()),
+            _ => None,
+        }
+    }
+
+    /// Returns the first boundary this proposal crosses that `policy` forbids.
+    pub fn privacy_violation(&self, policy: PrivacyPolicy) -> Option<PrivacyBoundary> {
+        let pools_spent = self.pools_spent();
+        let mut pools_paid = self.pools_received();
+        if let Some(pool) = self.change_pool() {
+            if !pools_paid.contains(&pool) {
+                pools_paid.push(pool);
+            }
+        }
+        let mut boundaries = vec![];
+        if pools_spent.contains(&Pool::Transparent) {
+            boundaries.push(PrivacyBoundary::TransparentSource);
+        }
+        if pools_paid.contains(&Pool::Transparent) {
+            boundaries.push(PrivacyBoundary::TransparentRecipient);
+        }
+        for from in pools_spent
+            .iter()
+            .filter(|pool| **pool != Pool::Transparent)
+        {
+            for to in pools_paid.iter().filter(|pool| **pool != Pool::Transparent) {
+                if from != to {
+                    boundaries.push(PrivacyBoundary::PoolCrossing {
+                        from: *from,
+                        to: *to,
+                    });
+                }
+            }
+        }
+        boundaries
+            .into_iter()
+            .find(|boundary| boundary.required_policy() > policy)
+    }
+
+    pub fn check_privacy(&self, policy: PrivacyPolicy) -> ZingoLibResult<()> {
+        match self.privacy_violation(policy) {
+            Some(boundary) => ZingoLibError::InsufficientPrivacy(policy, boundary).handle(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sapling_crypto::{value::NoteValue, zip32::ExtendedSpendingKey, Rseed};
+    use zcash_client_backend::address;
+    use zcash_primitives::{
+        legacy::TransparentAddress,
+        transaction::{components::amount::NonNegativeAmount, TxId},
+    };
+
+    use super::{ChangeReceiver, PrivacyBoundary, PrivacyPolicy, Proposal};
+    use crate::{
+        error::ZingoLibError,
+        wallet::{
+            data::SpendableSaplingNote, notes::TransparentNote, receivers::ReceiverPreference,
+            Pool, Receivers,
+        },
+    };
+
+    fn empty_proposal() -> Proposal {
+        let fvk = orchard::keys::FullViewingKey::from(
+            &orchard::keys::SpendingKey::from_bytes([1; 32]).unwrap(),
+        );
+        Proposal {
+            orchard_notes: vec![],
+            sapling_notes: vec![],
+            utxos: vec![],
+            receivers: vec![],
+            change: None,
+            change_receiver: ChangeReceiver::Orchard(
+                fvk.address_at(0u32, orchard::keys::Scope::Internal),
+            ),
+            receiver_preference: ReceiverPreference::default(),
+            fee: 10_000,
+        }
+    }
+
+    fn utxo(value: u64) -> TransparentNote {
+        crate::test_framework::TransparentNoteBuilder::default()
+            .value(value)
+            .build()
+    }
+
+    fn transparent_recipient() -> address::Address {
+        address::Address::Transparent(TransparentAddress::PublicKey([7; 20]))
+    }
+
+    fn orchard_recipient() -> address::Address {
+        let fvk = orchard::keys::FullViewingKey::from(
+            &orchard::keys::SpendingKey::from_bytes([2; 32]).unwrap(),
+        );
+        address::Address::Unified(
+            address::UnifiedAddress::from_receivers(
+                Some(fvk.address_at(0u32, orchard::keys::Scope::External)),
+                None,
+                None,
+            )
+            .unwrap(),
+        )
+    }
+
+    fn sapling_recipient() -> address::Address {
+        address::Address::Sapling(ExtendedSpendingKey::master(&[2; 32]).default_address().1)
+    }
+
+    fn paying(recipient: address::Address) -> Receivers {
+        vec![(
+            recipient,
+            NonNegativeAmount::from_u64(10_000).unwrap(),
+            None,
+        )]
+    }
+
+    #[test]
+    fn note_selection_pools_follow_the_policy() {
+        let pools = |policy: PrivacyPolicy, recipient: address::Address| {
+            policy
+                .note_selection_pools(ReceiverPreference::OrchardFirst, &paying(recipient), false)
+                .unwrap()
+        };
+        // Only the recipient's own pool, so that no value crosses pools
+        assert_eq!(
+            pools(PrivacyPolicy::FullPrivacy, orchard_recipient()),
+            vec![Pool::Orchard]
+        );
+        assert_eq!(
+            pools(PrivacyPolicy::FullPrivacy, sapling_recipient()),
+            vec![Pool::Sapling]
+        );
+        assert_eq!(
+            pools(PrivacyPolicy::AllowRevealedAmounts, sapling_recipient()),
+            vec![Pool::Orchard, Pool::Sapling]
+        );
+        assert_eq!(
+            pools(
+                PrivacyPolicy::AllowRevealedRecipients,
+                transparent_recipient()
+            ),
+            vec![Pool::Orchard, Pool::Sapling]
+        );
+        // Transparent funds only once the shielded ones are used up
+        assert_eq!(
+            pools(PrivacyPolicy::AllowFullyTransparent, orchard_recipient()),
+            vec![Pool::Orchard, Pool::Sapling, Pool::Transparent]
+        );
+    }
+
+    #[test]
+    fn tex_recipients_need_fully_transparent() {
+        let receivers = paying(transparent_recipient());
+        assert!(matches!(
+            PrivacyPolicy::AllowRevealedRecipients.note_selection_pools(
+                ReceiverPreference::OrchardFirst,
+                &receivers,
+                true
+            ),
+            Err(ZingoLibError::InsufficientPrivacy(
+                PrivacyPolicy::AllowRevealedRecipients,
+                PrivacyBoundary::TransparentSource
+            ))
+        ));
+        assert_eq!(
+            PrivacyPolicy::AllowFullyTransparent
+                .note_selection_pools(ReceiverPreference::OrchardFirst, &receivers, true)
+                .unwrap(),
+            vec![Pool::Transparent]
+        );
+    }
+
+    fn sapling_note(value: u64) -> SpendableSaplingNote {
+        let recipient = ExtendedSpendingKey::master(&[3; 32]).default_address().1;
+        SpendableSaplingNote {
+            transaction_id: TxId::from_bytes([0; 32]),
+            nullifier: sapling_crypto::Nullifier([0; 32]),
+            diversifier: *recipient.diversifier(),
+            note: sapling_crypto::Note::from_parts(
+                recipient,
+                NoteValue::from_raw(value),
+                Rseed::AfterZip212([0; 32]),
+            ),
+            witnessed_position: incrementalmerkletree::Position::from(0u64),
+            extsk: None,
+        }
+    }
+
+    #[test]
+    fn consolidating_sapling_into_orchard_crosses_pools() {
+        let fvk = orchard::keys::FullViewingKey::from(
+            &orchard::keys::SpendingKey::from_bytes([2; 32]).unwrap(),
+        );
+        let own_address = address::Address::Unified(
+            address::UnifiedAddress::from_receivers(
+                Some(fvk.address_at(0u32, orchard::keys::Scope::External)),
+                Some(ExtendedSpendingKey::master(&[2; 32]).default_address().1),
+                None,
+            )
+            .unwrap(),
+        );
+        let mut consolidation = Proposal {
+            sapling_notes: vec![sapling_note(40_000), sapling_note(30_000)],
+            receiver_preference: ReceiverPreference::OrchardFirst,
+            ..empty_proposal()
+        };
+        consolidation.sweep_to(own_address, None).unwrap();
+        assert_eq!(
+            consolidation.privacy_violation(PrivacyPolicy::FullPrivacy),
+            Some(PrivacyBoundary::PoolCrossing {
+                from: Pool::Sapling,
+                to: Pool::Orchard
+            })
+        );
+        assert_eq!(
+            consolidation.privacy_violation(PrivacyPolicy::AllowRevealedAmounts),
+            None
+        );
+
+        // Matching the funding pool keeps the notes in sapling
+        consolidation.receiver_preference = ReceiverPreference::MatchFunding;
+        assert_eq!(consolidation.pools_received(), vec![Pool::Sapling]);
+        assert_eq!(
+            consolidation.privacy_violation(PrivacyPolicy::FullPrivacy),
+            None
+        );
+    }
+
+    #[test]
+    fn sweep_fails_when_the_fee_takes_everything() {
+        let mut proposal = Proposal {
+            utxos: vec![utxo(10_000)],
+            ..empty_proposal()
+        };
+        assert_eq!(
+            proposal.sweep_to(transparent_recipient(), None),
+            Err(10_000)
+        );
+        assert!(proposal.receivers.is_empty());
+    }
+
+    #[test]
+    fn transparent_source_requires_fully_transparent() {
+        let proposal = Proposal {
+            utxos: vec![crate::test_framework::TransparentNoteBuilder::default()
+                .value(100_000)
+                .build()],
+            change: Some(90_000),
+            ..empty_proposal()
+        };
+        assert_eq!(
+            proposal.privacy_violation(PrivacyPolicy::AllowRevealedRecipients),
+            Some(PrivacyBoundary::TransparentSource)
+        );
+        assert_eq!(
+            proposal.privacy_violation(PrivacyPolicy::AllowFullyTransparent),
+            None
+        );
+    }
+
+    #[test]
+    fn empty_change_crosses_no_pool() {
+        let proposal = empty_proposal();
+        assert_eq!(proposal.change_pool(), None);
+        let proposal = Proposal {
+            change: Some(0),
+            ..empty_proposal()
+        };
+        assert_eq!(proposal.change_pool(), None);
+        let proposal = Proposal {
+            change: Some(1),
+            ..empty_proposal()
+        };
+        assert_eq!(proposal.change_pool(), Some(Pool::Orchard));
+    }
+
+    #[test]
+    fn policies_are_ordered_by_permissiveness() {
+        assert!(PrivacyPolicy::FullPrivacy < PrivacyPolicy::AllowRevealedAmounts);
+        assert!(PrivacyPolicy::AllowRevealedAmounts < PrivacyPolicy::AllowRevealedRecipients);
+        assert!(PrivacyPolicy::AllowRevealedRecipients < PrivacyPolicy::AllowFullyTransparent);
+        assert_eq!(
+            PrivacyBoundary::PoolCrossing {
+                from: Pool::Sapling,
+                to: Pool::Orchard
+            }
+            .required_policy(),
+            PrivacyPolicy::AllowRevealedAmounts
+        );
+        for policy in [
+            PrivacyPolicy::FullPrivacy,
+            PrivacyPolicy::AllowRevealedAmounts,
+            PrivacyPolicy::AllowRevealedRecipients,
+            PrivacyPolicy::AllowFullyTransparent,
+        ] {
+            assert_eq!(policy.to_string().parse::<PrivacyPolicy>(), Ok(policy));
+        }
+    }
+}
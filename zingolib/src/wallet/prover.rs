@@ -0,0 +1,79 @@
+//! The prover of the Sapling parts of a transaction, as chosen by [`ProverKind`].
+//! The Sapling prover traits can't be made into trait objects, so the choice is an enum
+//! that builds with the prover it holds.
+use rand::rngs::OsRng;
+use sapling_crypto::builder::ProverProgress;
+use zcash_primitives::transaction::{
+    builder::{BuildResult, Builder, Error},
+    fees::FeeRule,
+};
+use zcash_proofs::prover::LocalTxProver;
+use zingoconfig::{ChainType, ProverKind};
+
+pub enum SaplingProver {
+    Local(LocalTxProver),
+    /// See [`ProverKind::Mock`]
+    #[cfg(any(test, feature = "mock-prover"))]
+    Mock,
+}
+
+impl SaplingProver {
+    /// The prover for `kind`. The Sapling parameters are only read for the local prover.
+    pub fn new<F>(kind: ProverKind, read_sapling_params: F) -> Result<Self, String>
+    where
+        F: FnOnce() -> Result<(Vec<u8>, Vec<u8>), String>,
+    {
+        match kind {
+            ProverKind::Local => {
+                let (sapling_output, sapling_spend) = read_sapling_params()?;
+                Ok(SaplingProver::Local(LocalTxProver::from_bytes(
+                    &sapling_spend,
+                    &sapling_output,
+                )))
+            }
+            #[cfg(any(test, feature = "mock-prover"))]
+            ProverKind::Mock => Ok(SaplingProver::Mock),
+            #[cfg(not(any(test, feature = "mock-prover")))]
+            ProverKind::Mock => {
+                Err("This build has no mock prover. Enable the mock-prover feature.".to_string())
+            }
+        }
+    }
+
+    pub(crate) fn build<U: ProverProgress, FR: FeeRule>(
+        &self,
+        tx_builder: Builder<'_, ChainType, U>,
+        fee_rule: &FR,
+    ) -> Result<BuildResult, Error<FR::Error>> {
+        match self {
+            SaplingProver::Local(prover) => tx_builder.build(OsRng, prover, prover, fee_rule),
+            #[cfg(any(test, feature = "mock-prover"))]
+            SaplingProver::Mock => tx_builder.build(
+                OsRng,
+                &sapling_crypto::prover::mock::MockSpendProver,
+                &sapling_crypto::prover::mock::MockOutputProver,
+                fee_rule,
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zingoconfig::ProverKind;
+
+    use super::SaplingProver;
+
+    #[test]
+    fn only_the_local_prover_reads_params() {
+        let no_params = || Err("no params".to_string());
+        assert!(matches!(
+            SaplingProver::new(ProverKind::Mock, no_params),
+            Ok(SaplingProver::Mock)
+        ));
+        assert!(matches!(
+            SaplingProver::new(ProverKind::Local, no_params),
+            Err(e) if e == "no params"
+        ));
+    }
+}
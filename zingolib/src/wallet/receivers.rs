@@ -0,0 +1,210 @@
+//! Which receiver of a unified address is paid, and the transparent addresses that may
+//! only be paid from transparent funds.
+use bech32::FromBase32;
+use zcash_client_backend::address;
+use zcash_primitives::legacy::TransparentAddress;
+use zingoconfig::ChainType;
+
+use super::{fees::TransactionShape, Pool, Receivers};
+
+/// Which receiver of a unified address is paid, when it has more than one.
+/// A unified address without a receiver in a shielded pool this wallet knows is paid
+/// transparently under any preference.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReceiverPreference {
+    /// Orchard, then Sapling, then transparent
+    #[default]
+    OrchardFirst,
+    /// Sapling, then Orchard, then transparent
+    SaplingFirst,
+    /// The shielded pool the transaction spends from, so that no value crosses pools.
+    /// Notes are selected from a single pool that every recipient can be paid in, when
+    /// that pool alone can fund the transaction.
+    MatchFunding,
+}
+
+impl std::str::FromStr for ReceiverPreference {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "orchard_first" => Ok(ReceiverPreference::OrchardFirst),
+            "sapling_first" => Ok(ReceiverPreference::SaplingFirst),
+            "match_funding" => Ok(ReceiverPreference::MatchFunding),
+            other => Err(format!(
+                "Unknown receiver preference '{other}'. Expected one of orchard_first, \
+                sapling_first, match_funding"
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for ReceiverPreference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReceiverPreference::OrchardFirst => write!(f, "orchard_first"),
+            ReceiverPreference::SaplingFirst => write!(f, "sapling_first"),
+            ReceiverPreference::MatchFunding => write!(f, "match_funding"),
+        }
+    }
+}
+
+impl ReceiverPreference {
+    pub(crate) fn as_u8(&self) -> u8 {
+        match self {
+            ReceiverPreference::OrchardFirst => 0,
+            ReceiverPreference::SaplingFirst => 1,
+            ReceiverPreference::MatchFunding => 2,
+        }
+    }
+
+    pub(crate) fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(ReceiverPreference::OrchardFirst),
+            1 => Some(ReceiverPreference::SaplingFirst),
+            2 => Some(ReceiverPreference::MatchFunding),
+            _ => None,
+        }
+    }
+
+    /// The pools a unified address is paid in, most preferred first.
+    fn order(&self, funding_pools: &[Pool]) -> Vec<Pool> {
+        let mut order = match self {
+            ReceiverPreference::OrchardFirst => vec![Pool::Orchard, Pool::Sapling],
+            ReceiverPreference::SaplingFirst => vec![Pool::Sapling, Pool::Orchard],
+            ReceiverPreference::MatchFunding => {
+                let mut order = funding_pools
+                    .iter()
+                    .copied()
+                    .filter(|pool| *pool != Pool::Transparent)
+                    .collect::<Vec<_>>();
+                for pool in [Pool::Orchard, Pool::Sapling] {
+                    if !order.contains(&pool) {
+                        order.push(pool);
+                    }
+                }
+                order
+            }
+        };
+        order.push(Pool::Transparent);
+        order
+    }
+
+    /// The outputs that pay `receivers`, and change in `change_pool`, in a transaction
+    /// spending from `funding_pools`. Note selection and the builder both count outputs
+    /// this way, so that the fee a selection is sized for is the fee the build needs.
+    pub fn output_shape(
+        &self,
+        receivers: &Receivers,
+        funding_pools: &[Pool],
+        change_pool: Option<Pool>,
+    ) -> TransactionShape {
+        let mut shape = TransactionShape::default();
+        for pool in receivers
+            .iter()
+            .filter_map(|(recipient, _, _)| self.receiving_pool(recipient, funding_pools))
+            .chain(change_pool)
+        {
+            match pool {
+                Pool::Orchard => shape.orchard_outputs += 1,
+                Pool::Sapling => shape.sapling_outputs += 1,
+                Pool::Transparent => shape.transparent_outputs += 1,
+            }
+        }
+        shape
+    }
+
+    /// The pool `recipient` is paid in by a transaction spending from `funding_pools`.
+    /// `None` for a unified address without any receiver this wallet can pay.
+    pub fn receiving_pool(
+        &self,
+        recipient: &address::Address,
+        funding_pools: &[Pool],
+    ) -> Option<Pool> {
+        match recipient {
+            address::Address::Transparent(_) => Some(Pool::Transparent),
+            address::Address::Sapling(_) => Some(Pool::Sapling),
+            address::Address::Unified(ua) => {
+                self.order(funding_pools)
+                    .into_iter()
+                    .find(|pool| match pool {
+                        Pool::Orchard => ua.orchard().is_some(),
+                        Pool::Sapling => ua.sapling().is_some(),
+                        Pool::Transparent => ua.transparent().is_some(),
+                    })
+            }
+        }
+    }
+}
+
+/// Decodes a ZIP 320 TEX address: a P2PKH address that may only be paid by transactions
+/// spending transparent funds alone, so that the payment can't be linked to shielded funds.
+pub fn decode_tex_address(chain: &ChainType, encoded: &str) -> Option<TransparentAddress> {
+    let (hrp, data, variant) = bech32::decode(encoded).ok()?;
+    if hrp != chain.hrp_tex_address() || variant != bech32::Variant::Bech32m {
+        return None;
+    }
+    let hash = <[u8; 20]>::try_from(Vec::<u8>::from_base32(&data).ok()?).ok()?;
+    Some(TransparentAddress::PublicKey(hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use bech32::ToBase32;
+    use zcash_primitives::legacy::TransparentAddress;
+    use zingoconfig::ChainType;
+
+    use super::{decode_tex_address, ReceiverPreference};
+    use crate::wallet::Pool;
+
+    #[test]
+    fn transparent_receivers_are_paid_last() {
+        for preference in [
+            ReceiverPreference::OrchardFirst,
+            ReceiverPreference::SaplingFirst,
+            ReceiverPreference::MatchFunding,
+        ] {
+            assert_eq!(
+                preference.order(&[Pool::Transparent]).last(),
+                Some(&Pool::Transparent)
+            );
+            assert_eq!(preference.order(&[Pool::Transparent]).len(), 3);
+            assert_eq!(
+                ReceiverPreference::from_u8(preference.as_u8()),
+                Some(preference)
+            );
+            assert_eq!(preference.to_string().parse(), Ok(preference));
+        }
+        assert_eq!(
+            ReceiverPreference::SaplingFirst.order(&[Pool::Orchard]),
+            vec![Pool::Sapling, Pool::Orchard, Pool::Transparent]
+        );
+    }
+
+    #[test]
+    fn match_funding_prefers_the_funding_pool() {
+        let preference = ReceiverPreference::MatchFunding;
+        assert_eq!(
+            preference.order(&[Pool::Sapling, Pool::Transparent]),
+            vec![Pool::Sapling, Pool::Orchard, Pool::Transparent]
+        );
+        assert_eq!(
+            preference.order(&[]),
+            ReceiverPreference::OrchardFirst.order(&[])
+        );
+    }
+
+    #[test]
+    fn decodes_tex_addresses_of_the_chain() {
+        let encoded =
+            bech32::encode("textest", [7u8; 20].to_base32(), bech32::Variant::Bech32m).unwrap();
+        assert_eq!(
+            decode_tex_address(&ChainType::Testnet, &encoded),
+            Some(TransparentAddress::PublicKey([7; 20]))
+        );
+        assert_eq!(decode_tex_address(&ChainType::Mainnet, &encoded), None);
+        let bech32 =
+            bech32::encode("textest", [7u8; 20].to_base32(), bech32::Variant::Bech32).unwrap();
+        assert_eq!(decode_tex_address(&ChainType::Testnet, &bech32), None);
+    }
+}
@@ -681,22 +681,40 @@ where
         // Include only non-0 value notes that haven't been spent, or haven't been included
         // in an unconfirmed spend yet.
         if Self::check_spendability_of_note(note_and_metadata, spend_key) {
-            // Filter out notes with nullifier or position not yet known
-            if let (Some(nf), Some(pos)) = (
-                note_and_metadata.nullifier(),
-                note_and_metadata.witnessed_position(),
-            ) {
-                Some(Self::from_parts_unchecked(
-                    transaction_id,
-                    nf,
-                    *note_and_metadata.diversifier(),
-                    note_and_metadata.note().clone(),
-                    *pos,
-                    spend_key,
-                ))
-            } else {
-                None
-            }
+            Self::from_positioned_note(transaction_id, note_and_metadata, spend_key)
+        } else {
+            None
+        }
+    }
+
+    /// As [`Self::from`], but for a note without its spend key, so that a view-only wallet
+    /// can propose transactions for the wallet holding the key to sign.
+    fn from_viewed(transaction_id: TxId, note_and_metadata: &D::WalletNote) -> Option<Self> {
+        if Self::check_unspent(note_and_metadata) {
+            Self::from_positioned_note(transaction_id, note_and_metadata, None)
+        } else {
+            None
+        }
+    }
+
+    /// Filters out notes with nullifier or position not yet known
+    fn from_positioned_note(
+        transaction_id: TxId,
+        note_and_metadata: &D::WalletNote,
+        spend_key: Option<&D::SpendingKey>,
+    ) -> Option<Self> {
+        if let (Some(nf), Some(pos)) = (
+            note_and_metadata.nullifier(),
+            note_and_metadata.witnessed_position(),
+        ) {
+            Some(Self::from_parts_unchecked(
+                transaction_id,
+                nf,
+                *note_and_metadata.diversifier(),
+                note_and_metadata.note().clone(),
+                *pos,
+                spend_key,
+            ))
         } else {
             None
         }
@@ -706,9 +724,12 @@ where
         note_and_metadata: &D::WalletNote,
         spend_key: Option<&D::SpendingKey>,
     ) -> bool {
+        Self::check_unspent(note_and_metadata) && spend_key.is_some()
+    }
+
+    fn check_unspent(note_and_metadata: &D::WalletNote) -> bool {
         note_and_metadata.spent().is_none()
             && note_and_metadata.pending_spent().is_none()
-            && spend_key.is_some()
             && note_and_metadata.value() != 0
     }
     /// The checks needed are shared between domains, and thus are performed in the
@@ -194,11 +194,8 @@ impl TransactionRecord {
 
         let block = BlockHeight::from_u32(reader.read_i32::<LittleEndian>()? as u32);
 
-        let unconfirmed = if version <= 20 {
-            false
-        } else {
-            reader.read_u8()? == 1
-        };
+        // 0 for confirmed, 1 for broadcast, and since version 25, 2 for expired
+        let status_tag = if version <= 20 { 0 } else { reader.read_u8()? };
 
         let datetime = if version >= 4 {
             reader.read_u64::<LittleEndian>()?
@@ -232,7 +229,7 @@ impl TransactionRecord {
         };
 
         // Outgoing metadata was only added in version 2
-        let outgoing_metadata = Vector::read(&mut reader, |r| OutgoingTxData::read(r))?;
+        let outgoing_metadata = Vector::read(&mut reader, |r| OutgoingTxData::read(r, version))?;
 
         let _full_tx_scanned = reader.read_u8()? > 0;
 
@@ -261,7 +258,10 @@ impl TransactionRecord {
                 Ok(orchard::note::Nullifier::from_bytes(&n).unwrap())
             })?
         };
-        let status = ConfirmationStatus::from_blockheight_and_unconfirmed_bool(block, unconfirmed);
+        let status = match status_tag {
+            2 if version >= 25 => ConfirmationStatus::Expired(block),
+            tag => ConfirmationStatus::from_blockheight_and_unconfirmed_bool(block, tag == 1),
+        };
         Ok(Self {
             status,
             datetime,
@@ -280,7 +280,7 @@ impl TransactionRecord {
     }
 
     pub fn serialized_version() -> u64 {
-        23
+        25
     }
 
     pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
@@ -289,7 +289,11 @@ impl TransactionRecord {
         let block: u32 = self.status.get_height().into();
         writer.write_i32::<LittleEndian>(block as i32)?;
 
-        writer.write_u8(if !self.status.is_confirmed() { 1 } else { 0 })?;
+        writer.write_u8(match self.status {
+            ConfirmationStatus::Confirmed(_) => 0,
+            ConfirmationStatus::Broadcast(_) => 1,
+            ConfirmationStatus::Expired(_) => 2,
+        })?;
 
         writer.write_u64::<LittleEndian>(self.datetime)?;
 
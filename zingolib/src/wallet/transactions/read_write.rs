@@ -140,8 +140,10 @@ impl TransactionMetadataSet {
                 .iter()
                 .collect::<Vec<(&TxId, &TransactionRecord)>>();
             // Don't write down metadata for transactions in the mempool, we'll rediscover
-            // it on reload
-            transaction_metadatas.retain(|metadata| metadata.1.status.is_confirmed());
+            // it on reload. Expired sends are kept, as nothing would rediscover them.
+            transaction_metadatas.retain(|metadata| {
+                metadata.1.status.is_confirmed() || metadata.1.status.is_expired()
+            });
             transaction_metadatas.sort_by(|a, b| a.0.partial_cmp(b.0).unwrap());
 
             Vector::write(&mut writer, &transaction_metadatas, |w, (k, v)| {
@@ -157,6 +159,7 @@ impl TransactionMetadataSet {
 mod tests {
     use super::*;
     use std::io::Cursor;
+    use zingo_status::confirmation_status::ConfirmationStatus;
 
     #[tokio::test]
     async fn test_write() {
@@ -183,4 +186,45 @@ mod tests {
         // Verify the buffer contents here
         // ...
     }
+
+    #[tokio::test]
+    async fn expired_sends_are_saved() {
+        let (pending, expired, confirmed) = (
+            TxId::from_bytes([1; 32]),
+            TxId::from_bytes([2; 32]),
+            TxId::from_bytes([3; 32]),
+        );
+        let mut tms = TransactionMetadataSet {
+            current: HashMap::from([
+                (
+                    pending,
+                    TransactionRecord::new(ConfirmationStatus::Broadcast(90.into()), 0, &pending),
+                ),
+                (
+                    expired,
+                    TransactionRecord::new(ConfirmationStatus::Broadcast(90.into()), 0, &expired),
+                ),
+                (
+                    confirmed,
+                    TransactionRecord::new(ConfirmationStatus::Confirmed(80.into()), 0, &confirmed),
+                ),
+            ]),
+            witness_trees: None,
+        };
+        assert_eq!(tms.mark_expired(&[(expired, 130.into())]), vec![expired]);
+
+        let mut buffer = vec![];
+        tms.write(&mut buffer).await.unwrap();
+        let read =
+            TransactionMetadataSet::read(buffer.as_slice(), &WalletCapability::default()).unwrap();
+        assert!(!read.current.contains_key(&pending));
+        assert_eq!(
+            read.current[&expired].status,
+            ConfirmationStatus::Expired(130.into())
+        );
+        assert_eq!(
+            read.current[&confirmed].status,
+            ConfirmationStatus::Confirmed(80.into())
+        );
+    }
 }
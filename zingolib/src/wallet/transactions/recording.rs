@@ -27,6 +27,34 @@ impl TransactionMetadataSet {
         for txid in &txids_to_remove {
             self.current.remove(txid);
         }
+        self.release_spends(&txids_to_remove);
+    }
+
+    /// Marks each transaction that is not confirmed as expired at its expiry height, and
+    /// releases what it was spending, so that the funds can be spent again.
+    /// Spends are released even if the transaction is no longer recorded, as broadcast
+    /// transactions are not saved with the wallet but the notes they spent are. Once
+    /// marked, the transactions are saved, until the chain is past reorging them.
+    /// Returns the transactions that were newly marked.
+    pub(crate) fn mark_expired(&mut self, expired: &[(TxId, BlockHeight)]) -> Vec<TxId> {
+        let mut newly_expired = vec![];
+        for (txid, expiry_height) in expired {
+            if let Some(transaction_metadata) = self.current.get_mut(txid) {
+                if transaction_metadata.status.is_broadcast() {
+                    transaction_metadata.status = ConfirmationStatus::Expired(*expiry_height);
+                    // An expired transaction created no outputs
+                    transaction_metadata.sapling_notes.clear();
+                    transaction_metadata.orchard_notes.clear();
+                    transaction_metadata.transparent_notes.clear();
+                    newly_expired.push(*txid);
+                }
+            }
+        }
+        self.release_spends(&expired.iter().map(|(txid, _)| *txid).collect::<Vec<_>>());
+        newly_expired
+    }
+
+    fn release_spends(&mut self, txids_to_remove: &[TxId]) {
         self.current.values_mut().for_each(|transaction_metadata| {
             // Update UTXOs to rollback any spent utxos
             transaction_metadata
@@ -45,8 +73,8 @@ impl TransactionMetadataSet {
                     }
                 })
         });
-        self.remove_domain_specific_txids::<SaplingDomain>(&txids_to_remove);
-        self.remove_domain_specific_txids::<OrchardDomain>(&txids_to_remove);
+        self.remove_domain_specific_txids::<SaplingDomain>(txids_to_remove);
+        self.remove_domain_specific_txids::<OrchardDomain>(txids_to_remove);
     }
 
     fn remove_domain_specific_txids<D: DomainWalletExt>(&mut self, txids_to_remove: &[TxId])
@@ -117,6 +145,7 @@ impl TransactionMetadataSet {
             .iter()
             .filter(|(_, transaction_metadata)| {
                 transaction_metadata.status.is_broadcast_before(&cutoff)
+                    || matches!(transaction_metadata.status, ConfirmationStatus::Expired(expiry_height) if expiry_height < cutoff)
             }) // this transaction was submitted to the mempool before the cutoff and has not been confirmed. we deduce that it has expired.
             .map(|(_, transaction_metadata)| transaction_metadata.txid)
             .collect::<Vec<_>>();
@@ -0,0 +1,269 @@
+//! A transaction whose inputs, outputs, fee, anchors and witnesses are all fixed, but
+//! which is neither signed nor proven.
+//! A view-only wallet creates one, and carries it to a wallet holding the spending key,
+//! which signs and proves it without needing any chain state of its own.
+use std::io::{self, Read, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use incrementalmerkletree::{MerklePath, Position};
+use json::{object, JsonValue};
+use zcash_encoding::{Optional, Vector};
+use zcash_primitives::{
+    consensus::BlockHeight,
+    memo::{Memo, MemoBytes},
+    merkle_tree::HashSer,
+};
+use zingoconfig::ChainType;
+
+use super::{
+    data::COMMITMENT_TREE_LEVELS,
+    keys::unified::WalletCapability,
+    notes::TransparentNote,
+    proposal::ChangeReceiver,
+    receivers::ReceiverPreference,
+    traits::{FromBytes, ReadableWriteable, ToBytes},
+    Pool,
+};
+
+pub struct UnsignedSaplingSpend {
+    pub diversifier: sapling_crypto::Diversifier,
+    pub note: sapling_crypto::Note,
+    pub merkle_path: MerklePath<sapling_crypto::Node, COMMITMENT_TREE_LEVELS>,
+}
+
+pub struct UnsignedOrchardSpend {
+    pub diversifier: orchard::keys::Diversifier,
+    pub note: orchard::note::Note,
+    pub merkle_path: MerklePath<orchard::tree::MerkleHashOrchard, COMMITMENT_TREE_LEVELS>,
+}
+
+pub struct UnsignedTransaction {
+    pub submission_height: BlockHeight,
+    pub sapling_anchor: sapling_crypto::Anchor,
+    pub orchard_anchor: orchard::Anchor,
+    pub sapling_spends: Vec<UnsignedSaplingSpend>,
+    pub orchard_spends: Vec<UnsignedOrchardSpend>,
+    pub utxos: Vec<TransparentNote>,
+    /// Encoded recipient addresses, with the value and memo sent to each
+    pub outputs: Vec<(String, u64, Option<MemoBytes>)>,
+    pub change: Option<u64>,
+    pub change_receiver: ChangeReceiver,
+    /// The preference the outputs were proposed with, so that signing pays the same
+    /// receivers of each unified address
+    pub receiver_preference: ReceiverPreference,
+    pub fee: u64,
+}
+
+impl UnsignedTransaction {
+    pub const fn serialized_version() -> u64 {
+        2
+    }
+
+    /// Notes are read with the addresses of `wallet_capability`, so only the wallet the
+    /// notes belong to can read them back correctly.
+    pub fn read<R: Read>(mut reader: R, wallet_capability: &WalletCapability) -> io::Result<Self> {
+        let version = reader.read_u64::<LittleEndian>()?;
+        if version > Self::serialized_version() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Unsigned transaction version {} is from a newer version of zingo",
+                    version
+                ),
+            ));
+        }
+        let submission_height = BlockHeight::from_u32(reader.read_u32::<LittleEndian>()?);
+        let sapling_anchor =
+            Option::from(sapling_crypto::Anchor::from_bytes(read_bytes(&mut reader)?)).ok_or(
+                io::Error::new(io::ErrorKind::InvalidData, "Bad sapling anchor"),
+            )?;
+        let orchard_anchor =
+            Option::from(orchard::Anchor::from_bytes(read_bytes(&mut reader)?)).ok_or(
+                io::Error::new(io::ErrorKind::InvalidData, "Bad orchard anchor"),
+            )?;
+        let sapling_spends = Vector::read(&mut reader, |r| {
+            let diversifier = sapling_crypto::Diversifier::from_bytes(read_bytes(&mut *r)?);
+            Ok(UnsignedSaplingSpend {
+                diversifier,
+                note: ReadableWriteable::read(&mut *r, (diversifier, wallet_capability))?,
+                merkle_path: read_merkle_path(r)?,
+            })
+        })?;
+        let orchard_spends = Vector::read(&mut reader, |r| {
+            let diversifier = orchard::keys::Diversifier::from_bytes(read_bytes(&mut *r)?);
+            Ok(UnsignedOrchardSpend {
+                diversifier,
+                note: ReadableWriteable::read(&mut *r, (diversifier, wallet_capability))?,
+                merkle_path: read_merkle_path(r)?,
+            })
+        })?;
+        let utxos = Vector::read(&mut reader, |r| TransparentNote::read(r))?;
+        let outputs = Vector::read(&mut reader, |r| {
+            let address = String::from_utf8(Vector::read(&mut *r, |r| r.read_u8())?)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            let value = r.read_u64::<LittleEndian>()?;
+            let memo = Optional::read(&mut *r, |r| {
+                MemoBytes::from_bytes(&read_bytes::<_, 512>(r)?)
+                    .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Bad memo"))
+            })?;
+            Ok((address, value, memo))
+        })?;
+        let change = Optional::read(&mut reader, |r| r.read_u64::<LittleEndian>())?;
+        let change_receiver = match reader.read_u8()? {
+            0 => Option::from(orchard::Address::from_raw_address_bytes(&read_bytes(
+                &mut reader,
+            )?))
+            .map(ChangeReceiver::Orchard),
+            1 => sapling_crypto::PaymentAddress::from_bytes(&read_bytes(&mut reader)?)
+                .map(ChangeReceiver::Sapling),
+            _ => None,
+        }
+        .ok_or(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Bad change receiver",
+        ))?;
+        let fee = reader.read_u64::<LittleEndian>()?;
+        let receiver_preference = if version > 1 {
+            let v = reader.read_u8()?;
+            ReceiverPreference::from_u8(v).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Bad receiver preference {}", v),
+                )
+            })?
+        } else {
+            ReceiverPreference::OrchardFirst
+        };
+
+        Ok(Self {
+            submission_height,
+            sapling_anchor,
+            orchard_anchor,
+            sapling_spends,
+            orchard_spends,
+            utxos,
+            outputs,
+            change,
+            change_receiver,
+            receiver_preference,
+            fee,
+        })
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_u64::<LittleEndian>(Self::serialized_version())?;
+        writer.write_u32::<LittleEndian>(u32::from(self.submission_height))?;
+        writer.write_all(&self.sapling_anchor.to_bytes())?;
+        writer.write_all(&self.orchard_anchor.to_bytes())?;
+        Vector::write(&mut writer, &self.sapling_spends, |w, spend| {
+            w.write_all(&spend.diversifier.to_bytes())?;
+            spend.note.write(&mut *w)?;
+            write_merkle_path(w, &spend.merkle_path)
+        })?;
+        Vector::write(&mut writer, &self.orchard_spends, |w, spend| {
+            w.write_all(&spend.diversifier.to_bytes())?;
+            spend.note.write(&mut *w)?;
+            write_merkle_path(w, &spend.merkle_path)
+        })?;
+        Vector::write(&mut writer, &self.utxos, |w, utxo| utxo.write(w))?;
+        Vector::write(&mut writer, &self.outputs, |w, (address, value, memo)| {
+            Vector::write(&mut *w, address.as_bytes(), |w, byte| w.write_u8(*byte))?;
+            w.write_u64::<LittleEndian>(*value)?;
+            Optional::write(&mut *w, memo.as_ref(), |w, memo| {
+                w.write_all(memo.as_slice())
+            })
+        })?;
+        Optional::write(&mut writer, self.change, |w, change| {
+            w.write_u64::<LittleEndian>(change)
+        })?;
+        match self.change_receiver {
+            ChangeReceiver::Orchard(address) => {
+                writer.write_u8(0)?;
+                writer.write_all(&address.to_raw_address_bytes())?;
+            }
+            ChangeReceiver::Sapling(address) => {
+                writer.write_u8(1)?;
+                writer.write_all(&address.to_bytes())?;
+            }
+        }
+        writer.write_u64::<LittleEndian>(self.fee)?;
+        writer.write_u8(self.receiver_preference.as_u8())
+    }
+
+    /// Pools the spends are taken from, in the order of [`super::proposal::Proposal::pools_spent`].
+    pub fn pools_spent(&self) -> Vec<Pool> {
+        let mut pools = vec![];
+        if !self.orchard_spends.is_empty() {
+            pools.push(Pool::Orchard);
+        }
+        if !self.sapling_spends.is_empty() {
+            pools.push(Pool::Sapling);
+        }
+        if !self.utxos.is_empty() {
+            pools.push(Pool::Transparent);
+        }
+        pools
+    }
+
+    pub fn total_spent(&self) -> u64 {
+        self.sapling_spends
+            .iter()
+            .map(|spend| spend.note.value().inner())
+            .chain(
+                self.orchard_spends
+                    .iter()
+                    .map(|spend| spend.note.value().inner()),
+            )
+            .chain(self.utxos.iter().map(|utxo| utxo.value))
+            .sum()
+    }
+
+    /// What the transaction pays, for review before signing. The change is shown with the
+    /// address it returns to, which signing checks is the signer's own.
+    pub fn to_json(&self, chain: &ChainType) -> JsonValue {
+        object! {
+            "submission_height" => u32::from(self.submission_height),
+            "total_spent" => self.total_spent(),
+            "fee" => self.fee,
+            "change" => self.change.unwrap_or(0),
+            "change_address" => self.change.map(|_| self.change_receiver.encode(chain)),
+            "change_pool" => self.change.map(|_| JsonValue::from(self.change_receiver.pool())),
+            "outputs" => self.outputs.iter().map(|(address, value, memo)| object! {
+                "address" => address.clone(),
+                "value" => *value,
+                "memo" => memo.clone().and_then(|memo| match Memo::try_from(memo) {
+                    Ok(Memo::Text(text)) => Some(text.to_string()),
+                    _ => None,
+                }),
+            }).collect::<Vec<JsonValue>>(),
+        }
+    }
+}
+
+fn read_bytes<R: Read, const N: usize>(mut reader: R) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_merkle_path<R: Read, H: HashSer>(
+    mut reader: R,
+) -> io::Result<MerklePath<H, COMMITMENT_TREE_LEVELS>> {
+    let position = Position::from(reader.read_u64::<LittleEndian>()?);
+    let path_elems = (0..COMMITMENT_TREE_LEVELS)
+        .map(|_| H::read(&mut reader))
+        .collect::<io::Result<Vec<_>>>()?;
+    MerklePath::from_parts(path_elems, position)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Bad merkle path"))
+}
+
+fn write_merkle_path<W: Write, H: HashSer>(
+    mut writer: W,
+    path: &MerklePath<H, COMMITMENT_TREE_LEVELS>,
+) -> io::Result<()> {
+    writer.write_u64::<LittleEndian>(u64::from(path.position()))?;
+    for node in path.path_elems() {
+        node.write(&mut writer)?;
+    }
+    Ok(())
+}